@@ -0,0 +1,252 @@
+//! On-disk cache of API responses used for conditional requests.
+//!
+//! The validators returned by the server (`ETag` and `Last-Modified`) are
+//! stored next to the response body, and sent back as `If-None-Match` and
+//! `If-Modified-Since` on the next request. Some CDN layers in front of the
+//! API only support the date based validator, so both are kept.
+//!
+//! `404 Not Found` is cached for a while too, so a mistyped postal code
+//! doesn't send a request to the API on every run. `403 Forbidden` isn't,
+//! so fixed credentials work right away.
+use core::time::Duration;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bring_client::NorwegianPostalCode,
+    http_client::{
+        header::{HeaderMap, HeaderValue, ETAG, LAST_MODIFIED},
+        StatusCode,
+    },
+    io_error_to_string,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// A cached API response with its validators.
+pub struct CachedResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    pub body: serde_json::Value,
+}
+
+impl CachedResponse {
+    #[must_use]
+    /// Create a cache entry from response headers and body.
+    pub fn from_headers(headers: &HeaderMap, body: serde_json::Value) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(ToString::to_string)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            body,
+        }
+    }
+
+    #[must_use]
+    /// Whether the entry has any validator that can be used in a conditional request.
+    pub const fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// A cached error response.
+pub struct CachedError {
+    /// HTTP status of the response.
+    pub status: u16,
+    /// When the response was received.
+    pub received: DateTime<Utc>,
+}
+
+impl CachedError {
+    #[must_use]
+    /// Whether the entry is still used at `now` when errors are cached for `ttl`.
+    ///
+    /// ```
+    /// use core::time::Duration;
+    ///
+    /// use postgang::bring_client::cache::CachedError;
+    ///
+    /// let received = "2024-09-02T12:00:00Z".parse().unwrap();
+    /// let error = CachedError { status: 404, received };
+    /// let ttl = Duration::from_secs(600);
+    /// assert!(error.is_fresh(ttl, "2024-09-02T12:05:00Z".parse().unwrap()));
+    /// assert!(!error.is_fresh(ttl, "2024-09-02T12:10:00Z".parse().unwrap()));
+    /// assert!(!error.is_fresh(Duration::ZERO, received));
+    /// ```
+    pub fn is_fresh(&self, ttl: Duration, now: DateTime<Utc>) -> bool {
+        chrono::Duration::from_std(ttl).is_ok_and(|ttl| now < self.received + ttl)
+    }
+}
+
+impl core::fmt::Display for CachedError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let status = StatusCode::from_u16(self.status)
+            .map_or_else(|_| self.status.to_string(), |status| status.to_string());
+        write!(
+            f,
+            "HTTP status {status} cached from {}",
+            self.received.format("%Y-%m-%d %H:%M:%S UTC")
+        )
+    }
+}
+
+impl core::error::Error for CachedError {}
+
+#[derive(Debug, Clone)]
+/// Directory holding one cached response per postal code.
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    #[must_use]
+    pub const fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, postal_code: NorwegianPostalCode) -> PathBuf {
+        self.dir.join(format!("{postal_code}.json"))
+    }
+
+    #[must_use]
+    /// Load the cached response for `postal_code`.
+    ///
+    /// A missing or unreadable entry is treated as a cache miss.
+    pub fn load(&self, postal_code: NorwegianPostalCode) -> Option<CachedResponse> {
+        let path = self.path(postal_code);
+        let file = std::fs::File::open(&path).ok()?;
+        match serde_json::from_reader(file) {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                log::warn!("Ignoring invalid cache entry {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    /// Store `entry` as the cached response for `postal_code`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory or file can't be written.
+    pub fn store(
+        &self,
+        postal_code: NorwegianPostalCode,
+        entry: &CachedResponse,
+    ) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir).map_err(|err| io_error_to_string(&err, &self.dir))?;
+        let path = self.path(postal_code);
+        let file = std::fs::File::create(&path).map_err(|err| io_error_to_string(&err, &path))?;
+        serde_json::to_writer(file, entry).map_err(|err| format!("{err}: {}", path.display()))
+    }
+
+    fn error_path(&self, postal_code: NorwegianPostalCode) -> PathBuf {
+        self.dir.join(format!("{postal_code}.error.json"))
+    }
+
+    #[must_use]
+    /// Load the cached error response for `postal_code`.
+    pub fn load_error(&self, postal_code: NorwegianPostalCode) -> Option<CachedError> {
+        let file = std::fs::File::open(self.error_path(postal_code)).ok()?;
+        serde_json::from_reader(file).ok()
+    }
+
+    /// Store `error` as the cached error response for `postal_code`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory or file can't be written.
+    pub fn store_error(
+        &self,
+        postal_code: NorwegianPostalCode,
+        error: &CachedError,
+    ) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir).map_err(|err| io_error_to_string(&err, &self.dir))?;
+        let path = self.error_path(postal_code);
+        let file = std::fs::File::create(&path).map_err(|err| io_error_to_string(&err, &path))?;
+        serde_json::to_writer(file, error).map_err(|err| format!("{err}: {}", path.display()))
+    }
+
+    /// Remove the cached error response for `postal_code`, if any.
+    pub fn clear_error(&self, postal_code: NorwegianPostalCode) {
+        let path = self.error_path(postal_code);
+        match std::fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                log::warn!("{}", io_error_to_string(&err, &path));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CachedError, CachedResponse, ResponseCache};
+    use crate::{
+        bring_client::NorwegianPostalCode,
+        http_client::header::{HeaderMap, HeaderValue, ETAG, LAST_MODIFIED},
+    };
+
+    #[test]
+    fn test_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"abc\""));
+        headers.insert(
+            LAST_MODIFIED,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        let entry = CachedResponse::from_headers(&headers, serde_json::Value::Null);
+        assert_eq!(entry.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(
+            entry.last_modified.as_deref(),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        assert!(entry.has_validators());
+        assert!(
+            !CachedResponse::from_headers(&HeaderMap::new(), serde_json::Value::Null)
+                .has_validators()
+        );
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let dir = std::env::temp_dir().join(format!("postgang-cache-{}", std::process::id()));
+        let cache = ResponseCache::new(dir.clone());
+        let code = NorwegianPostalCode::try_from("7800").unwrap();
+        assert!(cache.load(code).is_none());
+        let entry = CachedResponse {
+            etag: None,
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            body: serde_json::json!({"delivery_dates": ["2015-10-21"]}),
+        };
+        cache.store(code, &entry).unwrap();
+        assert_eq!(cache.load(code), Some(entry));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_store_and_clear_error() {
+        let dir = std::env::temp_dir().join(format!("postgang-errors-{}", std::process::id()));
+        let cache = ResponseCache::new(dir.clone());
+        let code = NorwegianPostalCode::try_from("0001").unwrap();
+        let error = CachedError {
+            status: 404,
+            received: "2024-09-02T12:00:00Z".parse().unwrap(),
+        };
+        cache.store_error(code, &error).unwrap();
+        assert_eq!(cache.load_error(code), Some(error));
+        assert!(cache.load(code).is_none());
+        cache.clear_error(code);
+        assert!(cache.load_error(code).is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+//! Recorded API responses, for `api --record` and `api --replay`.
+//!
+//! A fixture file is a JSON object from postal code to the status and JSON
+//! body of the response, so it can be checked in and edited by hand.
+use alloc::collections::BTreeMap;
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bring_client::{
+        mailbox_delivery_dates::{read_input, ErrorDetails, UnknownPostalCode},
+        NorwegianPostalCode,
+    },
+    http_client::StatusCode,
+    io_error_to_string,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+/// A recorded response.
+pub struct Fixture {
+    pub status: u16,
+    /// The JSON body, `null` if it was empty or not JSON.
+    pub body: serde_json::Value,
+}
+
+impl Fixture {
+    #[must_use]
+    /// Record a response with `status` and `body`.
+    pub fn new(status: StatusCode, body: &[u8]) -> Self {
+        Self {
+            status: status.as_u16(),
+            body: serde_json::from_slice(body).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A replayed error response.
+pub struct ReplayedError {
+    pub status: u16,
+    pub details: Option<ErrorDetails>,
+}
+
+impl core::fmt::Display for ReplayedError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let status = StatusCode::from_u16(self.status)
+            .map_or_else(|_| self.status.to_string(), |status| status.to_string());
+        write!(f, "Replayed HTTP status {status}")?;
+        match &self.details {
+            Some(details) => write!(f, ": {details}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl core::error::Error for ReplayedError {}
+
+#[derive(Debug)]
+/// Responses recorded to or replayed from a file.
+pub struct Fixtures {
+    path: PathBuf,
+    responses: Mutex<BTreeMap<NorwegianPostalCode, Fixture>>,
+}
+
+impl Fixtures {
+    /// Read the fixtures in `path` for replaying.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read, is larger than `max_size`
+    /// bytes or isn't a fixture file.
+    pub fn load(path: &Path, max_size: u64) -> Result<Self, String> {
+        let value = read_input(Some(path), max_size).map_err(|err| err.to_string())?;
+        let responses =
+            serde_json::from_value(value).map_err(|err| format!("{err}: {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            responses: Mutex::new(responses),
+        })
+    }
+
+    /// Record to `path`, keeping the responses for other postal codes
+    /// already in it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be read.
+    pub fn open(path: &Path, max_size: u64) -> Result<Self, String> {
+        if path.exists() {
+            Self::load(path, max_size)
+        } else {
+            Ok(Self {
+                path: path.to_path_buf(),
+                responses: Mutex::default(),
+            })
+        }
+    }
+
+    fn responses(&self) -> std::sync::MutexGuard<'_, BTreeMap<NorwegianPostalCode, Fixture>> {
+        self.responses
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    #[must_use]
+    /// The response recorded for `postal_code`.
+    pub fn get(&self, postal_code: NorwegianPostalCode) -> Option<Fixture> {
+        self.responses().get(&postal_code).cloned()
+    }
+
+    /// Record `fixture` for `postal_code` and write the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn record(&self, postal_code: NorwegianPostalCode, fixture: Fixture) -> Result<(), String> {
+        let mut responses = self.responses();
+        responses.insert(postal_code, fixture);
+        let mut json = serde_json::to_string_pretty(&*responses)
+            .map_err(|err| format!("{err}: {}", self.path.display()))?;
+        json.push('\n');
+        std::fs::write(&self.path, json).map_err(|err| io_error_to_string(&err, &self.path))
+    }
+
+    /// The body recorded for `postal_code`, or its error.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UnknownPostalCode`] for a recorded `404`, [`ReplayedError`]
+    /// for other error statuses, and an error if nothing was recorded.
+    pub fn replay(
+        &self,
+        postal_code: NorwegianPostalCode,
+    ) -> Result<serde_json::Value, Box<dyn core::error::Error>> {
+        let fixture = self.get(postal_code).ok_or_else(|| {
+            format!(
+                "No recorded response for {postal_code} in {}",
+                self.path.display()
+            )
+        })?;
+        match fixture.status {
+            200..=299 => Ok(fixture.body),
+            404 => Err(UnknownPostalCode(postal_code).into()),
+            status => Err(ReplayedError {
+                status,
+                details: ErrorDetails::parse(&fixture.body.to_string()),
+            }
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Fixture, Fixtures, ReplayedError};
+    use crate::{
+        bring_client::{
+            mailbox_delivery_dates::{UnknownPostalCode, DEFAULT_MAX_SIZE},
+            NorwegianPostalCode,
+        },
+        http_client::StatusCode,
+    };
+
+    #[test]
+    fn test_record_and_replay() {
+        let path =
+            std::env::temp_dir().join(format!("postgang-fixtures-{}.json", std::process::id()));
+        let code = NorwegianPostalCode::try_from("7800").unwrap();
+        let unknown = NorwegianPostalCode::try_from("0001").unwrap();
+        let broken = NorwegianPostalCode::try_from("0002").unwrap();
+        let fixtures = Fixtures::open(&path, DEFAULT_MAX_SIZE).unwrap();
+        fixtures
+            .record(
+                code,
+                Fixture::new(StatusCode::OK, br#"{"delivery_dates":[]}"#),
+            )
+            .unwrap();
+        fixtures
+            .record(unknown, Fixture::new(StatusCode::NOT_FOUND, b""))
+            .unwrap();
+        fixtures
+            .record(
+                broken,
+                Fixture::new(StatusCode::BAD_GATEWAY, br#"{"message":"Try again"}"#),
+            )
+            .unwrap();
+
+        let fixtures = Fixtures::load(&path, DEFAULT_MAX_SIZE).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            fixtures.replay(code).unwrap(),
+            serde_json::json!({"delivery_dates": []})
+        );
+        assert!(fixtures
+            .replay(unknown)
+            .unwrap_err()
+            .is::<UnknownPostalCode>());
+        let err = fixtures.replay(broken).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ReplayedError>().map(|err| err.status),
+            Some(502)
+        );
+        assert_eq!(
+            err.to_string(),
+            "Replayed HTTP status 502 Bad Gateway: Try again"
+        );
+        assert!(fixtures
+            .replay(NorwegianPostalCode::try_from("7801").unwrap())
+            .is_err());
+    }
+}
@@ -1,19 +1,31 @@
 //! Mailbox delivery dates API.
 
-use core::fmt::Debug;
-use std::path::PathBuf;
-
-use chrono::NaiveDate;
-use reqwest::{
-    header::{HeaderMap, HeaderValue},
-    Client,
+use core::{fmt::Debug, time::Duration};
+use std::{
+    io::Read as _,
+    path::{Path, PathBuf},
+    sync::Mutex,
 };
+
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    bring_client::{ApiKey, ApiUid, NorwegianPostalCode, NORWAY},
+    bring_client::{
+        cache::{CachedError, CachedResponse, ResponseCache},
+        fixtures::{Fixture, Fixtures},
+        retry::RetryPolicy,
+        ApiKey, ApiUid, IpFamily, NorwegianPostalCode, Resolve, NORWAY,
+    },
+    http_client::{
+        self,
+        header::{HeaderMap, HeaderValue, DATE, IF_MODIFIED_SINCE, IF_NONE_MATCH},
+        Client, Redacted, Response, StatusCode,
+    },
     io_error_to_string,
+    locale::{Locale, Message},
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -23,6 +35,16 @@ pub struct DeliveryDate {
     pub date: NaiveDate,
 }
 
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for DeliveryDate {
+    /// A delivery date between 1970 and 2099.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let date = NaiveDate::from_num_days_from_ce_opt(u.int_in_range(719_163..=766_644)?)
+            .ok_or(arbitrary::Error::IncorrectFormat)?;
+        Ok(Self::new(u.arbitrary()?, date))
+    }
+}
+
 impl DeliveryDate {
     #[must_use]
     pub const fn new(postal_code: NorwegianPostalCode, date: NaiveDate) -> Self {
@@ -30,70 +52,993 @@ impl DeliveryDate {
     }
 }
 
+#[must_use]
+/// The earliest delivery on or after `today`.
+///
+/// ```
+/// use chrono::{DateTime, NaiveDate, Utc};
+/// use postgang::bring_client::mailbox_delivery_dates::{next_delivery, DeliveryDate};
+/// use postgang::bring_client::NorwegianPostalCode;
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let day = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+/// let dates = [
+///     DeliveryDate::new(postal_code, day(6)),
+///     DeliveryDate::new(postal_code, day(4)),
+///     DeliveryDate::new(postal_code, day(2)),
+/// ];
+/// assert_eq!(next_delivery(&dates, day(3)).map(|d| d.date), Some(day(4)));
+/// assert_eq!(next_delivery(&dates, day(7)).map(|d| d.date), None);
+/// ```
+pub fn next_delivery(delivery_dates: &[DeliveryDate], today: NaiveDate) -> Option<DeliveryDate> {
+    delivery_dates
+        .iter()
+        .filter(|delivery_date| delivery_date.date >= today)
+        .min_by_key(|delivery_date| (delivery_date.date, delivery_date.postal_code))
+        .copied()
+}
+
+#[derive(Debug)]
+/// Getting the delivery dates for a postal code failed.
+pub struct FetchError {
+    pub postal_code: NorwegianPostalCode,
+    pub source: Box<dyn core::error::Error>,
+}
+
+impl core::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}: {}", self.postal_code, self.source)
+    }
+}
+
+impl core::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The message and trace id of an error response from the API.
+pub struct ErrorDetails {
+    pub message: Option<String>,
+    pub trace_id: Option<String>,
+}
+
+impl ErrorDetails {
+    #[must_use]
+    /// Find the message and trace id in the JSON body of an error response.
+    ///
+    /// They are looked for at the top level and in the first entry of an
+    /// `errors` array.
+    ///
+    /// ```
+    /// use postgang::bring_client::mailbox_delivery_dates::ErrorDetails;
+    ///
+    /// let details =
+    ///     ErrorDetails::parse(r#"{"errors":[{"message":"Invalid API key"}],"traceId":"abc"}"#)
+    ///         .unwrap();
+    /// assert_eq!(details.message.as_deref(), Some("Invalid API key"));
+    /// assert_eq!(details.trace_id.as_deref(), Some("abc"));
+    /// assert_eq!(details.to_string(), "Invalid API key (trace id abc)");
+    /// assert!(ErrorDetails::parse("<html>Bad Gateway</html>").is_none());
+    /// ```
+    pub fn parse(body: &str) -> Option<Self> {
+        let body: serde_json::Value = serde_json::from_str(body).ok()?;
+        let first_error = body.get("errors").and_then(|errors| errors.get(0));
+        let field = |names: &[&str]| {
+            first_error.into_iter().chain([&body]).find_map(|value| {
+                names
+                    .iter()
+                    .find_map(|name| value.get(name)?.as_str())
+                    .map(ToString::to_string)
+            })
+        };
+        let details = Self {
+            message: field(&["message", "description", "errorMessage", "error"]),
+            trace_id: field(&["traceId", "trace_id", "requestId"]),
+        };
+        (details.message.is_some() || details.trace_id.is_some()).then_some(details)
+    }
+}
+
+impl core::fmt::Display for ErrorDetails {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match (&self.message, &self.trace_id) {
+            (Some(message), Some(trace_id)) => write!(f, "{message} (trace id {trace_id})"),
+            (Some(message), None) => f.write_str(message),
+            (None, Some(trace_id)) => write!(f, "trace id {trace_id}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// The API answered with an error status.
+pub struct ApiError {
+    pub source: http_client::Error,
+    /// From the body of the response.
+    pub details: Option<ErrorDetails>,
+}
+
+impl core::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.details {
+            Some(details) => write!(f, "{}: {details}", self.source),
+            None => write!(f, "{}", self.source),
+        }
+    }
+}
+
+impl core::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[derive(Debug)]
+/// The API rejected the credentials.
+pub struct AuthError(pub ApiError);
+
+impl core::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let explanation = Locale::English.message(Message::WrongCredentials);
+        write!(f, "{}\n{explanation}", self.0)
+    }
+}
+
+impl core::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+#[derive(Debug)]
+/// The API doesn't know the postal code, it answered `404 Not Found`.
+pub struct UnknownPostalCode(pub NorwegianPostalCode);
+
+impl core::fmt::Display for UnknownPostalCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&Locale::English.message(Message::UnknownPostalCode(self.0)))
+    }
+}
+
+impl core::error::Error for UnknownPostalCode {}
+
 #[derive(Serialize, Deserialize, Debug)]
 /// Represents JSON structure from the API.
 pub struct ApiResponse {
     pub delivery_dates: Vec<NaiveDate>,
 }
 
+/// JSON Schema describing the input accepted from files.
+pub const SCHEMA: &str = include_str!("../../delivery-dates.schema.json");
+
+const fn json_type(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+/// Decode file input as UTF-8, skipping a byte order mark.
+///
+/// ```
+/// use postgang::bring_client::mailbox_delivery_dates::decode_input;
+///
+/// assert_eq!(decode_input(b"\xef\xbb\xbf[]").unwrap(), "[]");
+/// assert_eq!(
+///     decode_input(b"[\"2024-09-0\xff\"]").unwrap_err(),
+///     "Invalid UTF-8 at byte 11"
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `bytes` is not valid UTF-8.
+pub fn decode_input(bytes: &[u8]) -> Result<&str, String> {
+    const BOM: &[u8] = b"\xef\xbb\xbf";
+    let skipped = if bytes.starts_with(BOM) { BOM.len() } else { 0 };
+    core::str::from_utf8(&bytes[skipped..])
+        .map_err(|err| format!("Invalid UTF-8 at byte {}", skipped + err.valid_up_to()))
+}
+
+/// Largest API response or input read by default, 1 MiB.
+pub const DEFAULT_MAX_SIZE: u64 = 1 << 20;
+
+/// Where the Bring API is, unless [`DeliveryDays::with_base_url`] says
+/// otherwise.
+pub const API_URL: &str = "https://api.bring.com";
+
+fn too_large(max_size: u64) -> String {
+    format!("Larger than the maximum of {max_size} bytes, see --max-response-size")
+}
+
+/// Read all of `reader`, failing if it has more than `max_size` bytes.
+fn read_limited(reader: impl std::io::Read, max_size: u64) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader
+        .take(max_size.saturating_add(1))
+        .read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > max_size {
+        return Err(std::io::Error::other(too_large(max_size)));
+    }
+    Ok(bytes)
+}
+
+/// Read the body of `resp`, failing if it has more than `max_size` bytes.
+///
+/// The body is read a chunk at a time, so an oversized response is not
+/// kept in memory.
+pub(crate) async fn read_body(
+    mut resp: Response,
+    max_size: u64,
+) -> Result<Vec<u8>, Box<dyn core::error::Error>> {
+    if resp
+        .content_length()
+        .is_some_and(|length| length > max_size)
+    {
+        return Err(too_large(max_size).into());
+    }
+    let mut body = Vec::new();
+    loop {
+        let Some(chunk) = resp.chunk().await? else {
+            break;
+        };
+        if (body.len() + chunk.len()) as u64 > max_size {
+            return Err(too_large(max_size).into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Read UTF-8 text from `path`, or from stdin if `None`.
+///
+/// # Errors
+///
+/// Returns an error if the input could not be read, is larger than
+/// `max_size` bytes or is not valid UTF-8.
+pub fn read_text(
+    path: Option<&Path>,
+    max_size: u64,
+) -> Result<String, Box<dyn core::error::Error>> {
+    let bytes = if let Some(path) = path {
+        std::fs::File::open(path)
+            .and_then(|file| read_limited(file, max_size))
+            .map_err(|err| io_error_to_string(&err, path))?
+    } else {
+        read_limited(std::io::stdin(), max_size).map_err(|err| format!("stdin: {err}"))?
+    };
+    Ok(decode_input(&bytes)
+        .map_err(|err| format!("{}: {err}", input_name(path)))?
+        .to_string())
+}
+
+fn input_name(path: Option<&Path>) -> String {
+    path.map_or_else(|| "stdin".into(), |path| path.display().to_string())
+}
+
+/// Read file input from `path`, or from stdin if `None`.
+///
+/// # Errors
+///
+/// Returns an error if the input could not be read, is larger than
+/// `max_size` bytes or is not valid JSON.
+pub fn read_input(
+    path: Option<&Path>,
+    max_size: u64,
+) -> Result<serde_json::Value, Box<dyn core::error::Error>> {
+    let content = read_text(path, max_size)?;
+    Ok(serde_json::from_str(&content).map_err(|err| format!("{}: {err}", input_name(path)))?)
+}
+
+#[must_use]
+/// Convert file input to the shape of an API response.
+///
+/// Files may contain either an API response or a plain array of dates.
+///
+/// ```
+/// use postgang::bring_client::mailbox_delivery_dates::normalize_input;
+/// use serde_json::json;
+///
+/// assert_eq!(
+///     normalize_input(json!(["2024-09-02"])),
+///     json!({"delivery_dates": ["2024-09-02"]})
+/// );
+/// assert_eq!(
+///     normalize_input(json!({"delivery_dates": ["2024-09-02"]})),
+///     json!({"delivery_dates": ["2024-09-02"]})
+/// );
+/// ```
+pub fn normalize_input(value: serde_json::Value) -> serde_json::Value {
+    if value.is_array() {
+        serde_json::json!({ "delivery_dates": value })
+    } else {
+        value
+    }
+}
+
+/// Validate `value` against [`SCHEMA`].
+///
+/// Returns a list of errors, each prefixed with the JSON pointer of the
+/// offending value.
+///
+/// ```
+/// use postgang::bring_client::mailbox_delivery_dates::validate;
+/// use serde_json::json;
+///
+/// assert!(validate(&json!({"delivery_dates": ["2024-09-02"]})).is_empty());
+/// assert_eq!(
+///     validate(&json!({"delivery_dates": ["2024-09-02", 3, "2024-02-30"]})),
+///     vec![
+///         "/delivery_dates/1: expected a date string, got number",
+///         "/delivery_dates/2: invalid date \"2024-02-30\": input is out of range",
+///     ]
+/// );
+/// assert!(validate(&json!(["2024-09-02"])).is_empty());
+/// assert_eq!(
+///     validate(&json!([1])),
+///     vec!["/0: expected a date string, got number"]
+/// );
+/// assert_eq!(
+///     validate(&json!("2024-09-02")),
+///     vec!["/: expected an object with a delivery_dates array or an array, got string"]
+/// );
+/// ```
+#[must_use]
+pub fn validate(value: &serde_json::Value) -> Vec<String> {
+    if let Some(dates) = value.as_array() {
+        return validate_dates("", dates);
+    }
+    let Some(object) = value.as_object() else {
+        return vec![format!(
+            "/: expected an object with a delivery_dates array or an array, got {}",
+            json_type(value)
+        )];
+    };
+    let Some(dates) = object.get("delivery_dates") else {
+        return vec!["/: missing required property delivery_dates".to_string()];
+    };
+    let Some(dates) = dates.as_array() else {
+        return vec![format!(
+            "/delivery_dates: expected an array, got {}",
+            json_type(dates)
+        )];
+    };
+    validate_dates("/delivery_dates", dates)
+}
+
+fn validate_dates(pointer: &str, dates: &[serde_json::Value]) -> Vec<String> {
+    dates
+        .iter()
+        .enumerate()
+        .filter_map(|(i, date)| match date {
+            serde_json::Value::String(s) => {
+                let valid_format = s.len() == 10
+                    && s.bytes().enumerate().all(|(i, c)| {
+                        if i == 4 || i == 7 {
+                            c == b'-'
+                        } else {
+                            c.is_ascii_digit()
+                        }
+                    });
+                if valid_format {
+                    s.parse::<NaiveDate>()
+                        .err()
+                        .map(|err| format!("{pointer}/{i}: invalid date {s:?}: {err}"))
+                } else {
+                    Some(format!(
+                        "{pointer}/{i}: invalid date {s:?}: expected YYYY-MM-DD"
+                    ))
+                }
+            }
+            _ => Some(format!(
+                "{pointer}/{i}: expected a date string, got {}",
+                json_type(date)
+            )),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+/// Options for the API client.
+pub struct ApiOptions {
+    /// Cache responses and use conditional requests when set.
+    pub cache: Option<ResponseCache>,
+    /// Only connect using this address family when set.
+    pub ip_family: Option<IpFamily>,
+    /// How long idle connections are kept open for reuse.
+    pub pool_idle_timeout: Option<Duration>,
+    /// How long connecting to the API may take.
+    pub connect_timeout: Option<Duration>,
+    /// How long each request may take in total, from connecting to reading
+    /// the whole response.
+    pub timeout: Option<Duration>,
+    /// Maximum number of idle connections kept per host.
+    pub pool_max_idle_per_host: Option<usize>,
+    /// How failed requests are retried.
+    pub retry: RetryPolicy,
+    /// Fixed addresses used instead of DNS.
+    pub resolve: Vec<Resolve>,
+    /// How long `404` responses are cached, zero to not cache them.
+    ///
+    /// `403` isn't cached, as it means wrong credentials, which are fixed for
+    /// all postal codes at once.
+    pub error_ttl: Duration,
+}
+
+/// Parse an HTTP date like `Wed, 21 Oct 2015 07:28:00 GMT`.
+///
+/// ```
+/// use postgang::bring_client::mailbox_delivery_dates::parse_http_date;
+/// use postgang::http_client::header::HeaderValue;
+///
+/// let date = parse_http_date(&HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+/// assert_eq!(date.unwrap().to_rfc3339(), "2015-10-21T07:28:00+00:00");
+/// assert!(parse_http_date(&HeaderValue::from_static("yesterday")).is_none());
+/// ```
+#[must_use]
+pub fn parse_http_date(value: &HeaderValue) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.to_str().ok()?)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Turn a response with an error status into an error.
+///
+/// `404` is cached for `error_ttl`.
+async fn error_response(
+    resp: Response,
+    err: http_client::Error,
+    postal_code: NorwegianPostalCode,
+    cache: Option<&ResponseCache>,
+    error_ttl: Duration,
+    max_size: u64,
+    record: Option<&Fixtures>,
+) -> Box<dyn core::error::Error> {
+    let status = resp.status();
+    if let (Some(cache), StatusCode::NOT_FOUND) = (cache, status) {
+        if !error_ttl.is_zero() {
+            let error = CachedError {
+                status: status.as_u16(),
+                received: Utc::now(),
+            };
+            if let Err(err) = cache.store_error(postal_code, &error) {
+                return err.into();
+            }
+        }
+    }
+    let body = if status == StatusCode::NOT_FOUND && record.is_none() {
+        None
+    } else {
+        read_body(resp, max_size).await.ok()
+    };
+    if let Some(fixtures) = record {
+        let fixture = Fixture::new(status, body.as_deref().unwrap_or_default());
+        if let Err(err) = fixtures.record(postal_code, fixture) {
+            return err.into();
+        }
+    }
+    if status == StatusCode::NOT_FOUND {
+        return UnknownPostalCode(postal_code).into();
+    }
+    let details = body.and_then(|body| ErrorDetails::parse(&String::from_utf8_lossy(&body)));
+    let err = ApiError {
+        source: err,
+        details,
+    };
+    if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+        AuthError(err).into()
+    } else {
+        err.into()
+    }
+}
+
 /// Delivery day provider.
 pub enum DeliveryDays {
     /// Fetches JSON from [Bring API](https://developer.bring.com/api/postal-code/#get-mailbox-delivery-dates-at-postal-code-get).
     // https://api.bring.com/address/api/{country-code}/postal-codes/{postal-code}/mailbox-delivery-dates
-    Api(Client),
+    Api {
+        client: Client,
+        /// Scheme and host of the API, [`API_URL`] unless changed.
+        base_url: String,
+        /// Sent with every request, for authentication.
+        headers: HeaderMap,
+        cache: Option<ResponseCache>,
+        retry: RetryPolicy,
+        error_ttl: Duration,
+        /// `Date` header of the latest response.
+        server_time: Mutex<Option<DateTime<Utc>>>,
+        max_size: u64,
+        /// Record the responses here when set.
+        record: Option<Box<Fixtures>>,
+    },
 
-    /// Reads JSON from a file.
-    File(Option<PathBuf>),
+    /// Replays responses recorded with [`DeliveryDays::with_recording`].
+    Replay(Fixtures),
+
+    /// Reads JSON from a file, or stdin if `path` is `None`.
+    File {
+        path: Option<PathBuf>,
+        max_size: u64,
+    },
 }
 
 impl DeliveryDays {
     /// Read dates from REST API.
+    ///
+    /// All requests made through the returned provider share one client, so
+    /// connections are kept alive and reused when fetching several postal codes.
     #[allow(clippy::missing_panics_doc)]
-    pub fn api(api_key: ApiKey, api_uid: ApiUid) -> Self {
-        let mut headers = HeaderMap::with_capacity(3);
-        headers.insert("accept", HeaderValue::from_str("application/json").unwrap());
-        headers.insert(super::HEADER_UID, api_uid.0);
-        headers.insert(super::HEADER_KEY, api_key.0);
-        log::debug!("Constructing HTTP client with headers: {:?}", headers);
-        let client = Client::builder().default_headers(headers).build().unwrap();
-        Self::Api(client)
+    pub fn api(api_key: ApiKey, api_uid: ApiUid, options: ApiOptions) -> Self {
+        let mut builder = Client::builder();
+        if let Some(ip_family) = options.ip_family {
+            builder = builder.local_address(ip_family.unspecified());
+        }
+        if let Some(timeout) = options.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = options.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(max) = options.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        for Resolve { host, addr } in &options.resolve {
+            // Like curl, an entry only applies to the port it names. The API
+            // is always reached over HTTPS on the default port.
+            if addr.port() == 443 {
+                builder = builder.resolve(host, *addr);
+            } else {
+                log::warn!(
+                    "Ignoring resolve entry for {host}:{}, only port 443 is used",
+                    addr.port()
+                );
+            }
+        }
+        let mut api = Self::api_with_client(builder.build().unwrap(), api_uid, api_key);
+        if let Self::Api {
+            cache,
+            retry,
+            error_ttl,
+            ..
+        } = &mut api
+        {
+            *cache = options.cache;
+            *retry = options.retry;
+            *error_ttl = options.error_ttl;
+        }
+        api
+    }
+
+    #[must_use]
+    /// Read dates from REST API using `client`, e.g. to share its connection
+    /// pool and TLS settings. See [`DeliveryDays::with_base_url`] to point
+    /// it somewhere else in tests.
+    ///
+    /// The credentials are sent with each request, and the other settings
+    /// are those of [`ApiOptions::default`].
+    ///
+    /// ```
+    /// use postgang::bring_client::{mailbox_delivery_dates::DeliveryDays, ApiKey, ApiUid};
+    /// use postgang::http_client::Client;
+    ///
+    /// let api = DeliveryDays::api_with_client(
+    ///     Client::new(),
+    ///     ApiUid::try_from("user@example.com").unwrap(),
+    ///     ApiKey::try_from("secret").unwrap(),
+    /// );
+    /// assert_eq!(api.server_time(), None);
+    /// ```
+    pub fn api_with_client(client: Client, api_uid: ApiUid, api_key: ApiKey) -> Self {
+        let headers = super::api_headers(api_uid, api_key);
+        let options = ApiOptions::default();
+        Self::Api {
+            client,
+            base_url: API_URL.to_string(),
+            headers,
+            cache: options.cache,
+            retry: options.retry,
+            error_ttl: options.error_ttl,
+            server_time: Mutex::new(None),
+            max_size: DEFAULT_MAX_SIZE,
+            record: None,
+        }
+    }
+
+    #[must_use]
+    /// Ask the API at `base_url`, like `http://127.0.0.1:8080`, instead of
+    /// [`API_URL`], e.g. a mock server in tests.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        if let Self::Api { base_url: url, .. } = &mut self {
+            base_url.trim_end_matches('/').clone_into(url);
+        }
+        self
+    }
+
+    #[must_use]
+    /// Record the API responses to `fixtures`.
+    pub fn with_recording(mut self, fixtures: Fixtures) -> Self {
+        if let Self::Api { record, .. } = &mut self {
+            *record = Some(Box::new(fixtures));
+        }
+        self
+    }
+
+    #[must_use]
+    /// Replay the responses in `fixtures` instead of asking the API.
+    pub const fn replay(fixtures: Fixtures) -> Self {
+        Self::Replay(fixtures)
+    }
+
+    #[must_use]
+    /// The time of the API server, from the `Date` header of the latest
+    /// response.
+    ///
+    /// Using it for timestamps keeps a skewed local clock from stamping
+    /// calendars in the future.
+    pub fn server_time(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Api { server_time, .. } => *server_time
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner),
+            Self::File { .. } | Self::Replay(_) => None,
+        }
     }
 
     #[must_use]
     /// Read dates from file.
     pub const fn file(path: Option<PathBuf>) -> Self {
-        Self::File(path)
+        Self::File {
+            path,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    #[must_use]
+    /// Fail on responses and input larger than `max_size` bytes, instead of
+    /// [`DEFAULT_MAX_SIZE`].
+    pub const fn with_max_size(mut self, max_size: u64) -> Self {
+        match &mut self {
+            Self::Api { max_size: max, .. } | Self::File { max_size: max, .. } => *max = max_size,
+            Self::Replay(_) => {}
+        }
+        self
     }
 
     /// Get a list of delivery dates.
     #[allow(clippy::missing_errors_doc)]
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(skip(self), fields(postal_code = %postal_code), err)
+    )]
     pub async fn get<T: DeserializeOwned>(
         &self,
         postal_code: NorwegianPostalCode,
-    ) -> Result<T, Box<dyn std::error::Error>> {
+    ) -> Result<T, Box<dyn core::error::Error>> {
         let response: T = match self {
-            Self::Api(client) => {
+            Self::Api {
+                client,
+                base_url,
+                headers,
+                cache,
+                retry,
+                error_ttl,
+                server_time,
+                max_size,
+                record,
+            } => {
                 let url = format!(
-                    "https://api.bring.com/address/api/{NORWAY}/postal-codes/{postal_code}/mailbox-delivery-dates"
+                    "{base_url}/address/api/{NORWAY}/postal-codes/{postal_code}/mailbox-delivery-dates"
                 );
                 log::debug!("Using URL: {url}");
-                let resp = client.get(&url).send().await?;
+                if let Some(error) = cache
+                    .as_ref()
+                    .and_then(|cache| cache.load_error(postal_code))
+                    .filter(|error| error.is_fresh(*error_ttl, Utc::now()))
+                    // Older versions cached 403 too
+                    .filter(|error| error.status == StatusCode::NOT_FOUND.as_u16())
+                {
+                    log::warn!("{postal_code}: Not asking the API again yet, got {error}");
+                    return Err(UnknownPostalCode(postal_code).into());
+                }
+                let cached = cache.as_ref().and_then(|cache| cache.load(postal_code));
+                let resp = retry
+                    .send(|| {
+                        let mut request = client.get(&url).headers(headers.clone());
+                        if let Some(cached) = &cached {
+                            if let Some(etag) = &cached.etag {
+                                request = request.header(IF_NONE_MATCH, etag);
+                            }
+                            if let Some(last_modified) = &cached.last_modified {
+                                request = request.header(IF_MODIFIED_SINCE, last_modified);
+                            }
+                        }
+                        request
+                    })
+                    .await?;
                 log::debug!("Got response status: {}", resp.status());
-                log::trace!("{:?}", resp);
-                resp.error_for_status_ref()?;
-                resp.json().await?
+                log::trace!("Got response headers: {}", Redacted(resp.headers()));
+                if let Some(date) = resp.headers().get(DATE).and_then(parse_http_date) {
+                    *server_time
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(date);
+                }
+                if let (StatusCode::NOT_MODIFIED, Some(cached)) = (resp.status(), cached) {
+                    log::debug!("Using cached response for {postal_code}");
+                    if let Some(fixtures) = record {
+                        let fixture = Fixture {
+                            status: StatusCode::OK.as_u16(),
+                            body: cached.body.clone(),
+                        };
+                        fixtures.record(postal_code, fixture)?;
+                    }
+                    return Ok(serde_json::from_value(cached.body)?);
+                }
+                if let Err(err) = resp.error_for_status_ref() {
+                    return Err(error_response(
+                        resp,
+                        err,
+                        postal_code,
+                        cache.as_ref(),
+                        *error_ttl,
+                        *max_size,
+                        record.as_deref(),
+                    )
+                    .await);
+                }
+                let headers = resp.headers().clone();
+                let bytes = read_body(resp, *max_size).await?;
+                if let Some(fixtures) = record {
+                    fixtures.record(postal_code, Fixture::new(StatusCode::OK, &bytes))?;
+                }
+                let body: serde_json::Value = serde_json::from_slice(&bytes)?;
+                if let Some(cache) = cache {
+                    cache.clear_error(postal_code);
+                    let entry = CachedResponse::from_headers(&headers, body);
+                    if entry.has_validators() {
+                        cache.store(postal_code, &entry)?;
+                    }
+                    serde_json::from_value(entry.body)?
+                } else {
+                    serde_json::from_value(body)?
+                }
             }
-            Self::File(Some(path)) => {
-                log::debug!("Reading from file: {:?}", path);
-                serde_json::from_reader(
-                    std::fs::File::open(path).map_err(|err| io_error_to_string(&err, path))?,
-                )?
-            }
-            Self::File(None) => {
-                log::debug!("Reading from stdin");
-                serde_json::from_reader(std::io::stdin())?
+            Self::File { path, max_size } => {
+                log::debug!("Reading from {}", input_name(path.as_deref()));
+                serde_json::from_value(normalize_input(read_input(path.as_deref(), *max_size)?))?
             }
+            Self::Replay(fixtures) => serde_json::from_value(fixtures.replay(postal_code)?)?,
         };
         Ok(response)
     }
+
+    /// Get delivery dates for several postal codes as a stream.
+    ///
+    /// At most `concurrency` requests are in flight at the same time. The
+    /// results are yielded in the same order as `postal_codes`.
+    pub fn get_stream<'a, T: DeserializeOwned + 'a>(
+        &'a self,
+        postal_codes: &'a [NorwegianPostalCode],
+        concurrency: usize,
+    ) -> impl Stream<Item = Result<(NorwegianPostalCode, T), Box<dyn core::error::Error>>> + 'a
+    {
+        stream::iter(postal_codes)
+            .map(move |&postal_code| async move {
+                self.get(postal_code)
+                    .await
+                    .map(|response| (postal_code, response))
+                    .map_err(|source| {
+                        FetchError {
+                            postal_code,
+                            source,
+                        }
+                        .into()
+                    })
+            })
+            .buffered(concurrency.max(1))
+    }
+
+    /// Get delivery dates for several postal codes.
+    ///
+    /// See [`DeliveryDays::get_stream`].
+    #[allow(clippy::missing_errors_doc)]
+    pub async fn get_all<T: DeserializeOwned>(
+        &self,
+        postal_codes: &[NorwegianPostalCode],
+        concurrency: usize,
+    ) -> Result<Vec<(NorwegianPostalCode, T)>, Box<dyn core::error::Error>> {
+        self.get_stream(postal_codes, concurrency)
+            .try_collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead as _, BufReader, Write as _},
+        net::TcpListener,
+        path::PathBuf,
+        thread::{self, JoinHandle},
+    };
+
+    use core::time::Duration;
+
+    use chrono::{NaiveDate, Utc};
+
+    use super::{ApiError, ApiOptions, ApiResponse, AuthError, DeliveryDays, UnknownPostalCode};
+    use crate::{
+        bring_client::{
+            cache::{CachedError, ResponseCache},
+            retry::RetryPolicy,
+            ApiKey, ApiUid, NorwegianPostalCode,
+        },
+        http_client::block_on,
+    };
+
+    /// A response with `status` like `200 OK`, the extra `headers` and the
+    /// JSON `body`.
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n{headers}\r\n{body}",
+            body.len()
+        )
+    }
+
+    /// Answer a request per connection on a local port with `responses`, in
+    /// turn. Returns the base URL and a handle giving the head of each
+    /// request.
+    fn serve(responses: Vec<String>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|response| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request = String::new();
+                    while reader.read_line(&mut request).unwrap() > 2 {}
+                    stream.write_all(response.as_bytes()).unwrap();
+                    request.to_lowercase()
+                })
+                .collect()
+        });
+        (url, handle)
+    }
+
+    /// An empty cache directory named after `test`.
+    fn cache_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("postgang-{test}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn api(url: &str, options: ApiOptions) -> DeliveryDays {
+        DeliveryDays::api(
+            ApiKey::try_from("secret").unwrap(),
+            ApiUid::try_from("ola@example.com").unwrap(),
+            ApiOptions {
+                retry: RetryPolicy {
+                    retries: 0,
+                    ..RetryPolicy::default()
+                },
+                ..options
+            },
+        )
+        .with_base_url(url)
+    }
+
+    fn code(code: &str) -> NorwegianPostalCode {
+        NorwegianPostalCode::try_from(code).unwrap()
+    }
+
+    #[test]
+    fn test_api() {
+        const LAST_MODIFIED: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+        let dir = cache_dir("api");
+        let (url, server) = serve(vec![
+            response(
+                "200 OK",
+                &format!("Last-Modified: {LAST_MODIFIED}\r\nDate: {LAST_MODIFIED}\r\n"),
+                r#"{"delivery_dates":["2024-09-02"]}"#,
+            ),
+            response("304 Not Modified", "", ""),
+            response("404 Not Found", "", ""),
+            response(
+                "400 Bad Request",
+                "",
+                r#"{"errors":[{"message":"Invalid postal code"}],"traceId":"abc"}"#,
+            ),
+        ]);
+        let api = api(
+            &format!("{url}/"),
+            ApiOptions {
+                cache: Some(ResponseCache::new(dir.clone())),
+                ..ApiOptions::default()
+            },
+        );
+        let dates = vec![NaiveDate::from_ymd_opt(2024, 9, 2).unwrap()];
+        block_on(async {
+            let response: ApiResponse = api.get(code("7800")).await.unwrap();
+            assert_eq!(response.delivery_dates, dates);
+            assert_eq!(
+                api.server_time().map(|time| time.to_rfc3339()).as_deref(),
+                Some("2015-10-21T07:28:00+00:00")
+            );
+            let response: ApiResponse = api.get(code("7800")).await.unwrap();
+            assert_eq!(response.delivery_dates, dates);
+            let err = api.get::<ApiResponse>(code("2450")).await.unwrap_err();
+            assert!(err.downcast_ref::<UnknownPostalCode>().is_some(), "{err}");
+            let err = api.get::<ApiResponse>(code("0001")).await.unwrap_err();
+            let details = err.downcast_ref::<ApiError>().unwrap().details.clone();
+            assert_eq!(
+                details.unwrap().to_string(),
+                "Invalid postal code (trace id abc)"
+            );
+        });
+        let requests = server.join().unwrap();
+        assert!(requests[0]
+            .starts_with("get /address/api/no/postal-codes/7800/mailbox-delivery-dates "));
+        assert!(requests[0].contains("x-mybring-api-uid: ola@example.com"));
+        assert!(!requests[0].contains("if-modified-since"));
+        assert!(requests[1].contains(&format!(
+            "if-modified-since: {}",
+            LAST_MODIFIED.to_lowercase()
+        )));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_error_cache() {
+        let dir = cache_dir("error-cache");
+        let cache = ResponseCache::new(dir.clone());
+        let (url, server) = serve(vec![
+            response("404 Not Found", "", ""),
+            response("403 Forbidden", "", r#"{"message":"Invalid API key"}"#),
+            response("403 Forbidden", "", r#"{"message":"Invalid API key"}"#),
+            response("404 Not Found", "", ""),
+        ]);
+        let api = api(
+            &url,
+            ApiOptions {
+                cache: Some(cache.clone()),
+                error_ttl: Duration::from_mins(10),
+                ..ApiOptions::default()
+            },
+        );
+        block_on(async {
+            // Stored, then answered from the cache
+            for _ in 0..2 {
+                let err = api.get::<ApiResponse>(code("2450")).await.unwrap_err();
+                assert!(err.downcast_ref::<UnknownPostalCode>().is_some(), "{err}");
+            }
+            assert_eq!(cache.load_error(code("2450")).unwrap().status, 404);
+            // Asked every time
+            for _ in 0..2 {
+                let err = api.get::<ApiResponse>(code("7800")).await.unwrap_err();
+                assert!(err.downcast_ref::<AuthError>().is_some(), "{err}");
+            }
+            assert!(cache.load_error(code("7800")).is_none());
+            // Expired
+            let expired = CachedError {
+                status: 404,
+                received: Utc::now() - chrono::Duration::hours(1),
+            };
+            cache.store_error(code("2450"), &expired).unwrap();
+            let err = api.get::<ApiResponse>(code("2450")).await.unwrap_err();
+            assert!(err.downcast_ref::<UnknownPostalCode>().is_some(), "{err}");
+        });
+        assert_eq!(server.join().unwrap().len(), 4);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
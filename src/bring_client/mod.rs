@@ -1,7 +1,16 @@
 //! Client for the [Bring postal code API](https://developer.bring.com/api/postal-code/).
-use core::fmt::{self, Debug, Display};
+use core::{
+    fmt::{self, Debug, Display},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+};
 
-use reqwest::header::HeaderValue;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::http_client::{
+    header::{HeaderMap, HeaderValue},
+    Redacted,
+};
 
 const HEADER_UID: &str = "X-Mybring-API-Uid";
 const HEADER_KEY: &str = "X-Mybring-API-Key";
@@ -9,7 +18,7 @@ const NORWAY: &str = "no";
 const INVALID_NORWEGIAN_POST_CODE: &str =
     "Invalid postal code format for Norway. Postal code must be numeric and consist of 4 digits";
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// Represents a norwegian postal code.
 ///
 /// Postal codes must be numeric and consist of 4 digits
@@ -47,12 +56,32 @@ impl<'a> TryFrom<&'a str> for NorwegianPostalCode {
     }
 }
 
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for NorwegianPostalCode {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Self(u.int_in_range(0..=9999)?))
+    }
+}
+
 impl Display for NorwegianPostalCode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_fmt(format_args!("{:04}", self.0))
     }
 }
 
+impl Serialize for NorwegianPostalCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for NorwegianPostalCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::try_from(value.as_str()).map_err(de::Error::custom)
+    }
+}
+
 #[derive(Clone)]
 /// API key to be used by the HTTP client.
 ///
@@ -90,13 +119,34 @@ impl TryFrom<&str> for ApiKey {
 
 #[cfg(test)]
 mod test {
-    use super::ApiKey;
+    use super::{api_headers, ApiKey, ApiUid, NorwegianPostalCode, Redacted};
 
     #[test]
     fn test_api_key_try_from_str() {
         let x = ApiKey::try_from("aaaa").unwrap();
         assert!(x.0.is_sensitive());
     }
+
+    #[test]
+    fn test_api_uid_try_from_str() {
+        let x = ApiUid::try_from("ola@example.com").unwrap();
+        assert!(x.0.is_sensitive());
+        assert_eq!(x.as_str(), Some("ola@example.com"));
+        assert!(!format!("{x:?}").contains("ola"));
+        let headers = api_headers(x, ApiKey::try_from("aaaa").unwrap());
+        assert!(!Redacted(&headers).to_string().contains("ola"));
+    }
+
+    #[test]
+    fn test_postal_code_serde() {
+        let code = NorwegianPostalCode::try_from("0001").unwrap();
+        assert_eq!(serde_json::to_string(&code).unwrap(), "\"0001\"");
+        assert_eq!(
+            serde_json::from_str::<NorwegianPostalCode>("\"0001\"").unwrap(),
+            code
+        );
+        assert!(serde_json::from_str::<NorwegianPostalCode>("\"001\"").is_err());
+    }
 }
 
 #[derive(Debug)]
@@ -105,20 +155,114 @@ pub struct InvalidApiKey;
 
 #[derive(Debug, Clone)]
 /// API user id to be used by the HTTP client.
+///
+/// The header is marked sensitive too, as the user id is an email address
+/// and half of the credentials.
 pub struct ApiUid(HeaderValue);
 
 #[derive(Debug)]
 /// A possible error when converting an [`ApiUid`] from a string.
 pub struct InvalidApiUid;
 
+impl ApiUid {
+    #[must_use]
+    /// The user id as a string, `None` if it isn't valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        self.0.to_str().ok()
+    }
+}
+
 impl TryFrom<&str> for ApiUid {
     type Error = InvalidApiUid;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        Ok(Self(
-            HeaderValue::from_str(value).map_err(|_| InvalidApiUid)?,
-        ))
+        let mut value = HeaderValue::from_str(value).map_err(|_| InvalidApiUid)?;
+        value.set_sensitive(true);
+        Ok(Self(value))
+    }
+}
+
+/// Headers sent with each API request, with the credentials.
+fn api_headers(api_uid: ApiUid, api_key: ApiKey) -> HeaderMap {
+    let mut headers = HeaderMap::with_capacity(3);
+    headers.insert("accept", HeaderValue::from_static("application/json"));
+    headers.insert(HEADER_UID, api_uid.0);
+    headers.insert(HEADER_KEY, api_key.0);
+    log::debug!("Sending requests with headers: {}", Redacted(&headers));
+    headers
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// IP address family used when connecting to the API.
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    #[must_use]
+    /// The unspecified local address of this family.
+    ///
+    /// Binding the client to it restricts connections to this family.
+    pub const fn unspecified(self) -> IpAddr {
+        match self {
+            Self::V4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Self::V6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A fixed address for a host name, bypassing DNS.
+///
+/// Parsed from `HOST:PORT:ADDRESS` like curl's `--resolve`, and like curl
+/// only used for connections to that port.
+///
+/// ```
+/// use core::net::SocketAddr;
+///
+/// use postgang::bring_client::Resolve;
+///
+/// let resolve: Resolve = "api.bring.com:443:127.0.0.1".parse().unwrap();
+/// assert_eq!(resolve.host, "api.bring.com");
+/// assert_eq!(resolve.addr, "127.0.0.1:443".parse::<SocketAddr>().unwrap());
+/// let resolve: Resolve = "api.bring.com:8443:[::1]".parse().unwrap();
+/// assert_eq!(resolve.addr, "[::1]:8443".parse::<SocketAddr>().unwrap());
+/// assert!("api.bring.com:127.0.0.1".parse::<Resolve>().is_err());
+/// ```
+pub struct Resolve {
+    pub host: String,
+    pub addr: SocketAddr,
+}
+
+impl FromStr for Resolve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Expected HOST:PORT:ADDRESS, got {s:?}");
+        let mut parts = s.splitn(3, ':');
+        let (Some(host), Some(port), Some(addr)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let port = port.parse().map_err(|_| invalid())?;
+        let ip: IpAddr = addr
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse()
+            .map_err(|_| invalid())?;
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            host: host.to_string(),
+            addr: SocketAddr::new(ip, port),
+        })
     }
 }
 
+pub mod cache;
+pub mod fixtures;
 pub mod mailbox_delivery_dates;
+pub mod postal_codes;
+pub mod retry;
@@ -0,0 +1,129 @@
+//! Postal code lookup, for `postgang lookup`.
+//!
+//! Uses the [postal code API](https://developer.bring.com/api/postal-code/)
+//! to tell whether a postal code exists and which post place it belongs to.
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bring_client::{
+        mailbox_delivery_dates::{read_body, DEFAULT_MAX_SIZE},
+        retry::RetryPolicy,
+        ApiKey, ApiUid, NorwegianPostalCode, NORWAY,
+    },
+    http_client::{self, header::HeaderMap, Client},
+    locale::{Locale, Message},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// The answer of the API for a postal code.
+pub struct PostPlace {
+    /// The post place, or why the postal code is invalid.
+    pub result: String,
+    pub valid: bool,
+    /// `NORMAL`, `POSTBOKS`, `SPESIALKUNDE` or `SERVICE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub postal_code_type: Option<String>,
+}
+
+impl PostPlace {
+    #[must_use]
+    /// One line describing `postal_code`, in `locale` when it is invalid.
+    ///
+    /// ```
+    /// use postgang::bring_client::postal_codes::PostPlace;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::locale::Locale;
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let place: PostPlace =
+    ///     serde_json::from_str(r#"{"result":"NAMSOS","valid":true,"postalCodeType":"NORMAL"}"#)
+    ///         .unwrap();
+    /// assert_eq!(place.describe(postal_code, Locale::English), "7800 NAMSOS (NORMAL)");
+    /// ```
+    pub fn describe(&self, postal_code: NorwegianPostalCode, locale: Locale) -> String {
+        if !self.valid {
+            return locale.message(Message::InvalidPostalCode(postal_code));
+        }
+        match &self.postal_code_type {
+            Some(kind) => format!("{postal_code} {} ({kind})", self.result),
+            None => format!("{postal_code} {}", self.result),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Looks up postal codes in the API.
+pub struct PostalCodes {
+    client: Client,
+    headers: HeaderMap,
+    retry: RetryPolicy,
+}
+
+impl PostalCodes {
+    #[must_use]
+    /// Look up postal codes using `client`, sending the credentials with
+    /// each request.
+    pub fn new(client: Client, api_uid: ApiUid, api_key: ApiKey) -> Self {
+        Self {
+            client,
+            headers: super::api_headers(api_uid, api_key),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    #[must_use]
+    /// Retry failed requests with `retry` instead of the default policy.
+    pub const fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Get the post place of `postal_code`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails or the API answers with
+    /// something else than a post place.
+    pub async fn lookup(
+        &self,
+        postal_code: NorwegianPostalCode,
+    ) -> Result<PostPlace, Box<dyn core::error::Error>> {
+        let url = format!(
+            "https://api.bring.com/shippingguide/api/postalCode.json?country={NORWAY}&pnr={postal_code}"
+        );
+        log::debug!("Using URL: {url}");
+        let resp = self
+            .retry
+            .send(|| self.client.get(&url).headers(self.headers.clone()))
+            .await?;
+        log::debug!("Got response status: {}", resp.status());
+        let resp = resp
+            .error_for_status()
+            .map_err(http_client::Error::without_url)?;
+        let body = read_body(resp, DEFAULT_MAX_SIZE).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PostPlace;
+    use crate::{bring_client::NorwegianPostalCode, locale::Locale};
+
+    #[test]
+    fn test_describe_invalid() {
+        let place: PostPlace =
+            serde_json::from_str(r#"{"result":"Ugyldig postnummer","valid":false}"#).unwrap();
+        assert_eq!(place.postal_code_type, None);
+        let postal_code = NorwegianPostalCode::try_from("0000").unwrap();
+        assert_eq!(
+            place.describe(postal_code, Locale::Norwegian),
+            "0000: Ugyldig postnummer"
+        );
+        assert_eq!(
+            place.describe(postal_code, Locale::English),
+            "0000: Invalid postal code"
+        );
+    }
+}
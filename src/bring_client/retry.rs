@@ -0,0 +1,135 @@
+//! Retrying failed API requests.
+//!
+//! Connection errors, timeouts, `429 Too Many Requests` and server errors
+//! are retried with exponential backoff. With jitter, each delay is picked at
+//! random between zero and the backoff, so instances started by the same
+//! schedule spread their retries out instead of retrying in lockstep.
+use core::time::Duration;
+
+use crate::http_client::{self, RequestBuilder, Response, StatusCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How failed requests are retried.
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt.
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each further retry.
+    pub base_delay: Duration,
+    /// Upper bound for the delay between two attempts.
+    pub max_delay: Duration,
+    /// Randomize the delays.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 2,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+#[must_use]
+/// Whether a response with `status` is worth retrying.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+#[must_use]
+/// Whether a request that failed with `err` is worth retrying.
+pub fn is_retryable_error(err: &http_client::Error) -> bool {
+    err.is_connect() || err.is_timeout() || err.status().is_some_and(is_retryable_status)
+}
+
+impl RetryPolicy {
+    #[must_use]
+    /// Delay before retry number `retry`, counting from zero.
+    ///
+    /// `random` is a number between 0 and 1 used for jitter.
+    ///
+    /// ```
+    /// use core::time::Duration;
+    ///
+    /// use postgang::bring_client::retry::RetryPolicy;
+    ///
+    /// let policy = RetryPolicy {
+    ///     jitter: false,
+    ///     ..RetryPolicy::default()
+    /// };
+    /// assert_eq!(policy.delay(0, 0.5), Duration::from_secs(1));
+    /// assert_eq!(policy.delay(2, 0.5), Duration::from_secs(4));
+    /// assert_eq!(policy.delay(10, 0.5), Duration::from_secs(30));
+    ///
+    /// let policy = RetryPolicy::default();
+    /// assert_eq!(policy.delay(2, 0.5), Duration::from_secs(2));
+    /// ```
+    pub fn delay(&self, retry: u32, random: f64) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(retry))
+            .min(self.max_delay);
+        if self.jitter {
+            backoff.mul_f64(random.clamp(0.0, 1.0))
+        } else {
+            backoff
+        }
+    }
+
+    /// Send the request built by `build`, retrying according to the policy.
+    ///
+    /// The last response is returned when retries are exhausted, so its
+    /// status must still be checked.
+    pub(crate) async fn send(
+        &self,
+        build: impl Fn() -> RequestBuilder,
+    ) -> Result<Response, http_client::Error> {
+        let mut retry = 0;
+        loop {
+            let result = http_client::send(build()).await;
+            let retryable = match &result {
+                Ok(response) => is_retryable_status(response.status()),
+                Err(err) => is_retryable_error(err),
+            };
+            if !retryable || retry >= self.retries {
+                return result;
+            }
+            let delay = self.delay(retry, fastrand::f64());
+            let shown = humantime::format_duration(Duration::from_millis(
+                u64::try_from(delay.as_millis()).unwrap_or(u64::MAX),
+            ));
+            match &result {
+                Ok(response) => log::warn!("Got {}, retrying in {shown}", response.status()),
+                Err(err) => log::warn!("{err}, retrying in {shown}"),
+            }
+            http_client::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::time::Duration;
+
+    use super::{is_retryable_status, RetryPolicy};
+    use crate::http_client::StatusCode;
+
+    #[test]
+    fn test_retryable_status() {
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(StatusCode::OK));
+    }
+
+    #[test]
+    fn test_jitter() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1, 0.0), Duration::ZERO);
+        assert_eq!(policy.delay(1, 1.0), Duration::from_secs(2));
+        assert!(policy.delay(1, fastrand::f64()) <= Duration::from_secs(2));
+    }
+}
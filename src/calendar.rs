@@ -1,27 +1,44 @@
 //! iCalendar generator
-use core::fmt;
+use alloc::{borrow::Cow, collections::BTreeMap};
+use core::{
+    fmt::{self, Write as _},
+    str::FromStr,
+};
 
 use chrono::{
     format::{DelayedFormat, StrftimeItems},
-    DateTime, Datelike, Duration, NaiveDate, Utc,
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc,
     Weekday::{Fri, Mon, Sat, Sun, Thu, Tue, Wed},
 };
 
-use crate::bring_client::mailbox_delivery_dates::DeliveryDate;
+use crate::{
+    bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode},
+    holidays,
+    locale::Locale,
+};
+
+use self::content_line::Folded;
 
-use self::content_line::ContentLine;
+pub mod jscalendar;
 
 #[inline]
 fn format_naive_date<'a>(date: NaiveDate) -> DelayedFormat<StrftimeItems<'a>> {
     date.format("%Y%m%d")
 }
 
+#[inline]
+fn format_local_time<'a>(time: NaiveDateTime) -> DelayedFormat<StrftimeItems<'a>> {
+    time.format("%Y%m%dT%H%M%S")
+}
+
 #[inline]
 fn format_timestamp<'a>(timestamp: &DateTime<Utc>) -> DelayedFormat<StrftimeItems<'a>> {
     timestamp.format("%Y%m%dT%H%M%SZ")
 }
 
-fn weekday(date: NaiveDate) -> &'static str {
+#[must_use]
+/// Norwegian name of the weekday of `date`.
+pub fn weekday(date: NaiveDate) -> &'static str {
     match date.weekday() {
         Mon => "mandag",
         Tue => "tirsdag",
@@ -33,15 +50,478 @@ fn weekday(date: NaiveDate) -> &'static str {
     }
 }
 
+#[must_use]
+/// Norwegian name of the month of `date`.
+pub fn month(date: NaiveDate) -> &'static str {
+    const MONTHS: [&str; 12] = [
+        "januar",
+        "februar",
+        "mars",
+        "april",
+        "mai",
+        "juni",
+        "juli",
+        "august",
+        "september",
+        "oktober",
+        "november",
+        "desember",
+    ];
+    MONTHS[date.month0() as usize]
+}
+
+#[must_use]
+/// Human readable summary of a delivery date.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::calendar::summary;
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+/// assert_eq!(summary(date), "7800: Posten kommer torsdag 13. august.");
+/// ```
+pub fn summary(delivery_date: DeliveryDate) -> String {
+    let date = delivery_date.date;
+    format!(
+        "{}: Posten kommer {} {}. {}.",
+        delivery_date.postal_code,
+        weekday(date),
+        date.day(),
+        month(date)
+    )
+}
+
+#[must_use]
+/// Replace the postal code at the start of `summary` with `name`.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::calendar::{rename, summary};
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+/// assert_eq!(rename(summary(date), date, "hytta"), "hytta: Posten kommer torsdag 13. august.");
+/// ```
+pub fn rename(summary: String, delivery_date: DeliveryDate, name: &str) -> String {
+    match summary.strip_prefix(&delivery_date.postal_code.to_string()) {
+        Some(rest) => format!("{name}{rest}"),
+        None => summary,
+    }
+}
+
+#[must_use]
+/// Replace the placeholders like `{name}` in `template` with their `values`,
+/// keeping unknown placeholders as they are.
+///
+/// The values are not searched for placeholders in turn.
+///
+/// ```
+/// use postgang::calendar::fill;
+///
+/// let values = [("name", "{code}".to_string()), ("code", "7800".to_string())];
+/// assert_eq!(fill("{name} {code} {unknown}", &values), "{code} 7800 {unknown}");
+/// ```
+pub fn fill(template: &str, values: &[(&str, String)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let value = values.iter().find(|(key, _)| {
+            rest.strip_prefix(key)
+                .is_some_and(|after| after.starts_with('}'))
+        });
+        match value {
+            Some((key, value)) => {
+                filled.push_str(value);
+                rest = &rest[key.len() + 1..];
+            }
+            None => filled.push('{'),
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+#[must_use]
+/// The placeholders of [`format_summary`] and their values for
+/// `delivery_date`.
+pub fn placeholders(
+    delivery_date: DeliveryDate,
+    name: &str,
+    locale: Locale,
+) -> Vec<(&'static str, String)> {
+    let date = delivery_date.date;
+    vec![
+        ("code", delivery_date.postal_code.to_string()),
+        ("name", name.to_string()),
+        ("date", date.to_string()),
+        ("weekday", locale.weekday(date).to_string()),
+        ("day", date.day().to_string()),
+        ("month", locale.month(date).to_string()),
+    ]
+}
+
+#[must_use]
+/// Fill in the placeholders of `template` for `delivery_date`.
+///
+/// The placeholders are `{code}`, `{name}`, `{date}`, `{weekday}`, `{day}` and
+/// `{month}`. `{name}` is the alias of the postal code, or the postal code.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::calendar::format_summary;
+/// use postgang::locale::Locale;
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+/// assert_eq!(
+///     format_summary("Post til {name} {weekday} ({date})", date, "hytta", Locale::Norwegian),
+///     "Post til hytta torsdag (1970-08-13)"
+/// );
+/// assert_eq!(
+///     format_summary("{code}: {weekday} {day} {month}", date, "7800", Locale::English),
+///     "7800: Thursday 13 August"
+/// );
+/// ```
+pub fn format_summary(
+    template: &str,
+    delivery_date: DeliveryDate,
+    name: &str,
+    locale: Locale,
+) -> String {
+    fill(template, &placeholders(delivery_date, name, locale))
+}
+
+#[must_use]
+/// Unique identifier of the event for a delivery date.
+pub fn uid(delivery_date: DeliveryDate) -> String {
+    format!(
+        "postgang-{}-{}",
+        delivery_date.postal_code, delivery_date.date
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Time of day the mail is expected, for timed instead of all-day events.
+///
+/// Parsed from `HH:MM-HH:MM`. The times are floating, so they are read in the
+/// time zone of the calendar client.
+///
+/// ```
+/// use chrono::NaiveTime;
+/// use postgang::calendar::DeliveryWindow;
+///
+/// let window: DeliveryWindow = "09:00-16:00".parse().unwrap();
+/// assert_eq!(window.start, NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+/// assert!("16:00-09:00".parse::<DeliveryWindow>().is_err());
+/// assert!("09:00".parse::<DeliveryWindow>().is_err());
+/// ```
+pub struct DeliveryWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl FromStr for DeliveryWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("Expected HH:MM-HH:MM, got {s:?}"))?;
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|err| format!("{err}: {time:?}"))
+        };
+        let (start, end) = (time(start)?, time(end)?);
+        if start >= end {
+            return Err(format!("The window must end after it starts: {s:?}"));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// When an alarm goes off.
+///
+/// Parsed from a duration before the event starts, e.g. `2h`, or from a
+/// time of day some days before the delivery, e.g. `1d@20:00` for the
+/// evening before or `@07:00` for the morning of.
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use chrono::NaiveTime;
+/// use postgang::calendar::AlarmTrigger;
+///
+/// assert_eq!(
+///     "2h".parse::<AlarmTrigger>().unwrap(),
+///     AlarmTrigger::Before(Duration::from_secs(2 * 60 * 60))
+/// );
+/// assert_eq!(
+///     "1d@20:00".parse::<AlarmTrigger>().unwrap(),
+///     AlarmTrigger::At {
+///         days_before: 1,
+///         time: NaiveTime::from_hms_opt(20, 0, 0).unwrap()
+///     }
+/// );
+/// assert!("@25:00".parse::<AlarmTrigger>().is_err());
+/// ```
+pub enum AlarmTrigger {
+    /// This long before the event starts.
+    Before(core::time::Duration),
+    /// At `time`, `days_before` days before the delivery date.
+    At { days_before: u32, time: NaiveTime },
+}
+
+impl FromStr for AlarmTrigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((days, time)) = s.split_once('@') else {
+            return humantime::parse_duration(s)
+                .map(Self::Before)
+                .map_err(|err| format!("{err}: {s:?}"));
+        };
+        let days_before = match days.strip_suffix('d') {
+            Some(days) => days.parse().map_err(|err| format!("{err}: {s:?}"))?,
+            None if days.is_empty() => 0,
+            None => return Err(format!("Expected days like 1d before @, got {s:?}")),
+        };
+        let time =
+            NaiveTime::parse_from_str(time, "%H:%M").map_err(|err| format!("{err}: {s:?}"))?;
+        Ok(Self::At { days_before, time })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// What an alarm does.
+pub enum AlarmAction {
+    /// Show the summary of the event.
+    Display,
+    /// Send the summary of the event to an email address.
+    Email(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A `VALARM` added to each active event.
+pub struct Alarm {
+    pub trigger: AlarmTrigger,
+    pub action: AlarmAction,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Settings of the events of one postal code, overriding those of the
+/// [`Calendar`].
+pub struct EventSettings {
+    pub locale: Option<Locale>,
+    pub alarm: Option<Alarm>,
+    /// Summary template, see [`format_summary`].
+    pub summary_template: Option<String>,
+}
+
+impl Alarm {
+    /// Seconds from the start of the event on `date` to the alarm, negative
+    /// before it.
+    fn offset(&self, date: NaiveDate, window: Option<DeliveryWindow>) -> i64 {
+        match self.trigger {
+            AlarmTrigger::Before(before) => -i64::try_from(before.as_secs()).unwrap_or(i64::MAX),
+            AlarmTrigger::At { days_before, time } => {
+                let start = window.map_or(NaiveTime::MIN, |window| window.start);
+                let at = (date - Duration::days(days_before.into())).and_time(time);
+                (at - date.and_time(start)).num_seconds()
+            }
+        }
+    }
+}
+
+/// Format `seconds` as an iCalendar duration, e.g. `-PT4H`.
+fn format_duration(seconds: i64) -> String {
+    let sign = if seconds < 0 { "-" } else { "" };
+    let seconds = seconds.unsigned_abs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    let mut duration = format!("{sign}PT");
+    for (value, unit) in [(hours, 'H'), (minutes, 'M'), (seconds, 'S')] {
+        if value > 0 {
+            // Writing to a String can't fail
+            let _ = write!(duration, "{value}{unit}");
+        }
+    }
+    if duration.ends_with('T') {
+        duration.push_str("0S");
+    }
+    duration
+}
+
+/// Longest content line in octets, without the line break, that RFC 5545
+/// allows before folding.
+pub const MAX_LINE_LENGTH: usize = 75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Line break between content lines.
+pub enum LineEnding {
+    /// `\r\n`, as RFC 5545 requires.
+    #[default]
+    Crlf,
+    /// `\n`, for tools that don't handle `\r\n`.
+    Lf,
+}
+
+impl LineEnding {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Crlf => "\r\n",
+            Self::Lf => "\n",
+        }
+    }
+}
+
+/// Default `URL` of the events, Posten's page about mail delivery.
+pub const DEFAULT_URL: &str = "https://www.posten.no/levering-av-post/";
+
 #[derive(Debug, Clone)]
 pub struct Calendar {
     delivery_dates: Vec<DeliveryDate>,
+    cancelled: Vec<DeliveryDate>,
+    sequences: BTreeMap<String, u32>,
     created: Option<DateTime<Utc>>,
+    week_numbers: bool,
+    url: Option<Cow<'static, str>>,
+    window: Option<DeliveryWindow>,
+    alarm: Option<Alarm>,
+    outlook: bool,
+    color: Option<String>,
+    line_length: usize,
+    line_ending: LineEnding,
+    bom: bool,
+    names: BTreeMap<NorwegianPostalCode, String>,
+    locale: Locale,
+    summary_template: Option<String>,
+    name: Option<String>,
+    event_settings: BTreeMap<NorwegianPostalCode, EventSettings>,
 }
 
 impl Calendar {
-    fn content_lines(&self) -> Vec<ContentLine> {
-        self.into()
+    /// The language of the events of `postal_code`.
+    fn locale(&self, postal_code: NorwegianPostalCode) -> Locale {
+        self.event_settings
+            .get(&postal_code)
+            .and_then(|settings| settings.locale)
+            .unwrap_or(self.locale)
+    }
+
+    /// The alarm of the events of `postal_code`.
+    fn alarm(&self, postal_code: NorwegianPostalCode) -> Option<&Alarm> {
+        self.event_settings
+            .get(&postal_code)
+            .and_then(|settings| settings.alarm.as_ref())
+            .or(self.alarm.as_ref())
+    }
+
+    /// Summary of the event for `delivery_date`.
+    fn summary(&self, delivery_date: DeliveryDate) -> String {
+        let postal_code = delivery_date.postal_code;
+        let name = self.names.get(&postal_code);
+        let template = self
+            .event_settings
+            .get(&postal_code)
+            .and_then(|settings| settings.summary_template.as_ref())
+            .or(self.summary_template.as_ref());
+        let locale = self.locale(postal_code);
+        match (template, name) {
+            (Some(template), _) => format_summary(
+                template,
+                delivery_date,
+                name.map_or(&postal_code.to_string(), |name| name),
+                locale,
+            ),
+            (None, Some(name)) => rename(locale.summary(delivery_date), delivery_date, name),
+            (None, None) => locale.summary(delivery_date),
+        }
+    }
+
+    /// Description of the event for `delivery_date`, with the week number
+    /// and holiday.
+    fn description(&self, delivery_date: DeliveryDate) -> Option<String> {
+        let date = delivery_date.date;
+        let week = self.week_numbers.then(|| date.iso_week().week());
+        let locale = self.locale(delivery_date.postal_code);
+        match (week, holidays::holiday(date)) {
+            (Some(week), Some(holiday)) => Some(format!(
+                "{}\n{}",
+                locale.week(week),
+                locale.holiday(holiday)
+            )),
+            (Some(week), None) => Some(locale.week(week)),
+            (None, Some(holiday)) => Some(locale.holiday(holiday)),
+            (None, None) => None,
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Calendar {
+    /// A calendar with arbitrary dates, texts and settings.
+    ///
+    /// The line length is kept between 10 and [`MAX_LINE_LENGTH`], like
+    /// the command line allows.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let time = |u: &mut arbitrary::Unstructured<'a>| {
+            NaiveTime::from_num_seconds_from_midnight_opt(u.int_in_range(0..=86_399)?, 0)
+                .ok_or(arbitrary::Error::IncorrectFormat)
+        };
+        let window = if u.arbitrary()? {
+            let (start, end) = (time(u)?, time(u)?);
+            (start < end).then_some(DeliveryWindow { start, end })
+        } else {
+            None
+        };
+        let alarm = if u.arbitrary()? {
+            Some(Alarm {
+                trigger: AlarmTrigger::Before(core::time::Duration::from_secs(
+                    u.int_in_range(0..=7 * 24 * 60 * 60)?,
+                )),
+                action: if u.arbitrary()? {
+                    AlarmAction::Email(u.arbitrary()?)
+                } else {
+                    AlarmAction::Display
+                },
+            })
+        } else {
+            None
+        };
+        let created = DateTime::from_timestamp(u.int_in_range(0..=4_102_444_799)?, 0);
+        Ok(Self::new(u.arbitrary()?, created)
+            .with_cancelled(u.arbitrary()?)
+            .with_week_numbers(u.arbitrary()?)
+            .with_url(u.arbitrary()?)
+            .with_window(window)
+            .with_alarm(alarm)
+            .with_outlook(u.arbitrary()?)
+            .with_color(u.arbitrary()?)
+            .with_line_length(u.int_in_range(10..=MAX_LINE_LENGTH)?)
+            .with_line_ending(if u.arbitrary()? {
+                LineEnding::Lf
+            } else {
+                LineEnding::Crlf
+            })
+            .with_bom(u.arbitrary()?)
+            .with_names(u.arbitrary()?)
+            .with_locale(if u.arbitrary()? {
+                Locale::English
+            } else {
+                Locale::Norwegian
+            })
+            .with_summary_template(u.arbitrary()?)
+            .with_calendar_name(u.arbitrary()?))
     }
 }
 
@@ -53,12 +533,387 @@ impl From<Vec<DeliveryDate>> for Calendar {
 
 impl Calendar {
     #[must_use]
-    pub fn new(delivery_dates: Vec<DeliveryDate>, created: Option<DateTime<Utc>>) -> Self {
+    pub const fn new(delivery_dates: Vec<DeliveryDate>, created: Option<DateTime<Utc>>) -> Self {
         Self {
             delivery_dates,
+            cancelled: Vec::new(),
+            sequences: BTreeMap::new(),
             created,
+            week_numbers: false,
+            url: Some(Cow::Borrowed(DEFAULT_URL)),
+            window: None,
+            alarm: None,
+            outlook: false,
+            color: None,
+            line_length: MAX_LINE_LENGTH,
+            line_ending: LineEnding::Crlf,
+            bom: false,
+            names: BTreeMap::new(),
+            locale: Locale::Norwegian,
+            summary_template: None,
+            name: None,
+            event_settings: BTreeMap::new(),
         }
     }
+
+    #[must_use]
+    /// Include `cancelled` delivery dates as events with `STATUS:CANCELLED`.
+    ///
+    /// The events keep the UID of the originally published event, so
+    /// calendar clients remove or strike out the stale event.
+    ///
+    /// ```
+    /// use chrono::{DateTime, FixedOffset, NaiveDate};
+    /// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::calendar::Calendar;
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = NaiveDate::from_ymd_opt(1970, 8, 13).unwrap();
+    /// let created = Some(DateTime::<FixedOffset>::parse_from_rfc3339("1970-08-13T00:00:00Z").unwrap().into());
+    /// let calendar = Calendar::new(vec![], created)
+    ///     .with_cancelled(vec![DeliveryDate::new(postal_code, date)]);
+    /// let ical_str = calendar.to_string();
+    ///
+    /// assert!(ical_str.contains(
+    ///     "DTSTART;VALUE=DATE:19700813\r\n\
+    ///      STATUS:CANCELLED\r\n\
+    ///      SUMMARY:7800: Posten kommer torsdag 13. august.\r\n"));
+    /// assert!(ical_str.contains("UID:postgang-7800-1970-08-13\r\n"));
+    /// ```
+    pub fn with_cancelled(mut self, cancelled: Vec<DeliveryDate>) -> Self {
+        self.cancelled = cancelled;
+        self
+    }
+
+    #[must_use]
+    /// Emit `SEQUENCE` for events with a known revision, keyed by UID.
+    pub fn with_sequences(mut self, sequences: BTreeMap<String, u32>) -> Self {
+        self.sequences = sequences;
+        self
+    }
+
+    #[must_use]
+    /// Put the ISO week number of each event in its `DESCRIPTION`.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::calendar::Calendar;
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+    /// let ical_str = Calendar::new(vec![date], None).with_week_numbers(true).to_string();
+    /// assert!(ical_str.contains("BEGIN:VEVENT\r\nDESCRIPTION:Uke 33\r\n"));
+    /// ```
+    pub const fn with_week_numbers(mut self, week_numbers: bool) -> Self {
+        self.week_numbers = week_numbers;
+        self
+    }
+
+    #[must_use]
+    /// Link the events to `url` instead of [`DEFAULT_URL`], or leave out
+    /// `URL` when `None`.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::calendar::Calendar;
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+    /// let calendar = Calendar::new(vec![date], None);
+    /// let ical_str = calendar.clone().with_url(Some("https://example.com/post".to_string())).to_string();
+    /// assert!(ical_str.contains("\r\nURL:https://example.com/post\r\n"));
+    /// assert!(!calendar.with_url(None).to_string().contains("URL:"));
+    /// ```
+    pub fn with_url(mut self, url: Option<String>) -> Self {
+        self.url = url.map(Cow::Owned);
+        self
+    }
+
+    #[must_use]
+    /// Emit timed events during `window` instead of all-day events.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::calendar::Calendar;
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+    /// let ical_str = Calendar::new(vec![date], None)
+    ///     .with_window(Some("09:00-16:00".parse().unwrap()))
+    ///     .to_string();
+    /// assert!(ical_str.contains("DTEND:19700813T160000\r\n"));
+    /// assert!(ical_str.contains("DTSTART:19700813T090000\r\n"));
+    /// ```
+    pub const fn with_window(mut self, window: Option<DeliveryWindow>) -> Self {
+        self.window = window;
+        self
+    }
+
+    #[must_use]
+    /// Add `alarm` to each event that is not cancelled.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::calendar::{Alarm, AlarmAction, Calendar};
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+    /// let alarm = Alarm {
+    ///     trigger: "1d@20:00".parse().unwrap(),
+    ///     action: AlarmAction::Display,
+    /// };
+    /// let ical_str = Calendar::new(vec![date], None).with_alarm(Some(alarm)).to_string();
+    /// assert!(ical_str.contains(
+    ///     "BEGIN:VALARM\r\n\
+    ///      ACTION:DISPLAY\r\n\
+    ///      DESCRIPTION:7800: Posten kommer torsdag 13. august.\r\n\
+    ///      TRIGGER:-PT4H\r\n\
+    ///      END:VALARM\r\n"
+    /// ));
+    /// ```
+    pub fn with_alarm(mut self, alarm: Option<Alarm>) -> Self {
+        self.alarm = alarm;
+        self
+    }
+
+    #[must_use]
+    /// Emit the Microsoft properties Outlook reads instead of `TRANSP`.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::calendar::Calendar;
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+    /// let ical_str = Calendar::new(vec![date], None).with_outlook(true).to_string();
+    /// assert!(ical_str.contains(
+    ///     "X-MICROSOFT-CDO-BUSYSTATUS:FREE\r\n\
+    ///      X-MICROSOFT-CDO-ALLDAYEVENT:TRUE\r\n\
+    ///      END:VEVENT\r\n"
+    /// ));
+    /// ```
+    pub const fn with_outlook(mut self, outlook: bool) -> Self {
+        self.outlook = outlook;
+        self
+    }
+
+    #[must_use]
+    /// Suggest `color`, a hex value like `#1E90FF`, for the calendar in
+    /// Apple Calendar.
+    pub fn with_color(mut self, color: Option<String>) -> Self {
+        self.color = color;
+        self
+    }
+
+    #[must_use]
+    /// Fold content lines longer than `line_length` octets instead of
+    /// [`MAX_LINE_LENGTH`].
+    ///
+    /// Lines are folded at whitespace when possible, and never inside a
+    /// UTF-8 character.
+    pub const fn with_line_length(mut self, line_length: usize) -> Self {
+        self.line_length = line_length;
+        self
+    }
+
+    #[must_use]
+    /// End lines with `line_ending`, also when folding.
+    pub const fn with_line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    #[must_use]
+    /// Start the output with a UTF-8 byte order mark.
+    ///
+    /// RFC 5545 output has none, but some importers need it to read the
+    /// file as UTF-8.
+    pub const fn with_bom(mut self, bom: bool) -> Self {
+        self.bom = bom;
+        self
+    }
+
+    #[must_use]
+    /// Show `names` instead of the postal codes in the summaries.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::calendar::Calendar;
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+    /// let ical_str = Calendar::new(vec![date], None)
+    ///     .with_names([(postal_code, "hjemme".to_string())].into())
+    ///     .to_string();
+    /// assert!(ical_str.contains("SUMMARY:hjemme: Posten kommer torsdag 13. august."));
+    /// assert!(ical_str.contains("UID:postgang-7800-"));
+    /// ```
+    pub fn with_names(mut self, names: BTreeMap<NorwegianPostalCode, String>) -> Self {
+        self.names = names;
+        self
+    }
+
+    #[must_use]
+    /// Write the summaries in `locale`.
+    pub const fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    #[must_use]
+    /// Use the `settings` of postal codes for their events instead of those
+    /// of the calendar.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::calendar::{Calendar, EventSettings};
+    /// use postgang::locale::Locale;
+    ///
+    /// let cabin = NorwegianPostalCode::try_from("2450").unwrap();
+    /// let date = NaiveDate::from_ymd_opt(1970, 8, 13).unwrap();
+    /// let dates = vec![
+    ///     DeliveryDate::new(NorwegianPostalCode::try_from("7800").unwrap(), date),
+    ///     DeliveryDate::new(cabin, date),
+    /// ];
+    /// let settings = EventSettings {
+    ///     locale: Some(Locale::English),
+    ///     ..EventSettings::default()
+    /// };
+    /// let ical_str = Calendar::new(dates, None)
+    ///     .with_event_settings([(cabin, settings)].into())
+    ///     .to_string();
+    /// assert!(ical_str.contains("SUMMARY:7800: Posten kommer torsdag 13. august."));
+    /// assert!(ical_str.contains("SUMMARY:2450: Mail arrives Thursday 13 August."));
+    /// ```
+    pub fn with_event_settings(
+        mut self,
+        settings: BTreeMap<NorwegianPostalCode, EventSettings>,
+    ) -> Self {
+        self.event_settings = settings;
+        self
+    }
+
+    #[must_use]
+    /// Make the summaries from `template`, see [`format_summary`].
+    pub fn with_summary_template(mut self, template: Option<String>) -> Self {
+        self.summary_template = template;
+        self
+    }
+
+    #[must_use]
+    /// Name the calendar with `X-WR-CALNAME`, shown by calendar apps.
+    ///
+    /// ```
+    /// use postgang::calendar::Calendar;
+    ///
+    /// let ical_str = Calendar::new(vec![], None)
+    ///     .with_calendar_name(Some("Posten hjemme".to_string()))
+    ///     .to_string();
+    /// assert!(ical_str.contains("METHOD:PUBLISH\r\nX-WR-CALNAME:Posten hjemme\r\n"));
+    /// ```
+    pub fn with_calendar_name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    #[must_use]
+    /// Fingerprint of each event's content, keyed by UID.
+    ///
+    /// `DTSTAMP` and `SEQUENCE` are not part of the fingerprint, so it only
+    /// changes when the event itself changes. The fingerprint is a 64-bit
+    /// FNV-1a hash, which is stable across runs and builds.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::calendar::Calendar;
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+    /// let active = Calendar::new(vec![date], None).fingerprints();
+    /// let cancelled = Calendar::new(vec![], None).with_cancelled(vec![date]).fingerprints();
+    ///
+    /// assert_eq!(active[0].0, "postgang-7800-1970-08-13");
+    /// assert_eq!(active, Calendar::new(vec![date], None).fingerprints());
+    /// assert_ne!(active, cancelled);
+    /// ```
+    pub fn fingerprints(&self) -> Vec<(String, u64)> {
+        content_line::fingerprints(self)
+    }
+
+    /// Write the calendar to `writer`, without building the whole
+    /// [`Calendar::to_string`] first.
+    ///
+    /// `writer` gets many small writes, so a file or socket should be
+    /// wrapped in a [`std::io::BufWriter`].
+    ///
+    /// ```
+    /// use postgang::calendar::Calendar;
+    ///
+    /// let calendar = Calendar::new(vec![], None);
+    /// let mut buffer = Vec::new();
+    /// calendar.write_to(&mut buffer).unwrap();
+    /// assert_eq!(buffer, calendar.to_string().as_bytes());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error returned by `writer`.
+    pub fn write_to(&self, writer: &mut (impl std::io::Write + ?Sized)) -> std::io::Result<()> {
+        write!(writer, "{self}")
+    }
+
+    #[must_use]
+    /// The calendar as an RFC 8984 [`jscalendar::Group`], for `--format
+    /// jscalendar`.
+    pub fn to_jscalendar(&self) -> jscalendar::Group {
+        jscalendar::group(self)
+    }
+}
+
+#[must_use]
+/// Like [`summary`], but reads "i dag" or "i morgen" for deliveries on
+/// `today` or the day after.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::calendar::relative_summary;
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+/// let today = NaiveDate::from_ymd_opt(1970, 8, 12).unwrap();
+/// assert_eq!(relative_summary(date, today), "7800: Posten kommer i morgen.");
+/// assert_eq!(relative_summary(date, date.date), "7800: Posten kommer i dag.");
+/// assert_eq!(
+///     relative_summary(date, NaiveDate::from_ymd_opt(1970, 8, 1).unwrap()),
+///     "7800: Posten kommer torsdag 13. august."
+/// );
+/// ```
+pub fn relative_summary(delivery_date: DeliveryDate, today: NaiveDate) -> String {
+    let day = match (delivery_date.date - today).num_days() {
+        0 => "i dag",
+        1 => "i morgen",
+        _ => return summary(delivery_date),
+    };
+    format!("{}: Posten kommer {day}.", delivery_date.postal_code)
 }
 
 impl fmt::Display for Calendar {
@@ -88,7 +943,7 @@ impl fmt::Display for Calendar {
     ///      DTEND;VALUE=DATE:19700814\r\n\
     ///      DTSTAMP:19700813T000000Z\r\n\
     ///      DTSTART;VALUE=DATE:19700813\r\n\
-    ///      SUMMARY:7800: Posten kommer torsdag 13.\r\n\
+    ///      SUMMARY:7800: Posten kommer torsdag 13. august.\r\n\
     ///      TRANSP:TRANSPARENT\r\n\
     ///      UID:postgang-7800-1970-08-13\r\n\
     ///      URL:https://www.posten.no/levering-av-post/\r\n\
@@ -107,106 +962,282 @@ impl fmt::Display for Calendar {
     ///      END:VCALENDAR\r\n");
     /// ```
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for content_line in self.content_lines() {
-            content_line.fmt(f)?;
+        if self.bom {
+            f.write_char('\u{feff}')?;
         }
-        Ok(())
+        content_line::write_calendar(
+            self,
+            &mut Folded::new(f, self.line_length, self.line_ending),
+        )
     }
 }
 
 mod content_line {
-    use core::fmt;
+    use alloc::borrow::Cow;
+    use core::fmt::{self, Write as _};
 
-    use crate::bring_client::mailbox_delivery_dates::DeliveryDate;
+    use crate::{bring_client::mailbox_delivery_dates::DeliveryDate, fnv1a};
 
     use super::{
-        format_naive_date, format_timestamp, weekday, Calendar, DateTime, Datelike, Duration, Utc,
+        format_duration, format_local_time, format_naive_date, format_timestamp, uid, Alarm,
+        AlarmAction, Calendar, DateTime, Duration, LineEnding, Utc, MAX_LINE_LENGTH,
     };
 
     #[derive(Debug)]
-    pub(super) struct ContentLine(String);
-
-    impl From<&str> for ContentLine {
-        fn from(x: &str) -> Self {
-            Self(x.to_string())
-        }
-    }
+    pub(super) struct ContentLine<'a>(&'a str);
 
-    impl From<String> for ContentLine {
-        fn from(x: String) -> Self {
+    impl<'a> From<&'a str> for ContentLine<'a> {
+        fn from(x: &'a str) -> Self {
             Self(x)
         }
     }
 
-    impl fmt::Display for ContentLine {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    impl ContentLine<'_> {
+        /// Write the line folded to at most `line_length` octets per line,
+        /// ending each line with `line_ending`.
+        pub(super) fn write(
+            &self,
+            f: &mut impl fmt::Write,
+            line_length: usize,
+            line_ending: LineEnding,
+        ) -> fmt::Result {
+            let line_ending = line_ending.as_str();
             if self.0.is_empty() {
                 return Ok(());
             }
-            let content = self.0.replace('\n', "\\n");
-            let mut content = content.as_str();
-            let mut boundary = next_boundary(&ContentLineToPrint::First(content));
+            let content = sanitize(self.0);
+            let mut content = content.as_ref();
+            let mut boundary = next_boundary(&ContentLineToPrint::First(content), line_length);
             f.write_str(&content[..boundary])?;
 
             while boundary < content.len() {
                 content = &content[boundary..];
-                f.write_str("\r\n ")?;
-                boundary = next_boundary(&ContentLineToPrint::Subsequent(content));
+                f.write_str(line_ending)?;
+                f.write_str(" ")?;
+                boundary = next_boundary(&ContentLineToPrint::Subsequent(content), line_length);
                 f.write_str(&content[..boundary])?;
             }
-            f.write_str("\r\n")
+            f.write_str(line_ending)
+        }
+    }
+
+    /// Escape newlines and drop the other ASCII control characters, which
+    /// are not allowed in content lines.
+    fn sanitize(content: &str) -> Cow<'_, str> {
+        if !content.bytes().any(|c| c != b'\t' && c.is_ascii_control()) {
+            return Cow::Borrowed(content);
+        }
+        Cow::Owned(
+            content
+                .replace("\r\n", "\n")
+                .chars()
+                .filter(|&c| c == '\n' || c == '\t' || !c.is_ascii_control())
+                .collect::<String>()
+                .replace('\n', "\\n"),
+        )
+    }
+
+    impl fmt::Display for ContentLine<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.write(f, MAX_LINE_LENGTH, LineEnding::Crlf)
+        }
+    }
+
+    /// Receives the content lines of a calendar, one at a time.
+    pub(super) trait Lines {
+        fn line(&mut self, line: fmt::Arguments<'_>) -> fmt::Result;
+    }
+
+    /// Folds and writes each line to `out`.
+    ///
+    /// Lines with arguments are formatted into one buffer reused for all
+    /// lines, so no line is allocated on its own.
+    pub(super) struct Folded<'a, W> {
+        out: &'a mut W,
+        buffer: String,
+        line_length: usize,
+        line_ending: LineEnding,
+    }
+
+    impl<'a, W: fmt::Write> Folded<'a, W> {
+        pub(super) const fn new(
+            out: &'a mut W,
+            line_length: usize,
+            line_ending: LineEnding,
+        ) -> Self {
+            Self {
+                out,
+                buffer: String::new(),
+                line_length,
+                line_ending,
+            }
+        }
+    }
+
+    impl<W: fmt::Write> Lines for Folded<'_, W> {
+        fn line(&mut self, line: fmt::Arguments<'_>) -> fmt::Result {
+            if let Some(line) = line.as_str() {
+                return ContentLine(line).write(self.out, self.line_length, self.line_ending);
+            }
+            self.buffer.clear();
+            self.buffer.write_fmt(line)?;
+            ContentLine(&self.buffer).write(self.out, self.line_length, self.line_ending)
+        }
+    }
+
+    #[derive(Default)]
+    /// The lines of an event that make up its fingerprint, each followed
+    /// by a newline.
+    struct Fingerprint(String);
+
+    impl Lines for Fingerprint {
+        fn line(&mut self, line: fmt::Arguments<'_>) -> fmt::Result {
+            let start = self.0.len();
+            self.0.write_fmt(line)?;
+            let line = &self.0[start..];
+            if line.starts_with("DTSTAMP:") || line.starts_with("SEQUENCE:") {
+                self.0.truncate(start);
+            } else {
+                self.0.push('\n');
+            }
+            Ok(())
         }
     }
 
     #[derive(Debug)]
-    struct DeliveryDateEntry {
+    struct DeliveryDateEntry<'a> {
         delivery_date: DeliveryDate,
-        created: Option<DateTime<Utc>>,
-    }
-
-    impl From<&DeliveryDateEntry> for Vec<ContentLine> {
-        fn from(value: &DeliveryDateEntry) -> Self {
-            let date = value.delivery_date.date;
-            let dt_end = format_naive_date(date + Duration::days(1));
-            let timestamp = format_timestamp(&(value.created.unwrap_or_else(Utc::now)));
-            let dt_start = format_naive_date(date);
-            let postal_code = value.delivery_date.postal_code;
-            let weekday = weekday(value.delivery_date.date);
-            let day = value.delivery_date.date.day();
-            vec![
-                "BEGIN:VEVENT".into(),
-                format!("DTEND;VALUE=DATE:{dt_end}").into(),
-                format!("DTSTAMP:{timestamp}").into(),
-                format!("DTSTART;VALUE=DATE:{dt_start}").into(),
-                format!("SUMMARY:{postal_code}: Posten kommer {weekday} {day}.").into(),
-                "TRANSP:TRANSPARENT".into(),
-                format!("UID:postgang-{postal_code}-{date}").into(),
-                "URL:https://www.posten.no/levering-av-post/".into(),
-                "END:VEVENT".into(),
-            ]
-        }
-    }
-
-    impl From<&Calendar> for Vec<ContentLine> {
-        fn from(value: &Calendar) -> Self {
-            let mut res: Self = vec![
-                "BEGIN:VCALENDAR".into(),
-                "VERSION:2.0".into(),
-                "PRODID:-//Aasan//Aasan Postgang//EN".into(),
-                "CALSCALE:GREGORIAN".into(),
-                "METHOD:PUBLISH".into(),
-            ];
-            res.extend(value.delivery_dates.iter().flat_map(|x| {
-                let xs: Self = (&DeliveryDateEntry {
-                    delivery_date: *x,
-                    created: value.created,
-                })
-                    .into();
-                xs
-            }));
-            res.push("END:VCALENDAR".into());
-            res
+        cancelled: bool,
+        calendar: &'a Calendar,
+        /// `DTSTAMP` of the event, the same for all events of the calendar.
+        timestamp: DateTime<Utc>,
+    }
+
+    fn entries(value: &Calendar) -> impl Iterator<Item = DeliveryDateEntry<'_>> + '_ {
+        let timestamp = value.created.unwrap_or_else(Utc::now);
+        let active = value.delivery_dates.iter().map(|x| (x, false));
+        let cancelled = value.cancelled.iter().map(|x| (x, true));
+        active
+            .chain(cancelled)
+            .map(move |(x, cancelled)| DeliveryDateEntry {
+                delivery_date: *x,
+                cancelled,
+                calendar: value,
+                timestamp,
+            })
+    }
+
+    pub(super) fn fingerprints(value: &Calendar) -> Vec<(String, u64)> {
+        entries(value)
+            .map(|entry| {
+                let mut fingerprint = Fingerprint::default();
+                // Writing to a String can't fail
+                let _ = write_event(&entry, &mut fingerprint);
+                (uid(entry.delivery_date), fnv1a(fingerprint.0.bytes()))
+            })
+            .collect()
+    }
+
+    fn write_event(value: &DeliveryDateEntry<'_>, out: &mut impl Lines) -> fmt::Result {
+        let calendar = value.calendar;
+        let date = value.delivery_date.date;
+        let uid = uid(value.delivery_date);
+        let summary = calendar.summary(value.delivery_date);
+        out.line(format_args!("BEGIN:VEVENT"))?;
+        if let Some(description) = calendar.description(value.delivery_date) {
+            out.line(format_args!("DESCRIPTION:{description}"))?;
+        }
+        match calendar.window {
+            Some(window) => out.line(format_args!(
+                "DTEND:{}",
+                format_local_time(date.and_time(window.end))
+            ))?,
+            None => out.line(format_args!(
+                "DTEND;VALUE=DATE:{}",
+                format_naive_date(date + Duration::days(1))
+            ))?,
+        }
+        out.line(format_args!(
+            "DTSTAMP:{}",
+            format_timestamp(&value.timestamp)
+        ))?;
+        match calendar.window {
+            Some(window) => out.line(format_args!(
+                "DTSTART:{}",
+                format_local_time(date.and_time(window.start))
+            ))?,
+            None => out.line(format_args!(
+                "DTSTART;VALUE=DATE:{}",
+                format_naive_date(date)
+            ))?,
+        }
+        if let Some(sequence) = calendar.sequences.get(&uid) {
+            out.line(format_args!("SEQUENCE:{sequence}"))?;
+        }
+        if value.cancelled {
+            out.line(format_args!("STATUS:CANCELLED"))?;
+        }
+        out.line(format_args!("SUMMARY:{summary}"))?;
+        out.line(format_args!("TRANSP:TRANSPARENT"))?;
+        out.line(format_args!("UID:{uid}"))?;
+        if let Some(url) = &calendar.url {
+            out.line(format_args!("URL:{url}"))?;
+        }
+        if calendar.outlook {
+            out.line(format_args!("X-MICROSOFT-CDO-BUSYSTATUS:FREE"))?;
+            if calendar.window.is_none() {
+                out.line(format_args!("X-MICROSOFT-CDO-ALLDAYEVENT:TRUE"))?;
+            }
         }
+        if let Some(alarm) = calendar
+            .alarm(value.delivery_date.postal_code)
+            .filter(|_| !value.cancelled)
+        {
+            write_alarm(alarm, value, &summary, out)?;
+        }
+        out.line(format_args!("END:VEVENT"))
+    }
+
+    fn write_alarm(
+        alarm: &Alarm,
+        entry: &DeliveryDateEntry<'_>,
+        summary: &str,
+        out: &mut impl Lines,
+    ) -> fmt::Result {
+        let trigger = alarm.offset(entry.delivery_date.date, entry.calendar.window);
+        out.line(format_args!("BEGIN:VALARM"))?;
+        match &alarm.action {
+            AlarmAction::Display => out.line(format_args!("ACTION:DISPLAY"))?,
+            AlarmAction::Email(address) => {
+                out.line(format_args!("ACTION:EMAIL"))?;
+                out.line(format_args!("ATTENDEE:mailto:{address}"))?;
+            }
+        }
+        out.line(format_args!("DESCRIPTION:{summary}"))?;
+        if matches!(alarm.action, AlarmAction::Email(_)) {
+            out.line(format_args!("SUMMARY:{summary}"))?;
+        }
+        out.line(format_args!("TRIGGER:{}", format_duration(trigger)))?;
+        out.line(format_args!("END:VALARM"))
+    }
+
+    /// Write the content lines of `value` to `out`.
+    pub(super) fn write_calendar(value: &Calendar, out: &mut impl Lines) -> fmt::Result {
+        out.line(format_args!("BEGIN:VCALENDAR"))?;
+        out.line(format_args!("VERSION:2.0"))?;
+        out.line(format_args!("PRODID:-//Aasan//Aasan Postgang//EN"))?;
+        out.line(format_args!("CALSCALE:GREGORIAN"))?;
+        out.line(format_args!("METHOD:PUBLISH"))?;
+        if let Some(name) = &value.name {
+            out.line(format_args!("X-WR-CALNAME:{name}"))?;
+        }
+        if let Some(color) = &value.color {
+            out.line(format_args!("X-APPLE-CALENDAR-COLOR:{color}"))?;
+        }
+        for entry in entries(value) {
+            write_event(&entry, out)?;
+        }
+        out.line(format_args!("END:VCALENDAR"))
     }
 
     enum ContentLineToPrint<'a> {
@@ -214,17 +1245,23 @@ mod content_line {
         Subsequent(&'a str),
     }
 
-    fn next_boundary(content: &ContentLineToPrint) -> usize {
-        const MAX_LINE: usize = 75;
+    /// Where to fold `content`, preferring the last whitespace that fits.
+    fn next_boundary(content: &ContentLineToPrint, line_length: usize) -> usize {
         let (content, limit) = match content {
-            ContentLineToPrint::First(x) => (x, MAX_LINE),
-            ContentLineToPrint::Subsequent(x) => (x, MAX_LINE - 1),
+            ContentLineToPrint::First(x) => (x, line_length),
+            ContentLineToPrint::Subsequent(x) => (x, line_length.saturating_sub(1)),
         };
         let content = content.as_bytes();
         let num_bytes = content.len();
         if limit >= num_bytes {
             return num_bytes;
         }
+        if let Some(i) = content[1..=limit]
+            .iter()
+            .rposition(|&c| c == b' ' || c == b'\t')
+        {
+            return i + 1;
+        }
         match content[..=limit]
             .iter()
             .rposition(|&c| !(128..192).contains(&c))
@@ -239,7 +1276,15 @@ mod content_line {
         let line = ContentLine::from(
             "123456789 123456789 123456789 123456789 123456789 123456789 123456789 123456789 ",
         );
-        assert_eq!(format!("{line}"), String::from("123456789 123456789 123456789 123456789 123456789 123456789 123456789 12345\r\n 6789 \r\n"));
+        assert_eq!(format!("{line}"), String::from("123456789 123456789 123456789 123456789 123456789 123456789 123456789\r\n  123456789 \r\n"));
+    }
+
+    #[test]
+    fn test_output_line_display_no_whitespace() {
+        let line = ContentLine::from(
+            "URL:https://example.com/123456789/123456789/123456789/123456789/123456789/123456789",
+        );
+        assert_eq!(format!("{line}"), String::from("URL:https://example.com/123456789/123456789/123456789/123456789/123456789/1\r\n 23456789\r\n"));
     }
 
     #[test]
@@ -270,9 +1315,194 @@ mod content_line {
         assert_eq!(format!("{line}"), "A\\nnna\r\n");
     }
 
+    #[test]
+    fn test_output_line_display_control_characters() {
+        let line = ContentLine::from("SUMMARY:A\r\nB\u{1b}[31m\rC\tD\u{7f}");
+        assert_eq!(format!("{line}"), "SUMMARY:A\\nB[31mC\tD\r\n");
+    }
+
     #[test]
     fn test_output_line_display_empty() {
         let line = ContentLine::from("");
         assert_eq!(format!("{line}"), "");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::{format_duration, Calendar, EventSettings, LineEnding};
+    use crate::{
+        bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode},
+        locale::Locale,
+    };
+
+    #[test]
+    fn test_locale_and_template() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(2024, 9, 3).unwrap());
+        let calendar = Calendar::new(vec![date], None).with_locale(Locale::English);
+        assert!(calendar
+            .to_string()
+            .contains("SUMMARY:7800: Mail arrives Tuesday 3 September.\r\n"));
+        let calendar = calendar
+            .with_names([(postal_code, "cabin".to_string())].into())
+            .with_summary_template(Some("Mail at the {name} on {weekday}".to_string()));
+        assert!(calendar
+            .to_string()
+            .contains("SUMMARY:Mail at the cabin on Tuesday\r\n"));
+    }
+
+    #[test]
+    fn test_holiday_description() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = |month, day| {
+            DeliveryDate::new(
+                postal_code,
+                NaiveDate::from_ymd_opt(2024, month, day).unwrap(),
+            )
+        };
+        let calendar = Calendar::new(vec![date(5, 16), date(5, 17)], None).to_string();
+        assert_eq!(calendar.matches("DESCRIPTION:").count(), 1);
+        assert!(calendar.contains(
+            "BEGIN:VEVENT\r\n\
+             DESCRIPTION:Merk: grunnlovsdag er en helligdag.\r\n\
+             DTEND;VALUE=DATE:20240518\r\n"
+        ));
+        let calendar = Calendar::new(vec![date(5, 17)], None)
+            .with_locale(Locale::English)
+            .with_week_numbers(true)
+            .to_string();
+        assert!(
+            calendar.contains("DESCRIPTION:Week 20\\nNote: grunnlovsdag is a public holiday.\r\n")
+        );
+    }
+
+    #[test]
+    fn test_event_settings() {
+        let home = NorwegianPostalCode::try_from("7800").unwrap();
+        let cabin = NorwegianPostalCode::try_from("2450").unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 17).unwrap();
+        let settings = EventSettings {
+            locale: Some(Locale::English),
+            ..EventSettings::default()
+        };
+        let calendar = Calendar::new(
+            vec![
+                DeliveryDate::new(home, date),
+                DeliveryDate::new(cabin, date),
+            ],
+            None,
+        )
+        .with_event_settings([(cabin, settings)].into())
+        .to_string();
+        assert!(calendar.contains("DESCRIPTION:Merk: grunnlovsdag er en helligdag.\r\n"));
+        assert!(calendar.contains("DESCRIPTION:Note: grunnlovsdag is a public holiday.\r\n"));
+        assert!(calendar.contains("SUMMARY:2450: Mail arrives Friday 17 May.\r\n"));
+        assert!(!calendar.contains("BEGIN:VALARM"));
+    }
+
+    #[test]
+    fn test_color() {
+        let calendar = Calendar::new(vec![], None)
+            .with_color(Some("#1E90FF".to_string()))
+            .to_string();
+        assert!(calendar.contains("METHOD:PUBLISH\r\nX-APPLE-CALENDAR-COLOR:#1E90FF\r\n"));
+    }
+
+    #[test]
+    fn test_line_ending() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(2024, 9, 2).unwrap());
+        let calendar = Calendar::new(vec![date], None)
+            .with_line_length(20)
+            .with_line_ending(LineEnding::Lf)
+            .to_string();
+        assert!(!calendar.contains('\r'));
+        assert!(calendar.contains("SUMMARY:7800: Posten\n  kommer mandag 2.\n  september.\n"));
+        assert!(calendar.lines().all(|line| line.len() <= 20));
+    }
+
+    #[test]
+    fn test_bom() {
+        let calendar = Calendar::new(vec![], None).with_bom(true).to_string();
+        assert!(calendar
+            .as_bytes()
+            .starts_with(b"\xef\xbb\xbfBEGIN:VCALENDAR\r\n"));
+        assert!(Calendar::new(vec![], None)
+            .to_string()
+            .starts_with("BEGIN:"));
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        use crate::ics;
+
+        // Seeded to be reproducible, the seed is in the message of failures
+        for seed in 0..200 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let bytes: Vec<u8> = core::iter::repeat_with(|| rng.u8(..)).take(4096).collect();
+            let Ok(calendar) = Calendar::arbitrary(&mut Unstructured::new(&bytes)) else {
+                continue;
+            };
+            let line_length = calendar.line_length;
+            let output = calendar.to_string();
+            let output = output.strip_prefix('\u{feff}').unwrap_or(&output);
+            for line in output.lines() {
+                assert!(
+                    line.len() <= line_length,
+                    "seed {seed}: {line:?} is too long"
+                );
+                assert!(
+                    !line.bytes().any(|c| c != b'\t' && c.is_ascii_control()),
+                    "seed {seed}: {line:?} has control characters"
+                );
+            }
+            let mut dates: Vec<_> = calendar
+                .delivery_dates
+                .iter()
+                .map(|date| (date.postal_code, date.date))
+                .collect();
+            dates.sort_unstable();
+            dates.dedup();
+            let events = ics::parse(output).unwrap_or_else(|err| panic!("seed {seed}: {err}"));
+            let parsed: Vec<_> = ics::delivery_dates(&events, None)
+                .unwrap_or_else(|err| panic!("seed {seed}: {err}"))
+                .into_iter()
+                .flat_map(|(code, dates)| dates.into_iter().map(move |date| (code, date)))
+                .collect();
+            assert_eq!(parsed, dates, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_single_timestamp() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let first = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+        let dates = first
+            .iter_days()
+            .take(500)
+            .map(|date| DeliveryDate::new(postal_code, date))
+            .collect();
+        let calendar = Calendar::new(dates, None).to_string();
+        let mut stamps: Vec<_> = calendar
+            .lines()
+            .filter(|line| line.starts_with("DTSTAMP:"))
+            .collect();
+        assert_eq!(stamps.len(), 500);
+        stamps.dedup();
+        assert_eq!(stamps.len(), 1);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(-4 * 60 * 60), "-PT4H");
+        assert_eq!(format_duration(7 * 60 * 60 + 30 * 60), "PT7H30M");
+        assert_eq!(format_duration(-5), "-PT5S");
+        assert_eq!(format_duration(0), "PT0S");
+    }
+}
@@ -0,0 +1,252 @@
+//! [JSCalendar](https://www.rfc-editor.org/rfc/rfc8984) version of a
+//! calendar, the JSON format of JMAP servers.
+//!
+//! The calendar becomes a `Group` with one `Event` per delivery date, with
+//! the same UIDs, summaries and alarms as the iCalendar output.
+use alloc::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, NaiveTime, Utc};
+use serde::Serialize;
+
+use super::{format_duration, uid, AlarmAction, Calendar, DeliveryDate};
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// A `Group` holding the events of a calendar.
+pub struct Group {
+    #[serde(rename = "@type")]
+    pub kind: &'static str,
+    pub uid: String,
+    pub prod_id: &'static str,
+    pub updated: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    pub entries: Vec<Event>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+/// An `Event` for a delivery date.
+pub struct Event {
+    #[serde(rename = "@type")]
+    pub kind: &'static str,
+    pub uid: String,
+    pub updated: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u32>,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Floating local time, like `DTSTART` in the iCalendar output.
+    pub start: String,
+    pub duration: String,
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub show_without_time: bool,
+    /// `confirmed` or `cancelled`.
+    pub status: &'static str,
+    pub free_busy_status: &'static str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub links: BTreeMap<&'static str, Link>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub alerts: BTreeMap<&'static str, Alert>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+/// A `Link` to a web page.
+pub struct Link {
+    #[serde(rename = "@type")]
+    pub kind: &'static str,
+    pub href: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+/// An `Alert` relative to the start of the event.
+pub struct Alert {
+    #[serde(rename = "@type")]
+    pub kind: &'static str,
+    pub trigger: OffsetTrigger,
+    /// `display` or `email`.
+    pub action: &'static str,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+/// An `OffsetTrigger` from the start of the event.
+pub struct OffsetTrigger {
+    #[serde(rename = "@type")]
+    pub kind: &'static str,
+    pub offset: String,
+}
+
+fn format_utc(timestamp: &DateTime<Utc>) -> String {
+    timestamp.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+fn event(
+    calendar: &Calendar,
+    delivery_date: DeliveryDate,
+    cancelled: bool,
+    updated: &str,
+) -> Event {
+    let date = delivery_date.date;
+    let uid = uid(delivery_date);
+    let (start, duration) = match calendar.window {
+        Some(window) => (
+            window.start,
+            format_duration((window.end - window.start).num_seconds()),
+        ),
+        None => (NaiveTime::MIN, "P1D".to_string()),
+    };
+    let alerts = calendar
+        .alarm(delivery_date.postal_code)
+        .into_iter()
+        .filter(|_| !cancelled)
+        .map(|alarm| {
+            let alert = Alert {
+                kind: "Alert",
+                trigger: OffsetTrigger {
+                    kind: "OffsetTrigger",
+                    offset: format_duration(alarm.offset(date, calendar.window)),
+                },
+                action: match alarm.action {
+                    AlarmAction::Display => "display",
+                    AlarmAction::Email(_) => "email",
+                },
+            };
+            ("1", alert)
+        })
+        .collect();
+    let links = calendar
+        .url
+        .iter()
+        .map(|url| {
+            let link = Link {
+                kind: "Link",
+                href: url.to_string(),
+            };
+            ("1", link)
+        })
+        .collect();
+    Event {
+        kind: "Event",
+        sequence: calendar.sequences.get(&uid).copied(),
+        uid,
+        updated: updated.to_string(),
+        title: calendar.summary(delivery_date),
+        description: calendar.description(delivery_date),
+        start: date.and_time(start).format("%Y-%m-%dT%H:%M:%S").to_string(),
+        duration,
+        show_without_time: calendar.window.is_none(),
+        status: if cancelled { "cancelled" } else { "confirmed" },
+        free_busy_status: "free",
+        links,
+        alerts,
+    }
+}
+
+/// The events of `calendar` as a `Group`.
+pub(super) fn group(calendar: &Calendar) -> Group {
+    let updated = format_utc(&calendar.created.unwrap_or_else(Utc::now));
+    let postal_codes: BTreeSet<_> = calendar
+        .delivery_dates
+        .iter()
+        .chain(&calendar.cancelled)
+        .map(|date| date.postal_code.to_string())
+        .collect();
+    let active = calendar.delivery_dates.iter().map(|date| (date, false));
+    let cancelled = calendar.cancelled.iter().map(|date| (date, true));
+    Group {
+        kind: "Group",
+        uid: ["postgang".to_string()]
+            .into_iter()
+            .chain(postal_codes)
+            .collect::<Vec<_>>()
+            .join("-"),
+        prod_id: "-//Aasan//Aasan Postgang//EN",
+        title: calendar.name.clone(),
+        color: calendar.color.clone(),
+        entries: active
+            .chain(cancelled)
+            .map(|(date, cancelled)| event(calendar, *date, cancelled, &updated))
+            .collect(),
+        updated,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use crate::{
+        bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode},
+        calendar::{Alarm, AlarmAction, Calendar},
+    };
+
+    #[test]
+    fn test_group() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let day =
+            |day| DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(2024, 9, day).unwrap());
+        let created = "2024-09-01T12:00:00Z".parse().ok();
+        let calendar = Calendar::new(vec![day(2)], created)
+            .with_cancelled(vec![day(4)])
+            .with_window("09:00-16:00".parse().ok())
+            .with_alarm(Some(Alarm {
+                trigger: "1d@20:00".parse().unwrap(),
+                action: AlarmAction::Display,
+            }))
+            .with_url(Some("https://example.com/".to_string()));
+        let json = serde_json::to_value(calendar.to_jscalendar()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "@type": "Group",
+                "uid": "postgang-7800",
+                "prodId": "-//Aasan//Aasan Postgang//EN",
+                "updated": "2024-09-01T12:00:00Z",
+                "entries": [
+                    {
+                        "@type": "Event",
+                        "uid": "postgang-7800-2024-09-02",
+                        "updated": "2024-09-01T12:00:00Z",
+                        "title": "7800: Posten kommer mandag 2. september.",
+                        "start": "2024-09-02T09:00:00",
+                        "duration": "PT7H",
+                        "status": "confirmed",
+                        "freeBusyStatus": "free",
+                        "links": {"1": {"@type": "Link", "href": "https://example.com/"}},
+                        "alerts": {"1": {
+                            "@type": "Alert",
+                            "trigger": {"@type": "OffsetTrigger", "offset": "-PT13H"},
+                            "action": "display"
+                        }}
+                    },
+                    {
+                        "@type": "Event",
+                        "uid": "postgang-7800-2024-09-04",
+                        "updated": "2024-09-01T12:00:00Z",
+                        "title": "7800: Posten kommer onsdag 4. september.",
+                        "start": "2024-09-04T09:00:00",
+                        "duration": "PT7H",
+                        "status": "cancelled",
+                        "freeBusyStatus": "free",
+                        "links": {"1": {"@type": "Link", "href": "https://example.com/"}}
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_all_day() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(2024, 12, 25).unwrap());
+        let group = Calendar::new(vec![date], None).to_jscalendar();
+        let event = &group.entries[0];
+        assert_eq!(event.start, "2024-12-25T00:00:00");
+        assert_eq!(event.duration, "P1D");
+        assert!(event.show_without_time);
+        assert!(event.description.is_some());
+    }
+}
@@ -0,0 +1,492 @@
+//! Configuration file.
+//!
+//! Settings given on the command line or in the environment take precedence
+//! over the configuration file.
+//!
+//! ```toml
+//! code = ["7800"]
+//! api-uid = "ola@example.com"
+//! api-key = "…"
+//! history = "/var/lib/postgang/history.json"
+//! output = "/var/www/postgang.ics"
+//!
+//! [aliases]
+//! home = "7800"
+//! cabin = "2450"
+//!
+//! [calendars.cabin]
+//! output = "/var/www/hytta.ics"
+//! name = "Posten på hytta"
+//! locale = "en"
+//! alarm = "1d@20:00"
+//! summary = "Post til {name} {weekday}"
+//!
+//! [telegram]
+//! token = "123456:…"
+//! chat-id = "-1001234567890"
+//!
+//! [matrix]
+//! homeserver = "https://matrix.example.com"
+//! token = "…"
+//! room-id = "!abcdefg:example.com"
+//!
+//! [smtp]
+//! host = "smtp.example.com"
+//! username = "ola@example.com"
+//! password = "…"
+//! from = "Postgang <ola@example.com>"
+//! to = ["ola@example.com", "kari@example.com"]
+//! attach-calendar = true
+//!
+//! [messages]
+//! changes = "Posten til {name} kommer {added}"
+//! reminder = "Post til {name} {weekday}"
+//! ```
+use alloc::collections::BTreeMap;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use toml_edit::{DocumentMut, Item, Table, TableLike, Value};
+
+use crate::{
+    bring_client::NorwegianPostalCode, io_error_to_string, notify::Secret, paths::base_dir,
+};
+
+/// Configuration file used when there is none for the user.
+pub const SYSTEM_CONFIG: &str = "/etc/postgang/config.toml";
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+/// Settings read from the configuration file.
+pub struct Config {
+    #[serde(default)]
+    pub code: Vec<NorwegianPostalCode>,
+    /// Left out of debug output like the key, as it is half of the
+    /// credentials.
+    pub api_uid: Option<Secret>,
+    pub api_key: Option<Secret>,
+    pub cache_dir: Option<PathBuf>,
+    pub history: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    /// Names that can be used instead of postal codes with `--code`.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, NorwegianPostalCode>,
+    /// Postal codes written to their own calendar, keyed by postal code or
+    /// alias.
+    #[serde(default)]
+    pub calendars: BTreeMap<String, CalendarConfig>,
+    pub telegram: Option<Telegram>,
+    pub matrix: Option<Matrix>,
+    pub smtp: Option<Smtp>,
+    pub messages: Option<Messages>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+/// Settings of the events of a single postal code, overriding the command
+/// line.
+pub struct CalendarConfig {
+    /// Where the calendar of its own is written. Without it, the events are
+    /// written to `--output`, or to the file of the postal code in
+    /// `--output-dir`.
+    pub output: Option<PathBuf>,
+    /// Calendar name shown by calendar apps, for a calendar of its own.
+    pub name: Option<String>,
+    /// Language of the summaries and descriptions, `nb` or `en`.
+    pub locale: Option<String>,
+    /// Alarm trigger like `--alarm`.
+    pub alarm: Option<String>,
+    /// Summary template, see [`crate::calendar::format_summary`].
+    pub summary: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+/// Telegram bot notifying about changes.
+pub struct Telegram {
+    /// Bot token from `@BotFather`.
+    pub token: Secret,
+    pub chat_id: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+/// Matrix account notifying about changes.
+pub struct Matrix {
+    pub homeserver: String,
+    /// Access token of the account.
+    pub token: Secret,
+    pub room_id: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+/// SMTP server sending email about changes, with the `smtp` feature.
+pub struct Smtp {
+    pub host: String,
+    /// Port, by default 587 for `starttls`, 465 for `tls` and 25 for `none`.
+    pub port: Option<u16>,
+    /// `starttls`, the default, `tls` or `none`.
+    pub tls: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+    /// Sender, like `Postgang <ola@example.com>`.
+    pub from: String,
+    /// Recipients.
+    pub to: Vec<String>,
+    /// Attach the calendar written to `output`.
+    #[serde(default)]
+    pub attach_calendar: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+/// Templates of the notification messages, see [`crate::notify::Templates`].
+pub struct Messages {
+    /// A line per postal code with changed delivery dates.
+    pub changes: Option<String>,
+    /// A reminder of the next delivery.
+    pub reminder: Option<String>,
+    /// Language of weekdays and months, `nb` or `en`.
+    pub locale: Option<String>,
+}
+
+impl Config {
+    /// Read the configuration from `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be read or is invalid.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content =
+            std::fs::read_to_string(path).map_err(|err| io_error_to_string(&err, path))?;
+        Self::parse(&content).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    /// Parse a configuration from TOML.
+    ///
+    /// ```
+    /// use postgang::config::Config;
+    ///
+    /// let config = Config::parse("code = [\"7800\"]\napi-uid = \"ola@example.com\"").unwrap();
+    /// assert_eq!(config.code[0].to_string(), "7800");
+    /// assert_eq!(config.api_uid.unwrap().0, "ola@example.com");
+    /// assert!(Config::parse("colour = \"blue\"").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `content` is not a valid configuration.
+    pub fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    /// Look up `code` as an alias, or else parse it as a postal code.
+    ///
+    /// ```
+    /// use postgang::config::Config;
+    ///
+    /// let config = Config::parse("[aliases]\nhome = \"7800\"").unwrap();
+    /// assert_eq!(config.postal_code("home").unwrap().to_string(), "7800");
+    /// assert_eq!(config.postal_code("2450").unwrap().to_string(), "2450");
+    /// assert!(config.postal_code("cabin").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `code` is neither an alias nor a postal code.
+    pub fn postal_code(&self, code: &str) -> Result<NorwegianPostalCode, String> {
+        match self.aliases.get(code) {
+            Some(postal_code) => Ok(*postal_code),
+            None => {
+                NorwegianPostalCode::try_from(code).map_err(|err| format!("--code {code}: {err}"))
+            }
+        }
+    }
+
+    #[must_use]
+    /// The aliases by postal code, the first in alphabetical order if a
+    /// postal code has several.
+    pub fn names(&self) -> BTreeMap<NorwegianPostalCode, String> {
+        let mut names = BTreeMap::new();
+        for (alias, postal_code) in &self.aliases {
+            names.entry(*postal_code).or_insert_with(|| alias.clone());
+        }
+        names
+    }
+}
+
+/// Commented configuration written by `postgang config init`.
+pub const TEMPLATE: &str = r#"# Configuration of postgang, see `postgang help`.
+# Settings given on the command line or in the environment take precedence.
+
+# Postal codes or aliases to fetch when --code is not given.
+code = []
+
+# Credentials for Bring API, from https://www.mybring.com/useradmin/account/settings/api
+# api-uid = "ola@example.com"
+# api-key = "…"
+
+# Where cached responses, the history and the calendar are kept.
+# cache-dir = "/var/cache/postgang"
+# history = "/var/lib/postgang/history.json"
+# output = "/var/www/postgang.ics"
+
+# Names that can be used instead of postal codes.
+[aliases]
+# home = "7800"
+# cabin = "2450"
+
+# Settings of the events of a postal code or alias, in a calendar of its own
+# with output.
+# [calendars.cabin]
+# output = "/var/www/hytta.ics"
+# name = "Posten på hytta"
+# locale = "en"
+# alarm = "1d@20:00"
+# summary = "Post på hytta {weekday}"
+
+# Notify about changed delivery dates with --notify.
+# [telegram]
+# token = "123456:…"
+# chat-id = "-1001234567890"
+#
+# [matrix]
+# homeserver = "https://matrix.example.com"
+# token = "…"
+# room-id = "!abcdefg:example.com"
+#
+# Needs the smtp feature.
+# [smtp]
+# host = "smtp.example.com"
+# tls = "starttls"
+# username = "ola@example.com"
+# password = "…"
+# from = "Postgang <ola@example.com>"
+# to = ["ola@example.com"]
+# attach-calendar = true
+
+# Notification messages, with the placeholders {code}, {name}, {place},
+# {added}, {removed} and {next} for changes, and those of summaries and
+# {place} for reminders. Dates are written in the language of locale.
+# [messages]
+# changes = "Posten til {name} kommer {added}"
+# reminder = "Post til {name} {weekday}"
+# locale = "nb"
+"#;
+
+/// Keys whose values are masked by [`mask_secrets`].
+const SECRETS: [&str; 3] = ["api-key", "token", "password"];
+
+fn document(content: &str) -> Result<DocumentMut, String> {
+    content.parse().map_err(|err| format!("{err}"))
+}
+
+/// The value of the dotted `key`, like `telegram.chat-id`, in `content`.
+///
+/// Strings are given without quotes, other values as TOML.
+///
+/// ```
+/// use postgang::config;
+///
+/// let content = "code = [\"7800\"]\n[telegram]\nchat-id = \"42\"\n";
+/// assert_eq!(config::get(content, "telegram.chat-id").unwrap().as_deref(), Some("42"));
+/// assert_eq!(config::get(content, "code").unwrap().as_deref(), Some("[\"7800\"]"));
+/// assert_eq!(config::get(content, "output").unwrap(), None);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `content` is not TOML.
+pub fn get(content: &str, key: &str) -> Result<Option<String>, String> {
+    let document = document(content)?;
+    let mut item = document.as_item();
+    for part in key.split('.') {
+        match item.get(part) {
+            Some(next) => item = next,
+            None => return Ok(None),
+        }
+    }
+    Ok(match item {
+        Item::Value(Value::String(value)) => Some(value.value().clone()),
+        Item::Value(value) => Some(value.clone().decorated("", "").to_string()),
+        Item::Table(table) => Some(table.to_string().trim_end().to_string()),
+        Item::None | Item::ArrayOfTables(_) => None,
+    })
+}
+
+/// `content` with the dotted `key` set to `value`, keeping comments and
+/// the order of the other settings.
+///
+/// Arrays, inline tables and booleans are parsed as TOML, anything else is
+/// set as a string, so postal codes need no quotes. The result is not
+/// checked against [`Config`], as a table may need several settings before
+/// it is complete.
+///
+/// ```
+/// use postgang::config;
+///
+/// let content = config::set("# Mine\ncode = []\n", "code", r#"["7800"]"#).unwrap();
+/// let content = config::set(&content, "aliases.home", "7800").unwrap();
+/// assert_eq!(content, "# Mine\ncode = [\"7800\"]\n\n[aliases]\nhome = \"7800\"\n");
+/// assert!(config::set(&content, "aliases..home", "7800").is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `content` is not TOML, or if `key` is invalid or
+/// goes through a setting that is not a table.
+pub fn set(content: &str, key: &str, value: &str) -> Result<String, String> {
+    let mut document = document(content)?;
+    let value = match value.parse::<Value>() {
+        Ok(parsed @ (Value::Array(_) | Value::InlineTable(_) | Value::Boolean(_))) => parsed,
+        _ => Value::from(value),
+    };
+    if key.split('.').any(str::is_empty) {
+        return Err(format!("Invalid key {key:?}"));
+    }
+    let (parents, last) = key.rsplit_once('.').unwrap_or(("", key));
+    let mut table: &mut dyn TableLike = document.as_table_mut();
+    for part in parents.split('.').filter(|part| !part.is_empty()) {
+        table = table
+            .entry(part)
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_like_mut()
+            .ok_or_else(|| format!("{part} is not a table"))?;
+    }
+    replace_value(table.entry(last).or_insert(Item::None), value);
+    Ok(document.to_string())
+}
+
+/// Set `item` to `value`, keeping the comments around the old value.
+fn replace_value(item: &mut Item, mut value: Value) {
+    if let Some(old) = item.as_value() {
+        *value.decor_mut() = old.decor().clone();
+    }
+    *item = Item::Value(value);
+}
+
+fn mask_table(table: &mut dyn TableLike) {
+    for (key, item) in table.iter_mut() {
+        if SECRETS.contains(&key.get()) && item.is_str() {
+            replace_value(item, Value::from("********"));
+        } else if let Some(table) = item.as_table_like_mut() {
+            mask_table(table);
+        }
+    }
+}
+
+/// `content` with the API key, tokens and passwords masked, for showing it.
+///
+/// ```
+/// use postgang::config;
+///
+/// let content = "api-key = \"secret\"\n[matrix]\ntoken = \"secret\" # Bot\n";
+/// assert_eq!(
+///     config::mask_secrets(content).unwrap(),
+///     "api-key = \"********\"\n[matrix]\ntoken = \"********\" # Bot\n"
+/// );
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `content` is not TOML.
+pub fn mask_secrets(content: &str) -> Result<String, String> {
+    let mut document = document(content)?;
+    mask_table(document.as_table_mut());
+    Ok(document.to_string())
+}
+
+#[must_use]
+/// The configuration file of the user, `$XDG_CONFIG_HOME/postgang/config.toml`.
+pub fn user_config(env: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    base_dir(&env, "XDG_CONFIG_HOME", ".config").map(|dir| dir.join("config.toml"))
+}
+
+#[must_use]
+/// Find the configuration file to use when none is given.
+///
+/// This is the configuration file of the user if it exists, or else
+/// [`SYSTEM_CONFIG`] if it exists.
+pub fn discover() -> Option<PathBuf> {
+    user_config(|var| std::env::var_os(var))
+        .into_iter()
+        .chain([PathBuf::from(SYSTEM_CONFIG)])
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{ffi::OsString, path::PathBuf};
+
+    use super::{user_config, Config, TEMPLATE};
+
+    #[test]
+    fn test_user_config() {
+        let env = |var: &str| match var {
+            "HOME" => Some(OsString::from("/home/ola")),
+            _ => None,
+        };
+        assert_eq!(
+            user_config(env),
+            Some(PathBuf::from("/home/ola/.config/postgang/config.toml"))
+        );
+    }
+
+    #[test]
+    fn test_debug_redacted() {
+        let config = Config::parse(
+            "api-uid = \"ola@example.com\"\n\
+             api-key = \"SUPERSECRETKEY\"\n\
+             [matrix]\n\
+             homeserver = \"https://matrix.example.com\"\n\
+             token = \"SECRETTOKEN\"\n\
+             room-id = \"!abc:example.com\"\n",
+        )
+        .unwrap();
+        let debug = format!("{config:?}");
+        for secret in ["ola@example.com", "SUPERSECRETKEY", "SECRETTOKEN"] {
+            assert!(!debug.contains(secret), "{debug}");
+        }
+    }
+
+    #[test]
+    fn test_template() {
+        assert_eq!(Config::parse(TEMPLATE).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            "code = [\"7800\", \"2450\"]\n\
+             cache-dir = \"/var/cache/postgang\"\n\
+             output = \"postgang.ics\"\n\
+             [telegram]\n\
+             token = \"123:abc\"\n\
+             chat-id = \"42\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.code.len(), 2);
+        assert_eq!(config.cache_dir, Some(PathBuf::from("/var/cache/postgang")));
+        assert_eq!(config.output, Some(PathBuf::from("postgang.ics")));
+        assert_eq!(config.telegram.unwrap().chat_id, "42");
+        assert!(Config::parse("code = [\"78\"]").is_err());
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        let config = Config::parse("[aliases]\nhome = \"7800\"\nbolig = \"7800\"").unwrap();
+        assert_eq!(config.names().into_values().collect::<Vec<_>>(), ["bolig"]);
+        let config =
+            Config::parse("[calendars.7800]\noutput = \"7800.ics\"\nlocale = \"en\"").unwrap();
+        assert_eq!(config.calendars["7800"].locale.as_deref(), Some("en"));
+        let config = Config::parse("[calendars.7800]\nlocale = \"en\"").unwrap();
+        assert_eq!(config.calendars["7800"].output, None);
+        assert_eq!(config.calendars["7800"].locale.as_deref(), Some("en"));
+        let config = Config::parse("[messages]\nreminder = \"Post {weekday}\"").unwrap();
+        assert_eq!(
+            config.messages.unwrap().reminder.as_deref(),
+            Some("Post {weekday}")
+        );
+    }
+}
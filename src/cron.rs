@@ -0,0 +1,112 @@
+//! Crontab entry running postgang, for `postgang generate cron`.
+use core::time::Duration;
+use std::path::Path;
+
+/// Quote `arg` for the shell cron runs commands with, escaping `%`, which
+/// cron turns into newlines.
+///
+/// ```
+/// use postgang::cron::quote;
+///
+/// assert_eq!(quote("--code=7800"), "--code=7800");
+/// assert_eq!(quote("Posten på hytta"), "'Posten på hytta'");
+/// assert_eq!(quote("it's"), r"'it'\''s'");
+/// assert_eq!(quote("100%"), r"'100\%'");
+/// ```
+#[must_use]
+pub fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@+".contains(c));
+    if plain {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''").replace('%', r"\%"))
+}
+
+/// The schedule of a job run every `interval`, at `minute` past the hour
+/// and, for daily jobs, at `hour`.
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use postgang::cron::schedule;
+///
+/// assert_eq!(schedule(Duration::from_mins(15), 7, 3).unwrap(), "7-59/15 * * * *");
+/// assert_eq!(schedule(Duration::from_hours(6), 7, 3).unwrap(), "7 */6 * * *");
+/// assert_eq!(schedule(Duration::from_hours(24), 7, 3).unwrap(), "7 3 * * *");
+/// assert!(schedule(Duration::from_mins(45), 7, 3).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if cron can't run a job that often, which is when
+/// `interval` divides neither an hour nor a day into whole minutes or hours.
+pub fn schedule(interval: Duration, minute: u32, hour: u32) -> Result<String, String> {
+    let minutes = interval.as_secs() / 60;
+    let invalid = || {
+        format!(
+            "Cron can't run a job every {}, use a part of an hour or a day",
+            humantime::format_duration(interval)
+        )
+    };
+    if !interval.as_secs().is_multiple_of(60) || minutes == 0 {
+        return Err(invalid());
+    }
+    if minutes < 60 {
+        if !60_u64.is_multiple_of(minutes) {
+            return Err(invalid());
+        }
+        let first = u64::from(minute) % minutes;
+        return Ok(format!("{first}-59/{minutes} * * * *"));
+    }
+    let hours = minutes / 60;
+    if !minutes.is_multiple_of(60) || !24_u64.is_multiple_of(hours) {
+        return Err(invalid());
+    }
+    Ok(match hours {
+        1 => format!("{minute} * * * *"),
+        24 => format!("{minute} {hour} * * *"),
+        hours => format!("{minute} */{hours} * * *"),
+    })
+}
+
+#[must_use]
+/// A crontab line running `exec` on `schedule` from `working_directory`
+/// with the variables `env`.
+///
+/// ```
+/// use std::path::Path;
+///
+/// use postgang::cron::entry;
+///
+/// let exec = ["/usr/bin/postgang", "--code", "7800", "api"].map(String::from);
+/// let env = [("POSTGANG_CONFIG".to_string(), "/etc/postgang.toml".to_string())];
+/// assert_eq!(
+///     entry("7 */6 * * *", Path::new("/srv"), &env, &exec),
+///     "7 */6 * * * cd /srv && POSTGANG_CONFIG=/etc/postgang.toml /usr/bin/postgang --code 7800 api"
+/// );
+/// ```
+pub fn entry(
+    schedule: &str,
+    working_directory: &Path,
+    env: &[(String, String)],
+    exec: &[String],
+) -> String {
+    let mut line = format!(
+        "{schedule} cd {} &&",
+        quote(&working_directory.to_string_lossy())
+    );
+    for (name, value) in env {
+        line.push(' ');
+        line.push_str(name);
+        line.push('=');
+        line.push_str(&quote(value));
+    }
+    for arg in exec {
+        line.push(' ');
+        line.push_str(&quote(arg));
+    }
+    line
+}
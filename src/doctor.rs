@@ -0,0 +1,139 @@
+//! Checks of the setup, for `postgang doctor`.
+//!
+//! Each check either passes with a short description of what it found, or
+//! fails with the reason, so a broken timer can be diagnosed in one run.
+use core::{fmt, time::Duration};
+use std::{fs::OpenOptions, path::Path};
+
+use crate::{
+    http_client::Client,
+    io_error_to_string,
+    locale::{Locale, Message},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The outcome of one check.
+///
+/// ```
+/// use postgang::doctor::Check;
+///
+/// let check = Check::new("config", Ok("/etc/postgang/config.toml".to_string()));
+/// assert_eq!(check.to_string(), "ok    config: /etc/postgang/config.toml");
+/// let check = Check::new("credentials", Err("--api-key is required".to_string()));
+/// assert_eq!(check.to_string(), "FAIL  credentials: --api-key is required");
+/// ```
+pub struct Check {
+    pub name: String,
+    /// What was found, or why the check failed.
+    pub result: Result<String, String>,
+}
+
+impl Check {
+    #[must_use]
+    pub fn new(name: impl Into<String>, result: Result<String, String>) -> Self {
+        Self {
+            name: name.into(),
+            result,
+        }
+    }
+
+    #[must_use]
+    pub const fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.result {
+            Ok(found) => write!(f, "ok    {}: {found}", self.name),
+            Err(reason) => write!(f, "FAIL  {}: {reason}", self.name),
+        }
+    }
+}
+
+/// Check that files can be created in `dir`, creating it if missing, and
+/// describe it in `locale`.
+///
+/// # Errors
+///
+/// Returns an error if the directory or a file in it can't be created.
+pub fn writable_dir(dir: &Path, locale: Locale) -> Result<String, String> {
+    std::fs::create_dir_all(dir).map_err(|err| io_error_to_string(&err, dir))?;
+    let probe = dir.join(format!(".postgang-doctor-{}", std::process::id()));
+    std::fs::File::create(&probe).map_err(|err| io_error_to_string(&err, dir))?;
+    std::fs::remove_file(&probe).map_err(|err| io_error_to_string(&err, &probe))?;
+    Ok(locale.message(Message::Writable(dir)))
+}
+
+/// Check that `path` can be written, without changing it.
+///
+/// An existing file is opened for appending. Otherwise a file must be
+/// possible to create in its directory, which is not created.
+///
+/// # Errors
+///
+/// Returns an error if the file or its directory can't be written.
+pub fn writable_file(path: &Path, locale: Locale) -> Result<String, String> {
+    if path.exists() {
+        OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|err| io_error_to_string(&err, path))?;
+        return Ok(locale.message(Message::Writable(path)));
+    }
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    if !dir.is_dir() {
+        return Err(locale.message(Message::NoSuchDirectory(dir)));
+    }
+    writable_dir(dir, locale).map(|_| locale.message(Message::CanBeCreated(path)))
+}
+
+/// Check that `url` answers within `timeout`, whatever the status.
+///
+/// # Errors
+///
+/// Returns an error if the request fails.
+pub async fn reachable(url: &str, timeout: Duration, locale: Locale) -> Result<String, String> {
+    let client = Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|err| err.to_string())?;
+    let resp = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    let status = resp.status().to_string();
+    Ok(locale.message(Message::Answered {
+        url,
+        status: &status,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{writable_dir, writable_file};
+    use crate::locale::Locale;
+
+    #[test]
+    fn test_writable() {
+        let dir = std::env::temp_dir().join(format!("postgang-doctor-{}", std::process::id()));
+        let locale = Locale::English;
+        assert!(writable_dir(&dir, locale).is_ok());
+        assert!(dir.is_dir());
+        let file = dir.join("postgang.ics");
+        assert_eq!(
+            writable_file(&file, locale),
+            Ok(format!("{} can be created", file.display()))
+        );
+        assert!(!file.exists());
+        std::fs::write(&file, "").unwrap();
+        assert!(writable_file(&file, locale).is_ok());
+        assert!(writable_file(&dir.join("missing").join("postgang.ics"), locale).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
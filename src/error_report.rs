@@ -0,0 +1,157 @@
+//! Machine-readable description of an error, for `--error-format json`.
+use core::error::Error;
+
+use serde::Serialize;
+
+use crate::{
+    bring_client::{
+        cache::CachedError,
+        fixtures::ReplayedError,
+        mailbox_delivery_dates::{ApiError, FetchError, UnknownPostalCode},
+        retry::is_retryable_error,
+        NorwegianPostalCode,
+    },
+    http_client,
+};
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+/// What went wrong, found by looking through the chain of error sources.
+pub struct ErrorReport {
+    /// One of `http`, `timeout`, `connect`, `request`, `json`, `io` or `other`.
+    pub kind: &'static str,
+    pub message: String,
+    pub http_status: Option<u16>,
+    pub postal_code: Option<NorwegianPostalCode>,
+    /// Whether running again later might succeed.
+    pub retryable: bool,
+    /// Trace id from the body of an API error response.
+    pub trace_id: Option<String>,
+}
+
+impl ErrorReport {
+    #[must_use]
+    /// Describe `err`.
+    ///
+    /// ```
+    /// use postgang::error_report::ErrorReport;
+    ///
+    /// let err: Box<dyn core::error::Error> = "--code is required".into();
+    /// let report = ErrorReport::new(err.as_ref());
+    /// assert_eq!(report.kind, "other");
+    /// assert_eq!(report.message, "--code is required");
+    /// assert!(!report.retryable);
+    /// ```
+    pub fn new(err: &(dyn Error + 'static)) -> Self {
+        let mut report = Self {
+            kind: "other",
+            message: err.to_string(),
+            http_status: None,
+            postal_code: None,
+            retryable: false,
+            trace_id: None,
+        };
+        let mut source = Some(err);
+        while let Some(err) = source {
+            if let Some(err) = err.downcast_ref::<FetchError>() {
+                report.postal_code = Some(err.postal_code);
+            } else if let Some(err) = err.downcast_ref::<ApiError>() {
+                report.trace_id = err
+                    .details
+                    .as_ref()
+                    .and_then(|details| details.trace_id.clone());
+            } else if let Some(err) = err.downcast_ref::<UnknownPostalCode>() {
+                report.kind = "http";
+                report.http_status = Some(404);
+                report.postal_code = Some(err.0);
+                break;
+            } else if let Some(err) = err.downcast_ref::<CachedError>() {
+                report.kind = "http";
+                report.http_status = Some(err.status);
+                break;
+            } else if let Some(err) = err.downcast_ref::<ReplayedError>() {
+                report.kind = "http";
+                report.http_status = Some(err.status);
+                report.trace_id = err
+                    .details
+                    .as_ref()
+                    .and_then(|details| details.trace_id.clone());
+                break;
+            } else if let Some(err) = err.downcast_ref::<http_client::Error>() {
+                report.http_status = err.status().map(|status| status.as_u16());
+                report.retryable = is_retryable_error(err);
+                report.kind = if err.status().is_some() {
+                    "http"
+                } else if err.is_timeout() {
+                    "timeout"
+                } else if err.is_connect() {
+                    "connect"
+                } else {
+                    "request"
+                };
+                break;
+            } else if err.is::<serde_json::Error>() {
+                report.kind = "json";
+                break;
+            } else if err.is::<std::io::Error>() {
+                report.kind = "io";
+                break;
+            }
+            source = err.source();
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ErrorReport;
+    use crate::bring_client::{
+        cache::CachedError,
+        mailbox_delivery_dates::{FetchError, UnknownPostalCode},
+        NorwegianPostalCode,
+    };
+
+    #[test]
+    fn test_fetch_error() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let err = FetchError {
+            postal_code,
+            source: serde_json::from_str::<u32>("x").unwrap_err().into(),
+        };
+        let report = ErrorReport::new(&err);
+        assert_eq!(report.kind, "json");
+        assert_eq!(report.postal_code, Some(postal_code));
+        assert!(report.message.starts_with("7800: "));
+        assert_eq!(report.http_status, None);
+    }
+
+    #[test]
+    fn test_unknown_postal_code() {
+        let postal_code = NorwegianPostalCode::try_from("0001").unwrap();
+        let report = ErrorReport::new(&UnknownPostalCode(postal_code));
+        assert_eq!(report.kind, "http");
+        assert_eq!(report.http_status, Some(404));
+        assert_eq!(report.postal_code, Some(postal_code));
+        assert!(report.message.contains("Unknown postal code 0001"));
+    }
+
+    #[test]
+    fn test_cached_error() {
+        let err = FetchError {
+            postal_code: NorwegianPostalCode::try_from("0001").unwrap(),
+            source: CachedError {
+                status: 404,
+                received: "2024-09-02T12:00:00Z".parse().unwrap(),
+            }
+            .into(),
+        };
+        let report = ErrorReport::new(&err);
+        assert_eq!(report.kind, "http");
+        assert_eq!(report.http_status, Some(404));
+        assert_eq!(
+            report.message,
+            "0001: HTTP status 404 Not Found cached from 2024-09-02 12:00:00 UTC"
+        );
+        assert!(!report.retryable);
+    }
+}
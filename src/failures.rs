@@ -0,0 +1,112 @@
+//! Runs of the Windows service that failed in a row.
+//!
+//! The service keeps them in a JSON file next to the history, so
+//! `postgang healthcheck` can report it as degraded and a restarted service
+//! keeps backing off.
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::io_error_to_string;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+/// Failed runs since the last successful one.
+pub struct Failures {
+    /// Number of runs in a row that failed.
+    pub count: u32,
+    /// When the last of them failed.
+    pub last_failure: DateTime<Utc>,
+}
+
+#[must_use]
+/// The file of the failures next to the `history`.
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+///
+/// use postgang::failures;
+///
+/// assert_eq!(
+///     failures::path(Path::new("/var/lib/postgang/history.json")),
+///     PathBuf::from("/var/lib/postgang/history.failures.json")
+/// );
+/// ```
+pub fn path(history: &Path) -> PathBuf {
+    history.with_extension("failures.json")
+}
+
+impl Failures {
+    /// Read the failures from `path`.
+    ///
+    /// A missing file means the last run succeeded, or none has failed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        match std::fs::File::open(path) {
+            Ok(file) => serde_json::from_reader(file)
+                .map(Some)
+                .map_err(|err| format!("{err}: {}", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(io_error_to_string(&err, path)),
+        }
+    }
+
+    /// Write the failures to `path`, replacing the file atomically like the
+    /// history.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn save(self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|err| io_error_to_string(&err, dir))?;
+        }
+        let mut tmp = path.to_path_buf().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let file = std::fs::File::create(&tmp).map_err(|err| io_error_to_string(&err, &tmp))?;
+        serde_json::to_writer(file, &self).map_err(|err| format!("{err}: {}", tmp.display()))?;
+        std::fs::rename(&tmp, path).map_err(|err| io_error_to_string(&err, path))
+    }
+
+    /// Forget the failures at `path` after a successful run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be removed.
+    pub fn clear(path: &Path) -> Result<(), String> {
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(io_error_to_string(&err, path))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, Utc};
+
+    use super::Failures;
+
+    #[test]
+    fn test_save() {
+        let path =
+            std::env::temp_dir().join(format!("postgang-failures-{}.json", std::process::id()));
+        assert_eq!(Failures::load(&path), Ok(None));
+        let failures = Failures {
+            count: 3,
+            last_failure: "2024-09-01T04:30:00Z".parse::<DateTime<Utc>>().unwrap(),
+        };
+        failures.save(&path).unwrap();
+        assert_eq!(Failures::load(&path), Ok(Some(failures)));
+        Failures::clear(&path).unwrap();
+        assert_eq!(Failures::load(&path), Ok(None));
+        Failures::clear(&path).unwrap();
+    }
+}
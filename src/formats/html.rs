@@ -0,0 +1,125 @@
+//! Static HTML page of upcoming delivery dates, to publish next to the
+//! calendar.
+use core::{fmt::Write, time::Duration};
+
+use chrono::NaiveDate;
+
+use crate::{
+    bring_client::mailbox_delivery_dates::DeliveryDate,
+    calendar::{relative_summary, summary},
+};
+
+/// Escape `text` for HTML content and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format the delivery dates on or after `today` as an HTML page.
+///
+/// With `relative`, deliveries today and tomorrow say so. With `refresh`,
+/// browsers reload the page that often.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::formats::html;
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let day = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+/// let dates = [
+///     DeliveryDate::new(postal_code, day(2)),
+///     DeliveryDate::new(postal_code, day(4)),
+/// ];
+/// let page = html::to_string(&dates, day(3), true, None);
+/// assert!(page.contains(
+///     "<li><time datetime=\"2024-09-04\">7800: Posten kommer i morgen.</time></li>"
+/// ));
+/// assert!(!page.contains("2024-09-02"));
+/// ```
+#[must_use]
+pub fn to_string(
+    delivery_dates: &[DeliveryDate],
+    today: NaiveDate,
+    relative: bool,
+    refresh: Option<Duration>,
+) -> String {
+    let mut upcoming: Vec<_> = delivery_dates
+        .iter()
+        .filter(|delivery_date| delivery_date.date >= today)
+        .collect();
+    upcoming.sort_by_key(|delivery_date| (delivery_date.date, delivery_date.postal_code));
+    let mut page = String::from(
+        "<!DOCTYPE html>\n\
+         <html lang=\"nb\">\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n",
+    );
+    // Writing to a String can't fail
+    if let Some(refresh) = refresh {
+        let _ = writeln!(
+            page,
+            "<meta http-equiv=\"refresh\" content=\"{}\">",
+            refresh.as_secs().max(1)
+        );
+    }
+    page.push_str("<title>Postgang</title>\n</head>\n<body>\n<h1>Postgang</h1>\n");
+    if upcoming.is_empty() {
+        page.push_str("<p>Ingen kommende leveringsdager.</p>\n");
+    } else {
+        page.push_str("<ul>\n");
+        for delivery_date in upcoming {
+            let text = if relative {
+                relative_summary(*delivery_date, today)
+            } else {
+                summary(*delivery_date)
+            };
+            let _ = writeln!(
+                page,
+                "<li><time datetime=\"{}\">{}</time></li>",
+                delivery_date.date,
+                escape(&text)
+            );
+        }
+        page.push_str("</ul>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+#[cfg(test)]
+mod test {
+    use core::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::{escape, to_string};
+
+    #[test]
+    fn test_empty_with_refresh() {
+        let today = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+        let page = to_string(&[], today, false, Some(Duration::from_secs(90)));
+        assert!(page.contains("<meta http-equiv=\"refresh\" content=\"90\">"));
+        assert!(page.contains("<p>Ingen kommende leveringsdager.</p>"));
+        assert!(!to_string(&[], today, false, None).contains("refresh"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+}
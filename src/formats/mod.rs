@@ -0,0 +1,7 @@
+//! Output formats other than iCalendar.
+pub mod html;
+pub mod remind;
+pub mod table;
+pub mod taskwarrior;
+pub mod waybar;
+pub mod yaml;
@@ -0,0 +1,38 @@
+//! Reminder file format for [remind(1)](https://dianne.skoll.ca/projects/remind/).
+use core::fmt::Write;
+
+use crate::{bring_client::mailbox_delivery_dates::DeliveryDate, calendar::summary};
+
+/// Format `delivery_dates` as `REM` lines.
+///
+/// `%` and `[` have special meaning in the message body and are escaped.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::formats::remind;
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let date = NaiveDate::from_ymd_opt(1970, 8, 13).unwrap();
+/// assert_eq!(
+///     remind::to_string(&[DeliveryDate::new(postal_code, date)]),
+///     "REM 13 Aug 1970 MSG 7800: Posten kommer torsdag 13. august.\n"
+/// );
+/// ```
+#[must_use]
+pub fn to_string(delivery_dates: &[DeliveryDate]) -> String {
+    let mut reminders = String::new();
+    for delivery_date in delivery_dates {
+        let message = summary(*delivery_date)
+            .replace('%', "%%")
+            .replace('[', "[\"[\"]");
+        // Writing to a String can't fail
+        let _ = writeln!(
+            reminders,
+            "REM {} MSG {message}",
+            delivery_date.date.format("%-d %b %Y")
+        );
+    }
+    reminders
+}
@@ -0,0 +1,108 @@
+//! Aligned table of upcoming delivery dates for terminals.
+use core::fmt::Write;
+
+use chrono::NaiveDate;
+
+use crate::{bring_client::mailbox_delivery_dates::DeliveryDate, calendar::weekday};
+
+const BOLD: &str = "\x1b[1m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Format the delivery dates on or after `today` as a table.
+///
+/// With `color`, the header is bold, today is green and tomorrow is yellow.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::formats::table;
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let day = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+/// let dates = [
+///     DeliveryDate::new(postal_code, day(2)),
+///     DeliveryDate::new(postal_code, day(4)),
+///     DeliveryDate::new(postal_code, day(14)),
+/// ];
+/// assert_eq!(
+///     table::to_string(&dates, day(3), false),
+///     "Postnummer  Ukedag  Dato        Dager\n\
+///      7800        onsdag  2024-09-04      1\n\
+///      7800        lørdag  2024-09-14     11\n"
+/// );
+/// ```
+#[must_use]
+pub fn to_string(delivery_dates: &[DeliveryDate], today: NaiveDate, color: bool) -> String {
+    let mut upcoming: Vec<_> = delivery_dates
+        .iter()
+        .filter(|delivery_date| delivery_date.date >= today)
+        .collect();
+    upcoming.sort_by_key(|delivery_date| (delivery_date.date, delivery_date.postal_code));
+    let weekday_width = upcoming
+        .iter()
+        .map(|delivery_date| weekday(delivery_date.date).chars().count())
+        .max()
+        .unwrap_or_default()
+        .max("Ukedag".len());
+    let paint = |style: &str, line: String| {
+        if color && !style.is_empty() {
+            format!("{style}{line}{RESET}\n")
+        } else {
+            format!("{line}\n")
+        }
+    };
+    let mut table = paint(
+        BOLD,
+        format!("Postnummer  {:weekday_width$}  Dato        Dager", "Ukedag"),
+    );
+    for delivery_date in upcoming {
+        let days = (delivery_date.date - today).num_days();
+        let style = match days {
+            0 => GREEN,
+            1 => YELLOW,
+            _ => "",
+        };
+        // Writing to a String can't fail
+        let _ = write!(
+            table,
+            "{}",
+            paint(
+                style,
+                format!(
+                    "{:10}  {:weekday_width$}  {}  {days:5}",
+                    delivery_date.postal_code.to_string(),
+                    weekday(delivery_date.date),
+                    delivery_date.date,
+                )
+            )
+        );
+    }
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::to_string;
+    use crate::bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode};
+
+    #[test]
+    fn test_color() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let day = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+        let dates = [
+            DeliveryDate::new(postal_code, day(2)),
+            DeliveryDate::new(postal_code, day(3)),
+        ];
+        assert_eq!(
+            to_string(&dates, day(2), true),
+            "\x1b[1mPostnummer  Ukedag   Dato        Dager\x1b[0m\n\
+             \x1b[32m7800        mandag   2024-09-02      0\x1b[0m\n\
+             \x1b[33m7800        tirsdag  2024-09-03      1\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Tasks for [Taskwarrior](https://taskwarrior.org/) `task import`.
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::Serialize;
+
+use crate::{
+    bring_client::mailbox_delivery_dates::DeliveryDate,
+    calendar::{summary, uid},
+    fnv1a,
+};
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+/// A pending task in Taskwarrior's JSON import format.
+pub struct Task {
+    pub uuid: String,
+    pub description: String,
+    pub status: &'static str,
+    pub due: String,
+    pub tags: Vec<&'static str>,
+}
+
+/// Deterministic UUID for a delivery date, so repeated imports update the
+/// existing task instead of creating duplicates.
+fn task_uuid(delivery_date: DeliveryDate) -> String {
+    let uid = uid(delivery_date);
+    let high = fnv1a(uid.bytes());
+    let low = fnv1a(b"taskwarrior:".iter().copied().chain(uid.bytes()));
+    // Version 8 (custom) and RFC 9562 variant
+    let high = (high & !0xf000) | 0x8000;
+    let low = (low & !(0b11 << 62)) | (0b10 << 62);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+/// Start of `date` in local time, formatted as a Taskwarrior UTC timestamp.
+fn due(date: NaiveDate) -> String {
+    let midnight = date.and_time(NaiveTime::MIN);
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map_or_else(|| midnight.and_utc(), |local| local.with_timezone(&Utc))
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+#[must_use]
+/// One task per delivery date, due at the start of the day.
+pub fn tasks(delivery_dates: &[DeliveryDate]) -> Vec<Task> {
+    delivery_dates
+        .iter()
+        .map(|delivery_date| Task {
+            uuid: task_uuid(*delivery_date),
+            description: summary(*delivery_date),
+            status: "pending",
+            due: due(delivery_date.date),
+            tags: vec!["postgang"],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::{task_uuid, tasks};
+    use crate::bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode};
+
+    #[test]
+    fn test_task_uuid() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date =
+            |day| DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(2024, 9, day).unwrap());
+        let uuid = task_uuid(date(2));
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "8");
+        assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"));
+        assert_eq!(uuid, task_uuid(date(2)));
+        assert_ne!(uuid, task_uuid(date(4)));
+    }
+
+    #[test]
+    fn test_tasks() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(2024, 9, 2).unwrap());
+        let tasks = tasks(&[date]);
+        assert_eq!(
+            tasks[0].description,
+            "7800: Posten kommer mandag 2. september."
+        );
+        assert_eq!(tasks[0].status, "pending");
+        assert_eq!(tasks[0].due.len(), "20240902T000000Z".len());
+    }
+}
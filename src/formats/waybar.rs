@@ -0,0 +1,121 @@
+//! Custom module output for [Waybar](https://github.com/Alexays/Waybar) and
+//! i3status-rust.
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::{
+    bring_client::mailbox_delivery_dates::DeliveryDate,
+    calendar::{relative_summary, summary},
+};
+
+/// Number of upcoming deliveries listed in the tooltip.
+const TOOLTIP_LINES: usize = 5;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+/// The JSON object read by a Waybar custom module with `return-type` `json`.
+pub struct Module {
+    pub text: String,
+    pub tooltip: String,
+    pub class: &'static str,
+}
+
+/// Describe the number of days until a delivery.
+fn days_until(days: i64) -> String {
+    match days {
+        0 => "i dag".to_string(),
+        1 => "i morgen".to_string(),
+        n => format!("om {n} dager"),
+    }
+}
+
+#[must_use]
+/// Show the days until the next delivery on or after `today`.
+///
+/// The tooltip lists the upcoming deliveries, with `relative` as "i dag" and
+/// "i morgen" when they are that close. `class` is one of `today`,
+/// `tomorrow`, `later` or `none`, for styling.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::formats::waybar;
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let today = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+/// let date = NaiveDate::from_ymd_opt(2024, 9, 4).unwrap();
+/// let module = waybar::module(&[DeliveryDate::new(postal_code, date)], today, false);
+/// assert_eq!(module.text, "om 2 dager");
+/// assert_eq!(module.tooltip, "7800: Posten kommer onsdag 4. september.");
+/// assert_eq!(module.class, "later");
+/// ```
+pub fn module(delivery_dates: &[DeliveryDate], today: NaiveDate, relative: bool) -> Module {
+    let mut upcoming: Vec<_> = delivery_dates
+        .iter()
+        .filter(|delivery_date| delivery_date.date >= today)
+        .collect();
+    upcoming.sort_by_key(|delivery_date| (delivery_date.date, delivery_date.postal_code));
+    let Some(next) = upcoming.first() else {
+        return Module {
+            text: "ingen levering".to_string(),
+            tooltip: "Ingen kjente leveringsdager".to_string(),
+            class: "none",
+        };
+    };
+    let days = (next.date - today).num_days();
+    let tooltip: Vec<_> = upcoming
+        .iter()
+        .take(TOOLTIP_LINES)
+        .map(|delivery_date| {
+            if relative {
+                relative_summary(**delivery_date, today)
+            } else {
+                summary(**delivery_date)
+            }
+        })
+        .collect();
+    Module {
+        text: days_until(days),
+        tooltip: tooltip.join("\n"),
+        class: match days {
+            0 => "today",
+            1 => "tomorrow",
+            _ => "later",
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::module;
+    use crate::bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode};
+
+    #[test]
+    fn test_module() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let day = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+        let dates = [
+            DeliveryDate::new(postal_code, day(4)),
+            DeliveryDate::new(postal_code, day(2)),
+            DeliveryDate::new(postal_code, day(1)),
+        ];
+
+        let module_today = module(&dates, day(2), false);
+        assert_eq!(module_today.text, "i dag");
+        assert_eq!(module_today.class, "today");
+        assert_eq!(
+            module_today.tooltip,
+            "7800: Posten kommer mandag 2. september.\n7800: Posten kommer onsdag 4. september."
+        );
+
+        let module_tomorrow = module(&dates, day(3), true);
+        assert_eq!(module_tomorrow.text, "i morgen");
+        assert_eq!(module_tomorrow.class, "tomorrow");
+        assert_eq!(module_tomorrow.tooltip, "7800: Posten kommer i morgen.");
+
+        let module_none = module(&dates, day(5), false);
+        assert_eq!(module_none.class, "none");
+    }
+}
@@ -0,0 +1,121 @@
+//! YAML version of the JSON output, for templating configuration from the
+//! delivery schedule.
+use serde_json::Value;
+
+/// Whether `key` can be written without quotes and still reads back as the
+/// same string.
+fn is_plain(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !matches!(
+            key.to_ascii_lowercase().as_str(),
+            "true" | "false" | "null" | "yes" | "no" | "on" | "off" | "y" | "n"
+        )
+}
+
+/// `value` on one line, or `None` for a non-empty array or object.
+///
+/// Strings are always quoted, JSON escapes are valid in double-quoted YAML.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some("null".to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::String(value) => Some(Value::from(value.as_str()).to_string()),
+        Value::Array(items) if items.is_empty() => Some("[]".to_string()),
+        Value::Object(map) if map.is_empty() => Some("{}".to_string()),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+fn write_block(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                out.push_str(&pad);
+                if is_plain(key) {
+                    out.push_str(key);
+                } else {
+                    out.push_str(&Value::from(key.as_str()).to_string());
+                }
+                out.push(':');
+                if let Some(scalar) = scalar(value) {
+                    out.push(' ');
+                    out.push_str(&scalar);
+                    out.push('\n');
+                } else {
+                    out.push('\n');
+                    write_block(out, value, indent + 2);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                if let Some(scalar) = scalar(item) {
+                    out.push_str(&pad);
+                    out.push_str("- ");
+                    out.push_str(&scalar);
+                    out.push('\n');
+                } else {
+                    // The first line of the nested block goes after the dash.
+                    let mut nested = String::new();
+                    write_block(&mut nested, item, indent + 2);
+                    out.push_str(&pad);
+                    out.push_str("- ");
+                    out.push_str(&nested[indent + 2..]);
+                }
+            }
+        }
+        scalar_value => {
+            out.push_str(&pad);
+            out.push_str(&scalar(scalar_value).unwrap_or_default());
+            out.push('\n');
+        }
+    }
+}
+
+#[must_use]
+/// Format `value` as a block style YAML document.
+///
+/// ```
+/// use postgang::formats::yaml;
+///
+/// let value = serde_json::json!({
+///     "7800": {"delivery_dates": ["2024-09-02", "2024-09-04"]},
+///     "0001": {"delivery_dates": []},
+/// });
+/// assert_eq!(
+///     yaml::to_string(&value),
+///     "\"0001\":\n  delivery_dates: []\n\
+///      \"7800\":\n  delivery_dates:\n    - \"2024-09-02\"\n    - \"2024-09-04\"\n"
+/// );
+/// ```
+pub fn to_string(value: &Value) -> String {
+    let mut out = String::new();
+    write_block(&mut out, value, 0);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::to_string;
+
+    #[test]
+    fn test_nested() {
+        let value = serde_json::json!([
+            {"name": "a\"b", "yes": true, "n": 1.5},
+            [null],
+            "x"
+        ]);
+        assert_eq!(
+            to_string(&value),
+            "- \"n\": 1.5\n  name: \"a\\\"b\"\n  \"yes\": true\n\
+             - - null\n\
+             - \"x\"\n"
+        );
+    }
+}
@@ -0,0 +1,587 @@
+//! Local history of delivery dates.
+//!
+//! Bring only returns delivery dates for a short horizon. The history
+//! records every delivery date seen per postal code in a JSON file, so
+//! calendars and statistics can go beyond that horizon.
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::fmt::Write;
+use std::path::PathBuf;
+
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{bring_client::NorwegianPostalCode, io_error_to_string};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// When a delivery date was first and last returned by a data source.
+pub struct Sighting {
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// A recorded delivery date, as exported.
+pub struct Record {
+    pub postal_code: NorwegianPostalCode,
+    pub date: NaiveDate,
+    #[serde(flatten)]
+    pub sighting: Sighting,
+}
+
+/// Format `records` as CSV with a header line.
+///
+/// ```
+/// use chrono::{DateTime, NaiveDate};
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::history::{to_csv, Record, Sighting};
+///
+/// let seen = DateTime::parse_from_rfc3339("2024-09-01T12:00:00Z").unwrap().into();
+/// let record = Record {
+///     postal_code: NorwegianPostalCode::try_from("7800").unwrap(),
+///     date: NaiveDate::from_ymd_opt(2024, 9, 2).unwrap(),
+///     sighting: Sighting { first_seen: seen, last_seen: seen },
+/// };
+/// assert_eq!(
+///     to_csv(&[record]),
+///     "postal_code,date,first_seen,last_seen\r\n\
+///      7800,2024-09-02,2024-09-01T12:00:00Z,2024-09-01T12:00:00Z\r\n"
+/// );
+/// ```
+#[must_use]
+pub fn to_csv(records: &[Record]) -> String {
+    let mut csv = String::from("postal_code,date,first_seen,last_seen\r\n");
+    for record in records {
+        // Writing to a String can't fail
+        let _ = write!(
+            csv,
+            "{},{},{},{}\r\n",
+            record.postal_code,
+            record.date,
+            record
+                .sighting
+                .first_seen
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            record
+                .sighting
+                .last_seen
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        );
+    }
+    csv
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+/// Delivery dates added and removed since the previous run.
+pub struct Changes {
+    pub postal_code: NorwegianPostalCode,
+    pub added: Vec<NaiveDate>,
+    pub removed: Vec<NaiveDate>,
+}
+
+impl Changes {
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// Last published revision of a calendar event.
+struct EventRevision {
+    fingerprint: u64,
+    sequence: u32,
+}
+
+/// Fingerprint of imported events, which never matches a real one.
+const UNKNOWN_FINGERPRINT: u64 = 0;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+struct HistoryFile {
+    postal_codes: BTreeMap<NorwegianPostalCode, BTreeMap<NaiveDate, Sighting>>,
+    /// Delivery dates returned by the previous run.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    last_fetched: BTreeMap<NorwegianPostalCode, Vec<NaiveDate>>,
+    /// Future delivery dates a later response left out within its horizon,
+    /// until they are returned again.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    removed: BTreeMap<NorwegianPostalCode, BTreeSet<NaiveDate>>,
+    /// When the delivery dates were last fetched from the API.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    refreshed: BTreeMap<NorwegianPostalCode, DateTime<Utc>>,
+    /// Published calendar events keyed by UID.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    events: BTreeMap<String, EventRevision>,
+}
+
+#[derive(Debug, Clone)]
+/// History store backed by a JSON file.
+pub struct History {
+    path: PathBuf,
+    file: HistoryFile,
+}
+
+impl History {
+    /// Open the history stored in `path`.
+    ///
+    /// A missing file is treated as an empty history.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but can't be read or parsed.
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => {
+                serde_json::from_reader(file).map_err(|err| format!("{err}: {}", path.display()))?
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HistoryFile::default(),
+            Err(err) => return Err(io_error_to_string(&err, &path)),
+        };
+        Ok(Self { path, file })
+    }
+
+    /// Record that `dates` were returned for `postal_code` at `seen`.
+    ///
+    /// Returns the changes compared to the previously recorded run, or
+    /// `None` if this is the first run for `postal_code`. Dates before
+    /// `seen` that are no longer returned are not considered removed, and
+    /// dates seen before are not considered added, so a date missing from a
+    /// single response isn't announced again when it comes back.
+    ///
+    /// Recorded dates from `seen` up to the last of `dates` that are left
+    /// out are remembered as removed until they are returned again, see
+    /// [`History::merge_future`].
+    pub fn record(
+        &mut self,
+        postal_code: NorwegianPostalCode,
+        dates: &[NaiveDate],
+        seen: DateTime<Utc>,
+    ) -> Option<Changes> {
+        let mut current = dates.to_vec();
+        current.sort_unstable();
+        current.dedup();
+        let known = self.file.postal_codes.get(&postal_code);
+        let changes = self
+            .file
+            .last_fetched
+            .insert(postal_code, current.clone())
+            .map(|previous| {
+                let today = seen.date_naive();
+                Changes {
+                    postal_code,
+                    added: current
+                        .iter()
+                        .filter(|date| {
+                            !previous.contains(date)
+                                && !known.is_some_and(|known| known.contains_key(date))
+                        })
+                        .copied()
+                        .collect(),
+                    removed: previous
+                        .into_iter()
+                        .filter(|date| *date >= today && !current.contains(date))
+                        .collect(),
+                }
+            });
+        let today = seen.date_naive();
+        let mut removed = self.file.removed.remove(&postal_code).unwrap_or_default();
+        if let (Some(known), Some(horizon)) = (known, current.last()) {
+            removed.extend(known.keys().filter(|date| {
+                **date >= today && *date <= horizon && current.binary_search(date).is_err()
+            }));
+        }
+        removed.retain(|date| *date >= today && current.binary_search(date).is_err());
+        if !removed.is_empty() {
+            self.file.removed.insert(postal_code, removed);
+        }
+        let entries = self.file.postal_codes.entry(postal_code).or_default();
+        for date in dates {
+            entries
+                .entry(*date)
+                .and_modify(|sighting| sighting.last_seen = seen)
+                .or_insert(Sighting {
+                    first_seen: seen,
+                    last_seen: seen,
+                });
+        }
+        changes
+    }
+
+    /// Record that the delivery dates of `postal_code` were fetched from the
+    /// API at `at`.
+    pub fn set_refreshed(&mut self, postal_code: NorwegianPostalCode, at: DateTime<Utc>) {
+        self.file.refreshed.insert(postal_code, at);
+    }
+
+    #[must_use]
+    /// When the delivery dates of `postal_code` were last fetched from the
+    /// API.
+    ///
+    /// Histories written before this was recorded fall back to when a date
+    /// was last seen.
+    pub fn refreshed(&self, postal_code: NorwegianPostalCode) -> Option<DateTime<Utc>> {
+        self.file.refreshed.get(&postal_code).copied().or_else(|| {
+            self.dates(postal_code)
+                .map(|(_, sighting)| sighting.last_seen)
+                .max()
+        })
+    }
+
+    /// All recorded delivery dates for `postal_code` in ascending order.
+    pub fn dates(
+        &self,
+        postal_code: NorwegianPostalCode,
+    ) -> impl Iterator<Item = (NaiveDate, Sighting)> + '_ {
+        self.file
+            .postal_codes
+            .get(&postal_code)
+            .into_iter()
+            .flatten()
+            .map(|(date, sighting)| (*date, *sighting))
+    }
+
+    /// Recorded delivery dates for `postal_code` within an optional date range.
+    pub fn dates_between(
+        &self,
+        postal_code: NorwegianPostalCode,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> impl Iterator<Item = (NaiveDate, Sighting)> + '_ {
+        self.dates(postal_code)
+            .filter(move |(date, _)| from.is_none_or(|from| *date >= from))
+            .filter(move |(date, _)| to.is_none_or(|to| *date <= to))
+    }
+
+    #[must_use]
+    /// All records for `postal_codes` within an optional date range.
+    pub fn records(
+        &self,
+        postal_codes: &[NorwegianPostalCode],
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Vec<Record> {
+        postal_codes
+            .iter()
+            .flat_map(|&postal_code| {
+                self.dates_between(postal_code, from, to)
+                    .map(move |(date, sighting)| Record {
+                        postal_code,
+                        date,
+                        sighting,
+                    })
+            })
+            .collect()
+    }
+
+    /// Future dates of `postal_code` that were removed, see
+    /// [`History::record`].
+    fn removed(&self, postal_code: NorwegianPostalCode) -> impl Iterator<Item = NaiveDate> + '_ {
+        self.file
+            .removed
+            .get(&postal_code)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    #[must_use]
+    /// Recorded dates within the horizon of `fresh` that are no longer
+    /// returned, and dates removed by earlier runs.
+    ///
+    /// Only dates from `today` up to the last fresh date are newly
+    /// considered, so an empty or shorter response doesn't cancel anything
+    /// beyond it. Dates that were cancelled stay cancelled until they are
+    /// returned again.
+    pub fn cancelled(
+        &self,
+        postal_code: NorwegianPostalCode,
+        fresh: &[NaiveDate],
+        today: NaiveDate,
+    ) -> Vec<NaiveDate> {
+        let mut cancelled: Vec<_> = fresh
+            .iter()
+            .max()
+            .into_iter()
+            .flat_map(|horizon| {
+                self.dates_between(postal_code, Some(today), Some(*horizon))
+                    .map(|(date, _)| date)
+            })
+            .chain(self.removed(postal_code).filter(|date| *date >= today))
+            .filter(|date| !fresh.contains(date))
+            .collect();
+        cancelled.sort_unstable();
+        cancelled.dedup();
+        cancelled
+    }
+
+    /// Merge `fresh` delivery dates with recorded dates beyond their horizon.
+    ///
+    /// Recorded dates from `today` onwards that are later than the last fresh
+    /// date are added, so a calendar doesn't shrink when the data source
+    /// returns a shorter horizon than before. Dates within the fresh horizon
+    /// are taken from `fresh` only, and dates [`History::record`] saw removed
+    /// are left out beyond it too, so removed dates stay removed.
+    #[must_use]
+    pub fn merge_future(
+        &self,
+        postal_code: NorwegianPostalCode,
+        fresh: &[NaiveDate],
+        today: NaiveDate,
+    ) -> Vec<NaiveDate> {
+        let horizon = fresh.iter().max().copied();
+        let mut dates = fresh.to_vec();
+        dates.extend(
+            self.dates(postal_code)
+                .map(|(date, _)| date)
+                .filter(|date| *date >= today && horizon.is_none_or(|horizon| *date > horizon))
+                .filter(|date| !self.removed(postal_code).any(|removed| removed == *date)),
+        );
+        dates.sort_unstable();
+        dates.dedup();
+        dates
+    }
+
+    /// Sequence numbers for events with the given fingerprints, keyed by UID.
+    ///
+    /// New events start at 0, and the sequence is incremented whenever the
+    /// fingerprint of a known event changes.
+    pub fn sequences(&mut self, fingerprints: &[(String, u64)]) -> BTreeMap<String, u32> {
+        fingerprints
+            .iter()
+            .map(|(uid, fingerprint)| {
+                let revision = self
+                    .file
+                    .events
+                    .entry(uid.clone())
+                    .and_modify(|revision| {
+                        if revision.fingerprint != *fingerprint {
+                            revision.fingerprint = *fingerprint;
+                            revision.sequence += 1;
+                        }
+                    })
+                    .or_insert(EventRevision {
+                        fingerprint: *fingerprint,
+                        sequence: 0,
+                    });
+                (uid.clone(), revision.sequence)
+            })
+            .collect()
+    }
+
+    /// Continue the `SEQUENCE` of an event published elsewhere.
+    ///
+    /// The fingerprint of the imported event is unknown, so the next time it
+    /// is published it gets a higher sequence than `sequence`.
+    pub fn import_sequence(&mut self, uid: &str, sequence: u32) {
+        self.file
+            .events
+            .entry(uid.to_string())
+            .and_modify(|revision| revision.sequence = revision.sequence.max(sequence))
+            .or_insert(EventRevision {
+                fingerprint: UNKNOWN_FINGERPRINT,
+                sequence,
+            });
+    }
+
+    /// Write the history back to its file.
+    ///
+    /// The file is replaced atomically, so an interrupted run can't leave a
+    /// truncated history behind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|err| io_error_to_string(&err, dir))?;
+        }
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let file = std::fs::File::create(&tmp).map_err(|err| io_error_to_string(&err, &tmp))?;
+        serde_json::to_writer(file, &self.file)
+            .map_err(|err| format!("{err}: {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path).map_err(|err| io_error_to_string(&err, &self.path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, NaiveDate, Utc};
+
+    use super::{Changes, History};
+    use crate::bring_client::NorwegianPostalCode;
+
+    fn timestamp(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().into()
+    }
+
+    #[test]
+    fn test_record() {
+        let path =
+            std::env::temp_dir().join(format!("postgang-history-{}.json", std::process::id()));
+        let code = NorwegianPostalCode::try_from("7800").unwrap();
+        let first = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+        let second = NaiveDate::from_ymd_opt(2024, 9, 4).unwrap();
+        let t1 = timestamp("2024-09-01T00:00:00Z");
+        let t2 = timestamp("2024-09-02T00:00:00Z");
+
+        let mut history = History::open(path.clone()).unwrap();
+        assert_eq!(history.record(code, &[first], t1), None);
+        assert_eq!(
+            history.record(code, &[second, first], t2),
+            Some(Changes {
+                postal_code: code,
+                added: vec![second],
+                removed: vec![],
+            })
+        );
+        history.save().unwrap();
+
+        let history = History::open(path.clone()).unwrap();
+        let dates: Vec<_> = history
+            .dates(code)
+            .map(|(date, sighting)| (date, sighting.first_seen, sighting.last_seen))
+            .collect();
+        assert_eq!(dates, vec![(first, t1, t2), (second, t2, t2)]);
+        assert_eq!(
+            history
+                .dates(NorwegianPostalCode::try_from("0001").unwrap())
+                .count(),
+            0
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_record_returning() {
+        let path = std::env::temp_dir().join("postgang-history-returning-does-not-exist.json");
+        let code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+        let mut history = History::open(path).unwrap();
+        let _ = history.record(code, &[date(4), date(6)], timestamp("2024-09-01T00:00:00Z"));
+        let changes = history
+            .record(code, &[date(6)], timestamp("2024-09-01T06:00:00Z"))
+            .unwrap();
+        assert_eq!(changes.removed, vec![date(4)]);
+        let changes = history
+            .record(code, &[date(4), date(6)], timestamp("2024-09-01T12:00:00Z"))
+            .unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn test_refreshed() {
+        let path = std::env::temp_dir().join("postgang-history-refreshed-does-not-exist.json");
+        let code = NorwegianPostalCode::try_from("7800").unwrap();
+        let mut history = History::open(path).unwrap();
+        assert_eq!(history.refreshed(code), None);
+        let seen = timestamp("2024-09-01T00:00:00Z");
+        let _ = history.record(code, &[NaiveDate::from_ymd_opt(2024, 9, 2).unwrap()], seen);
+        assert_eq!(history.refreshed(code), Some(seen));
+        let refreshed = timestamp("2024-09-03T00:00:00Z");
+        history.set_refreshed(code, refreshed);
+        assert_eq!(history.refreshed(code), Some(refreshed));
+    }
+
+    #[test]
+    fn test_record_removed() {
+        let path = std::env::temp_dir().join("postgang-history-removed-does-not-exist.json");
+        let code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+        let mut history = History::open(path).unwrap();
+        let _ = history.record(
+            code,
+            &[date(2), date(4), date(6)],
+            timestamp("2024-09-01T00:00:00Z"),
+        );
+        // 2 has passed, 4 was cancelled
+        let changes = history
+            .record(code, &[date(6), date(8)], timestamp("2024-09-03T00:00:00Z"))
+            .unwrap();
+        assert_eq!(changes.added, vec![date(8)]);
+        assert_eq!(changes.removed, vec![date(4)]);
+        assert!(!changes.is_empty());
+    }
+
+    #[test]
+    fn test_sequences() {
+        let path = std::env::temp_dir().join("postgang-history-sequences-does-not-exist.json");
+        let mut history = History::open(path).unwrap();
+        let uid = "postgang-7800-2024-09-02".to_string();
+        let sequences = |history: &mut History, fingerprint| {
+            history.sequences(&[(uid.clone(), fingerprint)])[&uid]
+        };
+        assert_eq!(sequences(&mut history, 1), 0);
+        assert_eq!(sequences(&mut history, 1), 0);
+        assert_eq!(sequences(&mut history, 2), 1);
+        assert_eq!(sequences(&mut history, 2), 1);
+        assert_eq!(sequences(&mut history, 1), 2);
+    }
+
+    #[test]
+    fn test_import_sequence() {
+        let path = std::env::temp_dir().join("postgang-history-import-does-not-exist.json");
+        let mut history = History::open(path).unwrap();
+        let uid = "postgang-7800-2024-09-02".to_string();
+        history.import_sequence(&uid, 3);
+        history.import_sequence(&uid, 1);
+        assert_eq!(history.sequences(&[(uid.clone(), 1)])[&uid], 4);
+        assert_eq!(history.sequences(&[(uid.clone(), 1)])[&uid], 4);
+    }
+
+    #[test]
+    fn test_merge_future() {
+        let path = std::env::temp_dir().join("postgang-history-merge-does-not-exist.json");
+        let code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+        let mut history = History::open(path).unwrap();
+        let _ = history.record(
+            code,
+            &[date(2), date(4), date(6), date(10), date(12)],
+            timestamp("2024-09-01T00:00:00Z"),
+        );
+
+        // 2 is in the past, 6 was removed within the fresh horizon
+        assert_eq!(
+            history.merge_future(code, &[date(4), date(8)], date(3)),
+            vec![date(4), date(8), date(10), date(12)]
+        );
+        assert_eq!(
+            history.merge_future(code, &[], date(5)),
+            vec![date(6), date(10), date(12)]
+        );
+        assert_eq!(
+            history.cancelled(code, &[date(4), date(8)], date(3)),
+            vec![date(6)]
+        );
+        assert_eq!(history.cancelled(code, &[], date(3)), vec![]);
+
+        // 6 is removed, then a shorter and an empty response come
+        let path = std::env::temp_dir().join("postgang-history-merge-removed-does-not-exist.json");
+        let mut history = History::open(path).unwrap();
+        let seen = timestamp("2024-09-01T00:00:00Z");
+        let _ = history.record(code, &[date(4), date(6), date(8)], seen);
+        let _ = history.record(code, &[date(4), date(8)], seen);
+        assert_eq!(history.cancelled(code, &[date(4)], date(1)), vec![date(6)]);
+        let _ = history.record(code, &[date(4)], seen);
+        assert_eq!(
+            history.merge_future(code, &[date(4)], date(1)),
+            vec![date(4), date(8)]
+        );
+        assert_eq!(
+            history.merge_future(code, &[], date(1)),
+            vec![date(4), date(8)]
+        );
+        assert_eq!(history.cancelled(code, &[], date(1)), vec![date(6)]);
+        // Until it comes back
+        let _ = history.record(code, &[date(4), date(6)], seen);
+        assert_eq!(
+            history.merge_future(code, &[date(4), date(6)], date(1)),
+            vec![date(4), date(6), date(8)]
+        );
+        assert_eq!(
+            history.cancelled(code, &[date(4), date(6)], date(1)),
+            vec![]
+        );
+    }
+}
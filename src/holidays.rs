@@ -0,0 +1,100 @@
+//! Norwegian public holidays.
+//!
+//! Mail is not delivered on public holidays, so a delivery date on one of
+//! them is most likely an error in the data.
+use chrono::{Datelike, NaiveDate};
+
+/// Easter Sunday of `year` in the Gregorian calendar.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::holidays::easter;
+///
+/// assert_eq!(easter(2024), NaiveDate::from_ymd_opt(2024, 3, 31));
+/// assert_eq!(easter(2025), NaiveDate::from_ymd_opt(2025, 4, 20));
+/// ```
+#[must_use]
+#[allow(clippy::many_single_char_names)]
+pub fn easter(year: i32) -> Option<NaiveDate> {
+    // Anonymous Gregorian algorithm (Meeus/Jones/Butcher)
+    let a = year.rem_euclid(19);
+    let b = year.div_euclid(100);
+    let c = year.rem_euclid(100);
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, u32::try_from(month).ok()?, u32::try_from(day).ok()?)
+}
+
+/// Norwegian name of the public holiday on `date`, if any.
+///
+/// Sundays are not counted as holidays.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::holidays::holiday;
+///
+/// let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+/// assert_eq!(holiday(date(5, 17)), Some("grunnlovsdag"));
+/// assert_eq!(holiday(date(3, 29)), Some("langfredag"));
+/// assert_eq!(holiday(date(5, 9)), Some("Kristi himmelfartsdag"));
+/// assert_eq!(holiday(date(5, 16)), None);
+/// ```
+#[must_use]
+pub fn holiday(date: NaiveDate) -> Option<&'static str> {
+    let fixed = match (date.month(), date.day()) {
+        (1, 1) => Some("1. nyttårsdag"),
+        (5, 1) => Some("arbeidernes dag"),
+        (5, 17) => Some("grunnlovsdag"),
+        (12, 25) => Some("1. juledag"),
+        (12, 26) => Some("2. juledag"),
+        _ => None,
+    };
+    fixed.or_else(|| {
+        let easter = easter(date.year())?;
+        match (date - easter).num_days() {
+            -3 => Some("skjærtorsdag"),
+            -2 => Some("langfredag"),
+            0 => Some("1. påskedag"),
+            1 => Some("2. påskedag"),
+            39 => Some("Kristi himmelfartsdag"),
+            49 => Some("1. pinsedag"),
+            50 => Some("2. pinsedag"),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Datelike, NaiveDate};
+
+    use super::{easter, holiday};
+
+    #[test]
+    fn test_easter() {
+        for (year, month, day) in [(1970, 3, 29), (2000, 4, 23), (2019, 4, 21), (2038, 4, 25)] {
+            assert_eq!(easter(year), NaiveDate::from_ymd_opt(year, month, day));
+        }
+    }
+
+    #[test]
+    fn test_holidays_in_year() {
+        let from = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        let holidays: Vec<_> = from
+            .iter_days()
+            .take_while(|date| date.year() == 2025)
+            .filter_map(holiday)
+            .collect();
+        assert_eq!(holidays.len(), 12);
+        assert_eq!(holidays[1], "skjærtorsdag");
+    }
+}
@@ -0,0 +1,196 @@
+//! Reading delivery dates back from iCalendar files.
+//!
+//! Only what postgang writes is understood: the date of `DTSTART`, `UID`,
+//! `SUMMARY`, `STATUS` and `SEQUENCE` of each `VEVENT`. Other properties and
+//! components are ignored, so simple calendars from other tools work too.
+use alloc::collections::BTreeMap;
+
+use chrono::NaiveDate;
+
+use crate::bring_client::NorwegianPostalCode;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A `VEVENT` read from a calendar.
+pub struct Event {
+    pub uid: Option<String>,
+    pub date: NaiveDate,
+    /// From a postgang `UID`, or a `SUMMARY` starting with the postal code.
+    pub postal_code: Option<NorwegianPostalCode>,
+    pub cancelled: bool,
+    pub sequence: Option<u32>,
+}
+
+/// Join folded lines and split the content into lines.
+fn unfold(content: &str) -> impl Iterator<Item = String> + '_ {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines.into_iter().filter(|line| !line.is_empty())
+}
+
+/// Split a content line into its name and value, leaving out parameters.
+fn property(line: &str) -> Option<(&str, &str)> {
+    let (name_and_params, value) = line.split_once(':')?;
+    let name = name_and_params.split(';').next()?;
+    Some((name, value))
+}
+
+fn postal_code(uid: Option<&str>, summary: Option<&str>) -> Option<NorwegianPostalCode> {
+    let from_uid = uid
+        .and_then(|uid| uid.strip_prefix("postgang-"))
+        .and_then(|rest| rest.split('-').next());
+    let from_summary = summary.and_then(|summary| summary.split(':').next());
+    from_uid
+        .into_iter()
+        .chain(from_summary)
+        .find_map(|code| NorwegianPostalCode::try_from(code.trim()).ok())
+}
+
+/// Read the events of an iCalendar file.
+///
+/// ```
+/// use postgang::ics;
+///
+/// let events = ics::parse(
+///     "BEGIN:VCALENDAR\r\n\
+///      BEGIN:VEVENT\r\n\
+///      DTSTART;VALUE=DATE:19700813\r\n\
+///      SUMMARY:7800: Posten kommer\r\n  torsdag 13. august.\r\n\
+///      UID:postgang-7800-1970-08-13\r\n\
+///      END:VEVENT\r\n\
+///      END:VCALENDAR\r\n",
+/// )
+/// .unwrap();
+/// assert_eq!(events[0].date.to_string(), "1970-08-13");
+/// assert_eq!(events[0].postal_code.unwrap().to_string(), "7800");
+/// assert!(!events[0].cancelled);
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if an event has no valid `DTSTART`.
+pub fn parse(content: &str) -> Result<Vec<Event>, String> {
+    let mut events = Vec::new();
+    let mut properties: Option<BTreeMap<String, String>> = None;
+    let mut nested = 0_usize;
+    for line in unfold(content) {
+        let Some((name, value)) = property(&line) else {
+            continue;
+        };
+        match (name.to_ascii_uppercase().as_str(), &mut properties) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                properties = Some(BTreeMap::new());
+            }
+            ("BEGIN", Some(_)) => nested += 1,
+            ("END", Some(_)) if nested > 0 => nested -= 1,
+            ("END", Some(event)) if value.eq_ignore_ascii_case("VEVENT") => {
+                events.push(event_from(event, events.len())?);
+                properties = None;
+            }
+            (name, Some(event)) if nested == 0 => {
+                event.insert(name.to_string(), value.to_string());
+            }
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+fn event_from(properties: &BTreeMap<String, String>, index: usize) -> Result<Event, String> {
+    let start = properties
+        .get("DTSTART")
+        .ok_or_else(|| format!("Event {} has no DTSTART", index + 1))?;
+    let date = start
+        .get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+        .ok_or_else(|| format!("Event {} has an invalid DTSTART: {start:?}", index + 1))?;
+    let uid = properties.get("UID").cloned();
+    Ok(Event {
+        postal_code: postal_code(
+            uid.as_deref(),
+            properties.get("SUMMARY").map(String::as_str),
+        ),
+        uid,
+        date,
+        cancelled: properties
+            .get("STATUS")
+            .is_some_and(|status| status.eq_ignore_ascii_case("CANCELLED")),
+        sequence: properties
+            .get("SEQUENCE")
+            .and_then(|sequence| sequence.parse().ok()),
+    })
+}
+
+/// Group the dates of the events that are not cancelled by postal code.
+///
+/// Events without a postal code get `default`.
+///
+/// # Errors
+///
+/// Returns an error if an event has no postal code and there is no
+/// `default`.
+pub fn delivery_dates(
+    events: &[Event],
+    default: Option<NorwegianPostalCode>,
+) -> Result<BTreeMap<NorwegianPostalCode, Vec<NaiveDate>>, String> {
+    let mut dates: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for event in events.iter().filter(|event| !event.cancelled) {
+        let postal_code = event
+            .postal_code
+            .or(default)
+            .ok_or_else(|| format!("No postal code for the event on {}, use --code", event.date))?;
+        dates.entry(postal_code).or_default().push(event.date);
+    }
+    for dates in dates.values_mut() {
+        dates.sort_unstable();
+        dates.dedup();
+    }
+    Ok(dates)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::{delivery_dates, parse};
+    use crate::{
+        bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode},
+        calendar::{Alarm, AlarmAction, Calendar},
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let day = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+        let calendar = Calendar::new(vec![DeliveryDate::new(postal_code, day(2))], None)
+            .with_cancelled(vec![DeliveryDate::new(postal_code, day(4))])
+            .with_alarm(Some(Alarm {
+                trigger: "1d@20:00".parse().unwrap(),
+                action: AlarmAction::Display,
+            }))
+            .with_line_length(20)
+            .to_string();
+        let events = parse(&calendar).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[1].cancelled);
+        assert_eq!(
+            delivery_dates(&events, None).unwrap(),
+            [(postal_code, vec![day(2)])].into()
+        );
+    }
+
+    #[test]
+    fn test_without_postal_code() {
+        let events =
+            parse("BEGIN:VEVENT\nDTSTART:20240902T090000Z\nSUMMARY:Post\nEND:VEVENT\n").unwrap();
+        assert_eq!(events[0].postal_code, None);
+        assert!(delivery_dates(&events, None).is_err());
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        assert_eq!(delivery_dates(&events, Some(postal_code)).unwrap().len(), 1);
+        assert!(parse("BEGIN:VEVENT\nSUMMARY:Post\nEND:VEVENT\n").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! launchd agent running postgang, for `postgang generate launchd` on macOS.
+use core::{fmt::Write as _, time::Duration};
+use std::path::Path;
+
+/// Label of the agent, also the name of its plist.
+pub const LABEL: &str = "no.aasan.postgang";
+
+/// Escape `text` for XML content.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[must_use]
+/// A `LaunchAgent` plist running `exec` from `working_directory` every
+/// `interval` with the variables `env`, logging to `log` if given.
+///
+/// ```
+/// use core::time::Duration;
+/// use std::path::Path;
+///
+/// use postgang::launchd::plist;
+///
+/// let exec = ["/usr/local/bin/postgang", "--code", "7800", "api"].map(String::from);
+/// let plist = plist(&exec, Path::new("/Users/ola"), Duration::from_hours(6), &[], None);
+/// assert!(plist.contains("<key>StartInterval</key>\n  <integer>21600</integer>\n"));
+/// assert!(plist.contains("    <string>7800</string>\n"));
+/// assert!(!plist.contains("EnvironmentVariables"));
+/// ```
+pub fn plist(
+    exec: &[String],
+    working_directory: &Path,
+    interval: Duration,
+    env: &[(String, String)],
+    log: Option<&Path>,
+) -> String {
+    let mut plist = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n  \
+           <key>Label</key>\n  \
+           <string>{LABEL}</string>\n  \
+           <key>ProgramArguments</key>\n  \
+           <array>\n"
+    );
+    // Writing to a String can't fail
+    for arg in exec {
+        let _ = writeln!(plist, "    <string>{}</string>", escape(arg));
+    }
+    let _ = write!(
+        plist,
+        "  </array>\n  \
+           <key>WorkingDirectory</key>\n  \
+           <string>{}</string>\n  \
+           <key>StartInterval</key>\n  \
+           <integer>{}</integer>\n  \
+           <key>RunAtLoad</key>\n  \
+           <true/>\n",
+        escape(&working_directory.to_string_lossy()),
+        interval.as_secs().max(1)
+    );
+    if !env.is_empty() {
+        plist.push_str("  <key>EnvironmentVariables</key>\n  <dict>\n");
+        for (name, value) in env {
+            let _ = writeln!(
+                plist,
+                "    <key>{}</key>\n    <string>{}</string>",
+                escape(name),
+                escape(value)
+            );
+        }
+        plist.push_str("  </dict>\n");
+    }
+    if let Some(log) = log {
+        let log = escape(&log.to_string_lossy());
+        let _ = writeln!(
+            plist,
+            "  <key>StandardOutPath</key>\n  <string>{log}</string>\n  \
+             <key>StandardErrorPath</key>\n  <string>{log}</string>"
+        );
+    }
+    plist.push_str("</dict>\n</plist>\n");
+    plist
+}
+
+#[cfg(test)]
+mod test {
+    use core::time::Duration;
+    use std::path::Path;
+
+    use super::plist;
+
+    #[test]
+    fn test_env_and_log() {
+        let exec = ["/usr/local/bin/postgang", "--on-change", "a && b"].map(String::from);
+        let env = [(
+            "POSTGANG_CONFIG".to_string(),
+            "/Users/ola/postgang.toml".to_string(),
+        )];
+        let plist = plist(
+            &exec,
+            Path::new("/"),
+            Duration::from_mins(30),
+            &env,
+            Some(Path::new("/Users/ola/Library/Logs/postgang.log")),
+        );
+        assert!(plist.contains("    <string>a &amp;&amp; b</string>\n"));
+        assert!(plist.contains(
+            "  <dict>\n    <key>POSTGANG_CONFIG</key>\n    <string>/Users/ola/postgang.toml</string>\n  </dict>\n"
+        ));
+        assert!(plist.contains(
+            "<key>StandardErrorPath</key>\n  <string>/Users/ola/Library/Logs/postgang.log</string>\n</dict>\n</plist>\n"
+        ));
+    }
+}
@@ -1,12 +1,51 @@
 //! Create iCalendar file for norwegian mailbox delivery dates.
+extern crate alloc;
+
 use std::io;
-use std::path::PathBuf;
+use std::path::Path;
 
 pub mod bring_client;
 pub mod calendar;
+pub mod config;
+pub mod cron;
+pub mod doctor;
+pub mod error_report;
+pub mod failures;
+pub mod formats;
+pub mod history;
+pub mod holidays;
+pub mod http_client;
+pub mod ics;
+pub mod launchd;
+pub mod locale;
+pub mod log_file;
+pub mod notify;
+pub mod paths;
+pub mod schedule;
+#[cfg(all(windows, feature = "windows-service"))]
+pub mod service;
+pub mod setup;
+#[cfg(feature = "smtp")]
+pub mod smtp;
+pub mod stats;
+pub mod systemd;
+#[cfg(feature = "tui")]
+pub mod tui;
 
 #[inline]
 #[must_use]
-pub fn io_error_to_string(err: &io::Error, path: &PathBuf) -> String {
-    format!("{err}: {path:?}")
+pub fn io_error_to_string(err: &io::Error, path: &Path) -> String {
+    format!("{err}: {}", path.display())
+}
+
+/// 64-bit FNV-1a hash of `bytes`.
+///
+/// Unlike [`core::hash::Hash`] implementations, the result is stable across
+/// runs and builds, so it can be persisted.
+pub(crate) fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0100_0000_01b3;
+    bytes.into_iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
 }
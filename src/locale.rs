@@ -0,0 +1,548 @@
+//! Language of messages printed on the command line.
+//!
+//! Published formats are in Norwegian, except the summaries of calendars
+//! given another locale in the configuration.
+use core::{error::Error, fmt, str::FromStr, time::Duration};
+use std::{ffi::OsString, path::Path};
+
+use chrono::{Datelike, NaiveDate, Weekday};
+
+use crate::{
+    bring_client::{
+        mailbox_delivery_dates::{AuthError, DeliveryDate, FetchError, UnknownPostalCode},
+        NorwegianPostalCode,
+    },
+    calendar::{self, relative_summary, summary},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    Norwegian,
+    English,
+}
+
+/// Questions and messages of `postgang setup`.
+#[derive(Debug, Clone, Copy)]
+pub enum Setup<'a> {
+    /// Where the API key comes from.
+    Intro,
+    ApiUid,
+    ApiKey,
+    PostalCode,
+    /// The credentials couldn't be checked, because of the error.
+    CheckFailed(&'a str),
+    /// Whether to overwrite the configuration file.
+    Overwrite(&'a Path),
+    /// Whether to store the API key in the keyring.
+    Keyring,
+    /// The configuration file was written.
+    Written(&'a Path),
+    /// The choices of a yes or no question, the default in upper case.
+    YesNo(bool),
+    /// An answer to a yes or no question that was neither.
+    NotYesNo,
+}
+
+/// Errors and reports printed on the command line.
+#[derive(Debug, Clone, Copy)]
+pub enum Message<'a> {
+    /// Why the API answered `401` or `403`.
+    WrongCredentials,
+    UnknownPostalCode(NorwegianPostalCode),
+    /// A lookup found that the postal code isn't in use.
+    InvalidPostalCode(NorwegianPostalCode),
+    InvalidPostalCodes(&'a str),
+    OnePostalCodeWithFile,
+    OutputDirNeedsIcal,
+    SplitByMonthNeedsFile,
+    /// `postgang healthcheck` found no refresh in the history.
+    NeverRefreshed,
+    /// How long ago the last refresh was, and the allowed age when it is
+    /// older than that.
+    Refreshed {
+        age: Duration,
+        max_age: Option<Duration>,
+    },
+    Stale,
+    /// Runs of the service in a row that failed, the last `age` ago.
+    FailedRuns {
+        count: u32,
+        age: Duration,
+    },
+    /// The delivery dates are fresh, but the last runs of the service failed.
+    Degraded,
+    /// `postgang doctor` found no configuration file.
+    NoConfigFile,
+    Valid(&'a Path),
+    CredentialsSet,
+    Writable(&'a Path),
+    CanBeCreated(&'a Path),
+    NoSuchDirectory(&'a Path),
+    Answered {
+        url: &'a str,
+        status: &'a str,
+    },
+    ChecksFailed {
+        failed: usize,
+        total: usize,
+    },
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    /// Parse a language tag like `nb`, `en` or `en_US.UTF-8`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "nb" | "nn" | "no" => Ok(Self::Norwegian),
+            "en" => Ok(Self::English),
+            _ => Err(format!("Unknown locale {s:?}, expected nb or en")),
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Norwegian => "nb",
+            Self::English => "en",
+        })
+    }
+}
+
+impl Locale {
+    #[must_use]
+    /// Pick the locale from `LC_ALL`, `LC_MESSAGES` or `LANG`, looked up
+    /// with `env`.
+    ///
+    /// Norwegian locales give Norwegian, anything else gives English,
+    /// including `C`, `POSIX` and no locale at all, as under cron.
+    ///
+    /// ```
+    /// use postgang::locale::Locale;
+    ///
+    /// let env = |lang: &'static str| move |var: &str| (var == "LANG").then(|| lang.into());
+    /// assert_eq!(Locale::from_env(env("en_GB.UTF-8")), Locale::English);
+    /// assert_eq!(Locale::from_env(env("nb_NO.UTF-8")), Locale::Norwegian);
+    /// assert_eq!(Locale::from_env(env("C.UTF-8")), Locale::English);
+    /// assert_eq!(Locale::from_env(|_| None), Locale::English);
+    /// ```
+    pub fn from_env(env: impl Fn(&str) -> Option<OsString>) -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(&env)
+            .find(|value| !value.is_empty())
+            .and_then(|value| value.to_str()?.parse().ok())
+            .unwrap_or(Self::English)
+    }
+
+    #[must_use]
+    /// Name of the weekday of `date`, in lower case in Norwegian.
+    pub fn weekday(self, date: NaiveDate) -> &'static str {
+        match self {
+            Self::Norwegian => calendar::weekday(date),
+            Self::English => match date.weekday() {
+                Weekday::Mon => "Monday",
+                Weekday::Tue => "Tuesday",
+                Weekday::Wed => "Wednesday",
+                Weekday::Thu => "Thursday",
+                Weekday::Fri => "Friday",
+                Weekday::Sat => "Saturday",
+                Weekday::Sun => "Sunday",
+            },
+        }
+    }
+
+    #[must_use]
+    /// Name of the month of `date`, in lower case in Norwegian.
+    pub fn month(self, date: NaiveDate) -> &'static str {
+        const MONTHS: [&str; 12] = [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ];
+        match self {
+            Self::Norwegian => calendar::month(date),
+            Self::English => MONTHS[date.month0() as usize],
+        }
+    }
+
+    #[must_use]
+    /// The weekday, day and month of `date`, like "mandag 2. september".
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::locale::Locale;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+    /// assert_eq!(Locale::Norwegian.date(date), "mandag 2. september");
+    /// assert_eq!(Locale::English.date(date), "Monday 2 September");
+    /// ```
+    pub fn date(self, date: NaiveDate) -> String {
+        match self {
+            Self::Norwegian => format!(
+                "{} {}. {}",
+                self.weekday(date),
+                date.day(),
+                self.month(date)
+            ),
+            Self::English => format!("{} {} {}", self.weekday(date), date.day(), self.month(date)),
+        }
+    }
+
+    #[must_use]
+    /// Summary of a delivery date in this locale.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::mailbox_delivery_dates::DeliveryDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::locale::Locale;
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(1970, 8, 13).unwrap());
+    /// assert_eq!(Locale::English.summary(date), "7800: Mail arrives Thursday 13 August.");
+    /// assert_eq!(Locale::Norwegian.summary(date), "7800: Posten kommer torsdag 13. august.");
+    /// ```
+    pub fn summary(self, delivery_date: DeliveryDate) -> String {
+        match self {
+            Self::Norwegian => summary(delivery_date),
+            Self::English => format!(
+                "{}: Mail arrives {}.",
+                delivery_date.postal_code,
+                self.date(delivery_date.date)
+            ),
+        }
+    }
+
+    #[must_use]
+    /// Like [`Locale::summary`], but saying today or tomorrow when the
+    /// delivery is that close.
+    pub fn relative_summary(self, delivery_date: DeliveryDate, today: NaiveDate) -> String {
+        match self {
+            Self::Norwegian => relative_summary(delivery_date, today),
+            Self::English => match (delivery_date.date - today).num_days() {
+                0 => format!("{}: Mail arrives today.", delivery_date.postal_code),
+                1 => format!("{}: Mail arrives tomorrow.", delivery_date.postal_code),
+                _ => self.summary(delivery_date),
+            },
+        }
+    }
+
+    #[must_use]
+    /// The ISO `week` number in an event description.
+    pub fn week(self, week: u32) -> String {
+        match self {
+            Self::Norwegian => format!("Uke {week}"),
+            Self::English => format!("Week {week}"),
+        }
+    }
+
+    #[must_use]
+    /// Note in an event description that the day is a public `holiday`.
+    pub fn holiday(self, holiday: &str) -> String {
+        match self {
+            Self::Norwegian => format!("Merk: {holiday} er en helligdag."),
+            Self::English => format!("Note: {holiday} is a public holiday."),
+        }
+    }
+
+    #[must_use]
+    /// Error message for a missing command line option.
+    pub fn required(self, option: &str) -> String {
+        match self {
+            Self::Norwegian => format!("{option} må oppgis"),
+            Self::English => format!("{option} is required"),
+        }
+    }
+
+    #[must_use]
+    /// An error or a line of a report.
+    ///
+    /// ```
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::locale::{Locale, Message};
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("0000").unwrap();
+    /// let message = Message::InvalidPostalCode(postal_code);
+    /// assert_eq!(Locale::English.message(message), "0000: Invalid postal code");
+    /// assert_eq!(Locale::Norwegian.message(message), "0000: Ugyldig postnummer");
+    /// ```
+    pub fn message(self, message: Message<'_>) -> String {
+        match self {
+            Self::Norwegian => norwegian(message),
+            Self::English => english(message),
+        }
+    }
+
+    #[must_use]
+    /// Describe `err` like its `Display`, with the API errors of
+    /// [`Message`] in this locale.
+    pub fn error(self, err: &(dyn Error + 'static)) -> String {
+        if let Some(err) = err.downcast_ref::<FetchError>() {
+            format!("{}: {}", err.postal_code, self.error(err.source.as_ref()))
+        } else if let Some(err) = err.downcast_ref::<AuthError>() {
+            format!("{}\n{}", err.0, self.message(Message::WrongCredentials))
+        } else if let Some(err) = err.downcast_ref::<UnknownPostalCode>() {
+            self.message(Message::UnknownPostalCode(err.0))
+        } else {
+            err.to_string()
+        }
+    }
+
+    #[must_use]
+    /// A question or message of `postgang setup`.
+    ///
+    /// ```
+    /// use std::path::Path;
+    ///
+    /// use postgang::locale::{Locale, Setup};
+    ///
+    /// let path = Path::new("postgang.toml");
+    /// assert_eq!(Locale::English.setup(Setup::Overwrite(path)), "postgang.toml exists, overwrite it?");
+    /// assert_eq!(Locale::Norwegian.setup(Setup::YesNo(true)), "J/n");
+    /// ```
+    pub fn setup(self, message: Setup<'_>) -> String {
+        match (self, message) {
+            (Self::Norwegian, Setup::Intro) => {
+                "Postgang henter leveringsdagene fra Bring, med en API-nøkkel fra\n\
+                 https://www.mybring.com/useradmin/account/settings/api"
+                    .to_string()
+            }
+            (Self::English, Setup::Intro) => {
+                "Postgang gets the delivery dates from Bring, with an API key from\n\
+                 https://www.mybring.com/useradmin/account/settings/api"
+                    .to_string()
+            }
+            (Self::Norwegian, Setup::ApiUid) => "Mybring-bruker (e-post)".to_string(),
+            (Self::English, Setup::ApiUid) => "Mybring user (email)".to_string(),
+            (Self::Norwegian, Setup::ApiKey) => "API-nøkkel".to_string(),
+            (Self::English, Setup::ApiKey) => "API key".to_string(),
+            (Self::Norwegian, Setup::PostalCode) => "Postnummer".to_string(),
+            (Self::English, Setup::PostalCode) => "Postal code".to_string(),
+            (Self::Norwegian, Setup::CheckFailed(err)) => {
+                format!("Fikk ikke sjekket brukeren og nøkkelen: {err}")
+            }
+            (Self::English, Setup::CheckFailed(err)) => {
+                format!("Couldn't check the user and the key: {err}")
+            }
+            (Self::Norwegian, Setup::Overwrite(path)) => {
+                format!("{} finnes, vil du skrive over den?", path.display())
+            }
+            (Self::English, Setup::Overwrite(path)) => {
+                format!("{} exists, overwrite it?", path.display())
+            }
+            (Self::Norwegian, Setup::Keyring) => {
+                "Lagre API-nøkkelen i nøkkelringen i stedet for i konfigurasjonsfilen?".to_string()
+            }
+            (Self::English, Setup::Keyring) => {
+                "Store the API key in the keyring instead of the configuration file?".to_string()
+            }
+            (Self::Norwegian, Setup::Written(path)) => format!(
+                "Skrev {}. Prøv `postgang next` etter `postgang api`.",
+                path.display()
+            ),
+            (Self::English, Setup::Written(path)) => format!(
+                "Wrote {}. Try `postgang next` after `postgang api`.",
+                path.display()
+            ),
+            (Self::Norwegian, Setup::YesNo(default)) => {
+                (if default { "J/n" } else { "j/N" }).to_string()
+            }
+            (Self::English, Setup::YesNo(default)) => {
+                (if default { "Y/n" } else { "y/N" }).to_string()
+            }
+            (Self::Norwegian, Setup::NotYesNo) => "Svar j eller n.".to_string(),
+            (Self::English, Setup::NotYesNo) => "Answer y or n.".to_string(),
+        }
+    }
+}
+
+/// [`Locale::message`] in Norwegian.
+fn norwegian(message: Message<'_>) -> String {
+    let duration = humantime::format_duration;
+    match message {
+        Message::WrongCredentials => {
+            "API-brukeren eller -nøkkelen er feil, eller nøkkelen er ikke aktivert for \
+             Postal Code API. Sjekk --api-uid og --api-key, POSTGANG_API_UID og \
+             POSTGANG_API_KEY, eller api-uid og api-key i konfigurasjonsfilen."
+                .to_string()
+        }
+        Message::UnknownPostalCode(postal_code) => {
+            format!("Ukjent postnummer {postal_code}, sjekk --code eller konfigurasjonsfilen")
+        }
+        Message::InvalidPostalCode(postal_code) => {
+            format!("{postal_code}: Ugyldig postnummer")
+        }
+        Message::InvalidPostalCodes(codes) => {
+            format!("Ugyldige postnumre: {codes}")
+        }
+        Message::OnePostalCodeWithFile => "Bare ett postnummer kan brukes med en fil".to_string(),
+        Message::OutputDirNeedsIcal => "--output-dir virker bare med --format ical".to_string(),
+        Message::SplitByMonthNeedsFile => {
+            "--split-by-month trenger --format ical og en fil å skrive".to_string()
+        }
+        Message::NeverRefreshed => "Aldri oppdatert".to_string(),
+        Message::Refreshed { age, max_age } => match max_age {
+            Some(max_age) => {
+                format!(
+                    "Oppdatert for {} siden, mer enn {}",
+                    duration(age),
+                    duration(max_age)
+                )
+            }
+            None => format!("Oppdatert for {} siden", duration(age)),
+        },
+        Message::Stale => "Leveringsdagene er for gamle".to_string(),
+        Message::FailedRuns { count, age } => {
+            format!(
+                "{count} kjøringer på rad feilet, sist for {} siden",
+                duration(age)
+            )
+        }
+        Message::Degraded => "Tjenesten feiler".to_string(),
+        Message::NoConfigFile => "Ingen konfigurasjonsfil, bruker standardverdiene".to_string(),
+        Message::Valid(path) => format!("{} er gyldig", path.display()),
+        Message::CredentialsSet => "--api-uid og --api-key er satt".to_string(),
+        Message::Writable(path) => {
+            format!("{} kan skrives", path.display())
+        }
+        Message::CanBeCreated(path) => {
+            format!("{} kan opprettes", path.display())
+        }
+        Message::NoSuchDirectory(path) => {
+            format!("Katalogen finnes ikke: {}", path.display())
+        }
+        Message::Answered { url, status } => {
+            format!("{url} svarte {status}")
+        }
+        Message::ChecksFailed { failed, total } => {
+            format!("{failed} av {total} sjekker feilet")
+        }
+    }
+}
+
+/// [`Locale::message`] in English.
+fn english(message: Message<'_>) -> String {
+    let duration = humantime::format_duration;
+    match message {
+        Message::WrongCredentials => {
+            "The API uid or key is wrong, or the key is not activated for the Postal Code \
+             API. Check --api-uid and --api-key, POSTGANG_API_UID and POSTGANG_API_KEY, or \
+             api-uid and api-key in the configuration file."
+                .to_string()
+        }
+        Message::UnknownPostalCode(postal_code) => {
+            format!("Unknown postal code {postal_code}, check --code or the configuration file")
+        }
+        Message::InvalidPostalCode(postal_code) => {
+            format!("{postal_code}: Invalid postal code")
+        }
+        Message::InvalidPostalCodes(codes) => {
+            format!("Invalid postal codes: {codes}")
+        }
+        Message::OnePostalCodeWithFile => {
+            "Only one postal code can be used with file input".to_string()
+        }
+        Message::OutputDirNeedsIcal => "--output-dir only works with --format ical".to_string(),
+        Message::SplitByMonthNeedsFile => {
+            "--split-by-month needs --format ical and a file to write".to_string()
+        }
+        Message::NeverRefreshed => "Never refreshed".to_string(),
+        Message::Refreshed { age, max_age } => match max_age {
+            Some(max_age) => {
+                format!(
+                    "Refreshed {} ago, more than {}",
+                    duration(age),
+                    duration(max_age)
+                )
+            }
+            None => format!("Refreshed {} ago", duration(age)),
+        },
+        Message::Stale => "Delivery dates are stale".to_string(),
+        Message::FailedRuns { count, age } => {
+            format!(
+                "{count} runs in a row failed, the last {} ago",
+                duration(age)
+            )
+        }
+        Message::Degraded => "The service is degraded".to_string(),
+        Message::NoConfigFile => "No configuration file, using defaults".to_string(),
+        Message::Valid(path) => format!("{} is valid", path.display()),
+        Message::CredentialsSet => "--api-uid and --api-key are set".to_string(),
+        Message::Writable(path) => format!("{} is writable", path.display()),
+        Message::CanBeCreated(path) => {
+            format!("{} can be created", path.display())
+        }
+        Message::NoSuchDirectory(path) => {
+            format!("No such directory: {}", path.display())
+        }
+        Message::Answered { url, status } => {
+            format!("{url} answered {status}")
+        }
+        Message::ChecksFailed { failed, total } => {
+            format!("{failed} of {total} checks failed")
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::Locale;
+    use crate::bring_client::{
+        mailbox_delivery_dates::{DeliveryDate, FetchError, UnknownPostalCode},
+        NorwegianPostalCode,
+    };
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("en_US.UTF-8".parse(), Ok(Locale::English));
+        assert_eq!("nn-NO".parse(), Ok(Locale::Norwegian));
+        assert_eq!("NB".parse(), Ok(Locale::Norwegian));
+        assert!("sv_SE".parse::<Locale>().is_err());
+    }
+
+    #[test]
+    fn test_relative_summary() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+        let date = |days| DeliveryDate::new(postal_code, today + chrono::Days::new(days));
+        let summary = |days| Locale::English.relative_summary(date(days), today);
+        assert_eq!(summary(0), "7800: Mail arrives today.");
+        assert_eq!(summary(1), "7800: Mail arrives tomorrow.");
+        assert_eq!(summary(2), "7800: Mail arrives Wednesday 4 September.");
+    }
+
+    #[test]
+    fn test_error() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let err = FetchError {
+            postal_code,
+            source: Box::new(UnknownPostalCode(postal_code)),
+        };
+        assert_eq!(Locale::English.error(&err), err.to_string());
+        assert_eq!(
+            Locale::Norwegian.error(&err),
+            "7800: Ukjent postnummer 7800, sjekk --code eller konfigurasjonsfilen"
+        );
+        let err: Box<dyn core::error::Error> = "--code is required".into();
+        assert_eq!(Locale::Norwegian.error(err.as_ref()), "--code is required");
+    }
+}
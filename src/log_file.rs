@@ -0,0 +1,148 @@
+//! Log file with size and time based rotation.
+//!
+//! When the file grows past the size limit, or gets older than the age
+//! limit, it is renamed to `<path>.1`, older files are shifted to `<path>.2`
+//! and so on, and a new file is started. Only the newest rotated files are
+//! kept.
+use core::time::Duration;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// When to rotate a log file.
+pub struct Rotation {
+    /// Rotate before the file grows past this many bytes.
+    pub max_size: Option<u64>,
+    /// Rotate when the file was started longer ago than this.
+    pub max_age: Option<Duration>,
+    /// Number of rotated files to keep.
+    pub keep: usize,
+}
+
+/// A log file that rotates itself according to a [`Rotation`].
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Rotation,
+    file: File,
+    size: u64,
+    started: SystemTime,
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{n}"));
+    PathBuf::from(rotated)
+}
+
+impl RotatingFile {
+    /// Open `path` for appending.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be opened.
+    pub fn open(path: PathBuf, rotation: Rotation) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        let started = metadata
+            .created()
+            .or_else(|_| metadata.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+        Ok(Self {
+            path,
+            rotation,
+            file,
+            size: metadata.len(),
+            started,
+        })
+    }
+
+    fn should_rotate(&self, len: usize) -> bool {
+        let too_big = self.rotation.max_size.is_some_and(|max_size| {
+            self.size > 0 && self.size.saturating_add(len as u64) > max_size
+        });
+        let too_old = self.rotation.max_age.is_some_and(|max_age| {
+            self.size > 0
+                && self
+                    .started
+                    .elapsed()
+                    .is_ok_and(|elapsed| elapsed > max_age)
+        });
+        too_big || too_old
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.rotation.keep == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.rotation.keep).rev() {
+                match std::fs::rename(rotated(&self.path, n), rotated(&self.path, n + 1)) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+            std::fs::rename(&self.path, rotated(&self.path, 1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.started = SystemTime::now();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.should_rotate(buf.len()) {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use super::{rotated, RotatingFile, Rotation};
+
+    #[test]
+    fn test_rotate_by_size() {
+        let dir = std::env::temp_dir().join(format!("postgang-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("postgang.log");
+        let rotation = Rotation {
+            max_size: Some(10),
+            max_age: None,
+            keep: 2,
+        };
+        let mut file = RotatingFile::open(path.clone(), rotation).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(
+            std::fs::read_to_string(rotated(&path, 1)).unwrap(),
+            "third\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(rotated(&path, 2)).unwrap(),
+            "second\n"
+        );
+        assert!(!rotated(&path, 3).exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,13 +1,49 @@
-use std::{error::Error, io::Write, path::PathBuf, process::ExitCode};
+extern crate alloc;
 
-use clap::{Parser as ClapParser, ValueEnum};
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::{error::Error, num::NonZeroUsize, pin::pin, time::Duration};
+use std::{
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc, Weekday};
+use clap::{parser::ValueSource, Args, CommandFactory as _, Parser as ClapParser, ValueEnum};
+use futures_util::StreamExt;
 use git_version::git_version;
+use serde::de::DeserializeOwned;
 
 use postgang::bring_client::mailbox_delivery_dates::{ApiResponse, DeliveryDate};
 use postgang::{
-    bring_client::{mailbox_delivery_dates::DeliveryDays, ApiKey, ApiUid, NorwegianPostalCode},
-    calendar::Calendar,
-    io_error_to_string,
+    bring_client::{
+        cache::ResponseCache,
+        fixtures::Fixtures,
+        mailbox_delivery_dates::{self, ApiOptions, DeliveryDays, SCHEMA},
+        postal_codes::PostalCodes,
+        retry::RetryPolicy,
+        ApiKey, ApiUid, IpFamily, NorwegianPostalCode, Resolve,
+    },
+    calendar::{
+        self, Alarm, AlarmAction, AlarmTrigger, Calendar, DeliveryWindow, EventSettings, LineEnding,
+    },
+    config::{self, Config},
+    cron,
+    doctor::{self, Check},
+    error_report::ErrorReport,
+    failures::{self, Failures},
+    formats,
+    history::{self, Changes, History},
+    holidays,
+    http_client::{self, Url},
+    ics, io_error_to_string, launchd,
+    locale::{Locale, Message, Setup},
+    log_file::{RotatingFile, Rotation},
+    notify::{self, notify_all, Notifier, Reminder},
+    paths::{self, Paths},
+    setup,
+    stats::{self, Stats},
+    systemd,
 };
 
 pub struct ApiResponseWithPostalCode {
@@ -26,14 +62,105 @@ impl From<ApiResponseWithPostalCode> for Vec<DeliveryDate> {
     }
 }
 
+/// Per postal code status lines for batch runs, on stderr to keep them out
+/// of a calendar written to stdout.
+struct Progress {
+    total: usize,
+    done: usize,
+    enabled: bool,
+}
+
+impl Progress {
+    const fn new(total: usize, enabled: bool) -> Self {
+        Self {
+            total,
+            done: 0,
+            enabled,
+        }
+    }
+
+    fn tick(&mut self, postal_code: NorwegianPostalCode) {
+        self.done += 1;
+        if self.enabled {
+            let width = self.total.to_string().len();
+            eprintln!("[{:>width$}/{}] {postal_code}", self.done, self.total);
+        }
+    }
+}
+
+async fn fetch_all<T: DeserializeOwned>(
+    endpoint: &DeliveryDays,
+    postal_codes: &[NorwegianPostalCode],
+    concurrency: NonZeroUsize,
+    progress: &mut Progress,
+) -> Result<Vec<(NorwegianPostalCode, T)>, Box<dyn Error>> {
+    let mut responses = Vec::with_capacity(postal_codes.len());
+    let mut stream = pin!(endpoint.get_stream(postal_codes, concurrency.get()));
+    loop {
+        let next = stream.next().await;
+        let Some(response) = next else { break };
+        let (postal_code, response) = response?;
+        progress.tick(postal_code);
+        responses.push((postal_code, response));
+    }
+    Ok(responses)
+}
+
 const VERSION: &str = git_version!(
     prefix = "git:",
     cargo_prefix = "cargo:",
     fallback = "unknown"
 );
 
-fn postal_code_parser(value: &str) -> Result<NorwegianPostalCode, String> {
-    NorwegianPostalCode::try_from(value).map_err(|err| err.to_string())
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    humantime::parse_duration(value).map_err(|err| err.to_string())
+}
+
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (number, unit) = value
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or((value, ""), |i| value.split_at(i));
+    let multiplier: u64 = match unit {
+        "" => 1,
+        "K" | "k" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("Unknown unit {unit:?}, expected K, M or G")),
+    };
+    number
+        .parse::<u64>()
+        .map_err(|err| err.to_string())?
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Size is too large: {value}"))
+}
+
+fn parse_mode(value: &str) -> Result<u32, String> {
+    match u32::from_str_radix(value, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("Expected an octal mode like 0644, got {value:?}")),
+    }
+}
+
+fn parse_hex_color(value: &str) -> Result<String, String> {
+    match value.strip_prefix('#') {
+        Some(hex) if matches!(hex.len(), 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+            Ok(value.to_uppercase())
+        }
+        _ => Err(format!("Expected a color like #1E90FF, got {value:?}")),
+    }
+}
+
+fn parse_line_length(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>().map_err(|err| err.to_string())? {
+        length if length < 10 => Err(format!("Line length must be at least 10, got {length}")),
+        length => Ok(length),
+    }
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Expected a weekday like mon or monday, got {value:?}"))
 }
 
 fn parse_api_key(value: &str) -> Result<ApiKey, String> {
@@ -44,26 +171,372 @@ fn parse_api_uid(value: &str) -> Result<ApiUid, String> {
     ApiUid::try_from(value).map_err(|err| format!("{err:?}"))
 }
 
+#[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+struct ApiArgs {
+    #[arg(long, env = "POSTGANG_API_UID", value_parser = parse_api_uid, hide_env_values = true)]
+    api_uid: Option<ApiUid>,
+    #[arg(long, env = "POSTGANG_API_KEY", value_parser = parse_api_key, hide_env_values = true)]
+    api_key: Option<ApiKey>,
+    /// Cache responses in this directory and use conditional requests, see `postgang paths`
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+    /// Don't cache responses
+    #[arg(long, conflicts_with = "cache_dir")]
+    no_cache: bool,
+    /// Only connect to the API over IPv4
+    #[arg(long, conflicts_with = "ipv6")]
+    ipv4: bool,
+    /// Only connect to the API over IPv6
+    #[arg(long)]
+    ipv6: bool,
+    /// How long idle connections are kept open for reuse, e.g. "90s"
+    #[arg(long, value_parser = parse_duration)]
+    pool_idle_timeout: Option<Duration>,
+    /// Maximum number of idle connections kept per host
+    #[arg(long)]
+    pool_max_idle_per_host: Option<usize>,
+    /// How long connecting to the API may take, e.g. "5s"
+    #[arg(long, value_parser = parse_duration)]
+    connect_timeout: Option<Duration>,
+    /// How long each request may take in total, including reading the response, e.g. "1m"
+    #[arg(long, value_parser = parse_duration)]
+    timeout: Option<Duration>,
+    /// Connect to ADDRESS for HOST:PORT instead of looking it up, like curl's --resolve
+    #[arg(long, value_name = "HOST:PORT:ADDRESS")]
+    resolve: Vec<Resolve>,
+    /// Number of times a failed request is retried
+    #[arg(long, default_value_t = RetryPolicy::default().retries)]
+    retries: u32,
+    /// Delay before the first retry, doubled for each further retry
+    #[arg(long, default_value = "1s", value_parser = parse_duration)]
+    retry_delay: Duration,
+    /// Wait the full delay between retries instead of a random part of it
+    #[arg(long)]
+    no_retry_jitter: bool,
+    /// How long 404 responses are cached before asking the API again, 0s to not cache them
+    #[arg(long, default_value = "10m", value_parser = parse_duration)]
+    error_cache_ttl: Duration,
+    /// Record the API responses to this JSON file, to replay them later
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+    /// Replay responses recorded with --record instead of asking the API
+    #[arg(long, value_name = "FILE", conflicts_with = "record")]
+    replay: Option<PathBuf>,
+}
+
+/// The credentials given on the command line, or else in `config`.
+///
+/// With the `keyring` feature, a key stored by `postgang setup` is used
+/// when no key is given.
+fn credentials(
+    api_uid: Option<ApiUid>,
+    api_key: Option<ApiKey>,
+    locale: Locale,
+    config: &Config,
+) -> Result<(ApiUid, ApiKey), String> {
+    let api_uid = match (api_uid, &config.api_uid) {
+        (Some(api_uid), _) => api_uid,
+        (None, Some(api_uid)) => parse_api_uid(&api_uid.0)?,
+        (None, None) => return Err(locale.required("--api-uid")),
+    };
+    #[cfg(feature = "keyring")]
+    let stored = || api_uid.as_str().and_then(postgang::setup::load_api_key);
+    #[cfg(not(feature = "keyring"))]
+    let stored = || None::<String>;
+    let api_key = match (api_key, &config.api_key) {
+        (Some(api_key), _) => api_key,
+        (None, Some(api_key)) => parse_api_key(&api_key.0)?,
+        (None, None) => match stored() {
+            Some(api_key) => parse_api_key(&api_key)?,
+            None => return Err(locale.required("--api-key")),
+        },
+    };
+    Ok((api_uid, api_key))
+}
+
+impl ApiArgs {
+    /// Create the API provider, taking missing credentials from `config`.
+    ///
+    /// No credentials are needed when replaying.
+    fn delivery_days(
+        self,
+        locale: Locale,
+        config: &Config,
+        cache_dir: Option<PathBuf>,
+        max_size: u64,
+    ) -> Result<DeliveryDays, String> {
+        if let Some(path) = &self.replay {
+            return Ok(DeliveryDays::replay(Fixtures::load(path, max_size)?));
+        }
+        let record = self
+            .record
+            .as_deref()
+            .map(|path| Fixtures::open(path, max_size))
+            .transpose()?;
+        let (api_uid, api_key) = credentials(self.api_uid, self.api_key, locale, config)?;
+        let api = DeliveryDays::api(
+            api_key,
+            api_uid,
+            ApiOptions {
+                cache: if self.no_cache {
+                    None
+                } else {
+                    self.cache_dir.or(cache_dir)
+                }
+                .map(ResponseCache::new),
+                ip_family: match (self.ipv4, self.ipv6) {
+                    (true, _) => Some(IpFamily::V4),
+                    (_, true) => Some(IpFamily::V6),
+                    _ => None,
+                },
+                pool_idle_timeout: self.pool_idle_timeout,
+                connect_timeout: self.connect_timeout,
+                timeout: self.timeout,
+                pool_max_idle_per_host: self.pool_max_idle_per_host,
+                resolve: self.resolve,
+                retry: RetryPolicy {
+                    retries: self.retries,
+                    base_delay: self.retry_delay,
+                    jitter: !self.no_retry_jitter,
+                    ..RetryPolicy::default()
+                },
+                error_ttl: self.error_cache_ttl,
+            },
+        );
+        Ok(match record {
+            Some(fixtures) => api.with_recording(fixtures),
+            None => api,
+        })
+    }
+}
+
 #[derive(ClapParser, Debug)]
 enum Commands {
     /// Get delivery dates from Bring API
-    Api {
-        #[arg(long, env = "POSTGANG_API_UID", value_parser = parse_api_uid, hide_env_values = true)]
-        api_uid: ApiUid,
-        #[arg(long, env = "POSTGANG_API_KEY", value_parser = parse_api_key, hide_env_values = true)]
-        api_key: ApiKey,
-    },
-    /// Get delivery dates from JSON file
+    Api(Box<ApiArgs>),
+    /// Get delivery dates from a JSON file with an API response or an array of dates
     File {
         /// File path, read from stdin of omitted
         input: Option<PathBuf>,
+        /// Only check the input against the JSON Schema and report errors
+        #[arg(long)]
+        validate_only: bool,
+        /// Print the JSON Schema for the input and exit
+        #[arg(long, conflicts_with_all = ["input", "validate_only"])]
+        print_schema: bool,
+    },
+    /// Read delivery dates back from an iCalendar file, e.g. to convert it with --format json
+    Convert {
+        /// File path, read from stdin if omitted
+        input: Option<PathBuf>,
+    },
+    /// Record the events of a previously published iCalendar file in the history
+    Import {
+        /// File path, read from stdin if omitted
+        input: Option<PathBuf>,
+    },
+    /// Get delivery dates recorded in the history
+    History {
+        /// Only include dates on or after this date
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Only include dates on or before this date
+        #[arg(long)]
+        to: Option<NaiveDate>,
+        #[command(subcommand)]
+        command: Option<HistoryCommands>,
+    },
+    /// Show the next delivery recorded in the history
+    Next {
+        /// Print the number of days until the next delivery, -1 if unknown
+        #[arg(long)]
+        days_until: bool,
+    },
+    /// Create, read or change the configuration file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Print files for running postgang with these options from a service manager
+    Generate {
+        #[command(subcommand)]
+        target: GenerateCommands,
+    },
+    /// Check the configuration, credentials, network access and file permissions
+    Doctor {
+        #[arg(long, env = "POSTGANG_API_UID", value_parser = parse_api_uid, hide_env_values = true)]
+        api_uid: Option<ApiUid>,
+        #[arg(long, env = "POSTGANG_API_KEY", value_parser = parse_api_key, hide_env_values = true)]
+        api_key: Option<ApiKey>,
+    },
+    /// Set up a configuration file by answering a few questions
+    Setup,
+    /// Look up the post place of postal codes in Bring API, to check that they exist
+    Lookup {
+        /// Postal codes or aliases, --code if omitted
+        #[arg(value_name = "CODE")]
+        codes: Vec<String>,
+        #[arg(long, env = "POSTGANG_API_UID", value_parser = parse_api_uid, hide_env_values = true)]
+        api_uid: Option<ApiUid>,
+        #[arg(long, env = "POSTGANG_API_KEY", value_parser = parse_api_key, hide_env_values = true)]
+        api_key: Option<ApiKey>,
+    },
+    /// Print where the config, cached responses, the history and the lock file are kept
+    Paths,
+    /// Print the options in effect and whether each comes from the command line, the environment, the config file or a default
+    Explain(Box<ApiArgs>),
+    /// Exit with an error if the delivery dates of --code were not fetched recently, or the service is failing
+    Healthcheck {
+        /// Longest time since the last successful run
+        #[arg(long, default_value = "12h", value_parser = parse_duration)]
+        max_age: Duration,
+    },
+    /// Show delivery cadence statistics from the history
+    Stats {
+        /// Only include dates on or after this date
+        #[arg(long)]
+        from: Option<NaiveDate>,
+        /// Only include dates on or before this date
+        #[arg(long)]
+        to: Option<NaiveDate>,
+    },
+    /// Browse the delivery dates in the history month by month
+    #[cfg(feature = "tui")]
+    Tui,
+    /// Install, start or stop postgang as a Windows service
+    #[cfg(all(windows, feature = "windows-service"))]
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommands,
+    },
+}
+
+#[derive(ClapParser, Debug)]
+enum HistoryCommands {
+    /// Export recorded delivery dates with first and last seen timestamps
+    Export {
+        /// Export format
+        #[arg(value_enum, long, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+    },
+}
+
+#[derive(ClapParser, Debug)]
+enum ConfigCommands {
+    /// Write a commented configuration file to start from
+    Init {
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print a setting, e.g. telegram.chat-id
+    Get { key: String },
+    /// Change a setting, keeping the rest of the file as it is
+    Set { key: String, value: String },
+    /// Print the configuration file with secrets masked
+    Show,
+}
+
+#[cfg(all(windows, feature = "windows-service"))]
+#[derive(ClapParser, Debug)]
+enum ServiceCommands {
+    /// Register the service, e.g. `postgang --code 7800 service install -- api`
+    Install {
+        /// When the service runs postgang: a duration, times like 05:00,17:00 or a cron expression
+        #[arg(long, alias = "interval", default_value = "6h")]
+        schedule: postgang::schedule::Schedule,
+        /// Subcommand and options run by the service, `api` if omitted
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+    /// Stop and remove the service
+    Uninstall,
+    /// Start the installed service
+    Start,
+    /// Stop the running service
+    Stop,
+    /// Run as the service, started by the service control manager
+    #[command(hide = true)]
+    Run {
+        #[arg(long)]
+        schedule: postgang::schedule::Schedule,
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+}
+
+#[derive(ClapParser, Debug)]
+enum GenerateCommands {
+    /// Print a systemd service and timer, e.g. `postgang --code 7800 generate systemd -- api`
+    Systemd {
+        /// How often the timer runs the service
+        #[arg(long, default_value = "6h", value_parser = parse_duration)]
+        interval: Duration,
+        /// Print user units, run with the XDG directories of the user
+        #[arg(long)]
+        user: bool,
+        /// Subcommand and options run by the service, `api` if omitted
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+    /// Print a crontab line, e.g. `postgang --code 7800 generate cron -- api`
+    Cron {
+        /// How often cron runs postgang, a part of an hour or a day
+        #[arg(long, default_value = "6h", value_parser = parse_duration)]
+        interval: Duration,
+        /// Subcommand and options run by cron, `api` if omitted
+        #[arg(last = true)]
+        command: Vec<String>,
+    },
+    /// Print a macOS `LaunchAgent` plist, e.g. `postgang --code 7800 generate launchd -- api`
+    Launchd {
+        /// How often launchd runs postgang
+        #[arg(long, default_value = "6h", value_parser = parse_duration)]
+        interval: Duration,
+        /// Subcommand and options run by launchd, `api` if omitted
+        #[arg(last = true)]
+        command: Vec<String>,
     },
 }
 
+#[derive(Debug, Clone, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
 #[derive(Debug, Clone, ValueEnum)]
 enum OutputFormat {
     Ical,
+    Html,
+    Jscalendar,
     Json,
+    Remind,
+    Table,
+    Taskwarrior,
+    Waybar,
+    Yaml,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum LogTarget {
+    Stderr,
+    Journald,
+    Syslog,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum ErrorFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(ClapParser, Debug)]
@@ -71,63 +544,2368 @@ enum OutputFormat {
 struct Cli {
     #[command(subcommand)]
     command: Commands,
-    #[arg(long, value_parser = postal_code_parser)]
-    /// Postal code
-    code: NorwegianPostalCode,
+    #[command(flatten)]
+    options: Options,
+}
+
+#[derive(Args, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+struct Options {
+    /// Read settings from this TOML file, see `postgang paths`
+    #[arg(long, env = "POSTGANG_CONFIG")]
+    config: Option<PathBuf>,
+    #[arg(long = "code", value_name = "CODE", value_delimiter = ',')]
+    /// Postal code or alias, repeat or separate with commas to fetch several
+    codes: Vec<String>,
+    /// Postal codes of `codes`, resolved with the aliases of the configuration
+    #[arg(skip)]
+    code: Vec<NorwegianPostalCode>,
+    /// Aliases of the configuration by postal code
+    #[arg(skip)]
+    names: BTreeMap<NorwegianPostalCode, String>,
+    /// Codes and aliases given for the same postal code, which is fetched once
+    #[arg(skip)]
+    duplicates: BTreeMap<NorwegianPostalCode, Vec<String>>,
+    /// Templates of the notification messages from the configuration
+    #[arg(skip)]
+    templates: notify::Templates,
+    /// Postal codes with their own settings or calendar in the configuration
+    #[arg(skip)]
+    calendars: BTreeMap<NorwegianPostalCode, CodeCalendar>,
+    /// Maximum number of postal codes fetched in parallel
+    #[arg(long, default_value_t = NonZeroUsize::new(4).unwrap())]
+    concurrency: NonZeroUsize,
+    /// Don't print status lines to a terminal when fetching several postal codes
+    #[arg(long)]
+    no_progress: bool,
     #[arg(long)]
     /// File path, print to stdout if omitted
     output: Option<PathBuf>,
+    /// Write a calendar per postal code to <CODE>.ics in this directory
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    output_dir: Option<PathBuf>,
+    /// Also write all postal codes to all.ics in --output-dir
+    #[arg(long, requires = "output_dir")]
+    merged: bool,
+    /// Also write each calendar month to its own file, e.g. post-2024-09.ics
+    #[arg(long)]
+    split_by_month: bool,
+    /// Permissions of written files, in octal like 0644
+    #[arg(long, value_name = "MODE", value_parser = parse_mode)]
+    output_mode: Option<u32>,
+    /// Group of written files, by name or id
+    #[arg(long, value_name = "GROUP")]
+    output_group: Option<String>,
+    /// Record every fetched delivery date in this history file, see `postgang paths`
+    #[arg(long)]
+    history: Option<PathBuf>,
+    /// Don't read or record the history
+    #[arg(long, conflicts_with = "history")]
+    no_history: bool,
+    /// Add recorded future dates beyond the fetched horizon from the history
+    #[arg(long, conflicts_with = "no_history")]
+    extend_from_history: bool,
+    /// Notify about changed dates: webhook:URL, push:URL, email:ADDRESS, exec:COMMAND, slack:URL, discord:URL or desktop
+    #[arg(long, conflicts_with = "no_history")]
+    notify: Vec<Notifier>,
+    /// Warn when the last delivery date is fewer than this many days ahead
+    #[arg(long, value_name = "DAYS", default_value_t = 7)]
+    min_horizon: i64,
+    /// Keep the previous output when the source returns no delivery dates
+    #[arg(long)]
+    keep_on_empty: bool,
+    /// Exit with this code when the source returns no delivery dates for a postal code
+    #[arg(long, value_name = "CODE")]
+    empty_exit_code: Option<u8>,
+    /// Run this command with `sh -c` when a written file changed
+    #[arg(long, value_name = "COMMAND")]
+    on_change: Option<String>,
+    /// Also notify when mail arrives today or tomorrow
+    #[arg(long)]
+    remind: bool,
+    /// Publish dates no longer returned by the source as cancelled events
+    #[arg(long, conflicts_with = "no_history")]
+    cancel_removed: bool,
+    /// Leave out delivery dates on this weekday, e.g. sat, repeat to exclude several
+    #[arg(long, value_name = "WEEKDAY", value_parser = parse_weekday)]
+    exclude_weekday: Vec<Weekday>,
+    /// Only emit the first N upcoming delivery dates per postal code
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+    /// Only log errors
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Log more, repeat for debug and trace messages, overrides `RUST_LOG`
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Log API requests and responses with headers and timing, credentials masked
+    #[arg(long)]
+    trace_http: bool,
+    /// How to report a failure, json prints kind, HTTP status, postal code, retryable and trace id to stderr
+    #[arg(value_enum, long, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+    /// Where to log, journald and syslog need postgang built with the feature of the same name
+    #[arg(value_enum, long, default_value_t = LogTarget::Stderr, conflicts_with = "log_file")]
+    log_target: LogTarget,
+    /// Write the log to this file instead of stderr, at info level unless `RUST_LOG` is set
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+    /// Rotate the log file before it grows past this size, e.g. 512K or 10M
+    #[arg(long, default_value = "10M", value_parser = parse_size, requires = "log_file")]
+    log_max_size: u64,
+    /// Largest API response or input file read, e.g. 512K or 1M
+    #[arg(long, default_value = "1M", value_parser = parse_size)]
+    max_response_size: u64,
+    /// Rotate the log file when it gets older than this, e.g. "1day"
+    #[arg(long, value_parser = parse_duration, requires = "log_file")]
+    log_max_age: Option<Duration>,
+    /// Number of rotated log files to keep
+    #[arg(long, default_value_t = 5, requires = "log_file")]
+    log_keep: usize,
     /// Output format
     #[arg(value_enum, long, default_value_t = OutputFormat::Ical)]
     format: OutputFormat,
+    /// Link calendar events to this page instead of Posten's page about mail delivery
+    #[arg(long, value_name = "URL", conflicts_with = "no_url")]
+    url: Option<Url>,
+    /// Don't link calendar events to a page
+    #[arg(long)]
+    no_url: bool,
+    /// Emit timed calendar events in this local time window, e.g. 09:00-16:00
+    #[arg(long, value_name = "HH:MM-HH:MM")]
+    delivery_window: Option<DeliveryWindow>,
+    /// Add an alarm this long before events, e.g. 2h, or at a time of day, e.g. 1d@20:00 the evening before
+    #[arg(long, value_name = "TRIGGER")]
+    alarm: Option<AlarmTrigger>,
+    /// Send the alarm as email to this address instead of displaying it
+    #[arg(long, value_name = "ADDRESS", requires = "alarm")]
+    alarm_email: Option<String>,
+    /// Add the properties Outlook needs to show events as free all-day events
+    #[arg(long)]
+    outlook: bool,
+    /// Color of the calendar in Apple Calendar, e.g. #1E90FF
+    #[arg(long, value_name = "HEX", value_parser = parse_hex_color)]
+    calendar_color: Option<String>,
+    /// Fold calendar lines longer than this many octets
+    #[arg(long, value_name = "OCTETS", default_value_t = calendar::MAX_LINE_LENGTH, value_parser = parse_line_length)]
+    line_length: usize,
+    /// End calendar lines with LF instead of CRLF
+    #[arg(long)]
+    lf: bool,
+    /// Start the calendar with a UTF-8 byte order mark, for importers that need it
+    #[arg(long)]
+    bom: bool,
+    /// Add the week number to the description of calendar events
+    #[arg(long)]
+    week_number: bool,
+    /// Language of messages and the next command, nb or en, defaults to nb with a Norwegian LANG and en otherwise
+    #[arg(long, value_name = "LOCALE")]
+    locale: Option<Locale>,
+    /// Say "i dag" and "i morgen" for the next days in the next command and the waybar and html formats
+    #[arg(long)]
+    relative_days: bool,
+    /// Make browsers reload the html format this often, e.g. "1h"
+    #[arg(long, value_parser = parse_duration)]
+    html_refresh: Option<Duration>,
+    /// Color the table format, auto colors when writing to a terminal
+    #[arg(value_enum, long, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
 }
 
-async fn try_main() -> Result<(), Box<dyn Error>> {
-    let cli = Cli::parse();
-    log::debug!("Got CLI args: {:?}", cli);
-    let endpoint = match cli.command {
-        Commands::Api { api_key, api_uid } => DeliveryDays::api(api_key, api_uid),
-        Commands::File { input } => DeliveryDays::file(input),
+impl Options {
+    /// The postal codes with a calendar file of their own, with its settings
+    /// and path.
+    fn own_calendars(&self) -> impl Iterator<Item = (NorwegianPostalCode, &CodeCalendar, &Path)> {
+        self.calendars.iter().filter_map(|(postal_code, settings)| {
+            let output = settings.output.as_deref()?;
+            Some((*postal_code, settings, output))
+        })
+    }
+
+    fn has_own_calendar(&self, postal_code: NorwegianPostalCode) -> bool {
+        self.calendars
+            .get(&postal_code)
+            .is_some_and(|settings| settings.output.is_some())
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Settings of the events of a single postal code, and of its own calendar
+/// if it has an `output`, from the configuration.
+struct CodeCalendar {
+    output: Option<PathBuf>,
+    name: Option<String>,
+    locale: Option<Locale>,
+    alarm: Option<AlarmTrigger>,
+    summary: Option<String>,
+}
+
+impl CodeCalendar {
+    fn new(key: &str, calendar: &config::CalendarConfig) -> Result<Self, String> {
+        let parse_error = |field| move |err| format!("calendars.{key}.{field}: {err}");
+        Ok(Self {
+            output: calendar.output.clone(),
+            name: calendar.name.clone(),
+            locale: calendar
+                .locale
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(parse_error("locale"))?,
+            alarm: calendar
+                .alarm
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(parse_error("alarm"))?,
+            summary: calendar.summary.clone(),
+        })
+    }
+}
+
+/// Where delivery dates are read from.
+enum Source {
+    Endpoint(Box<DeliveryDays>),
+    Calendar(Option<PathBuf>),
+    History {
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    },
+}
+
+fn parse_responses(
+    responses: &[(NorwegianPostalCode, serde_json::Value)],
+) -> Result<Vec<(NorwegianPostalCode, ApiResponse)>, serde_json::Error> {
+    responses
+        .iter()
+        .map(|(postal_code, response)| {
+            Ok((*postal_code, serde_json::from_value(response.clone())?))
+        })
+        .collect()
+}
+
+impl Source {
+    fn new(
+        command: Commands,
+        options: &Options,
+        config: &Config,
+        paths: &Paths,
+    ) -> Result<Self, Box<dyn Error>> {
+        let postal_codes = &options.code;
+        Ok(match command {
+            Commands::Api(args) => Self::Endpoint(Box::new(
+                args.delivery_days(
+                    locale(options),
+                    config,
+                    paths.cache_dir.clone(),
+                    options.max_response_size,
+                )?
+                .with_max_size(options.max_response_size),
+            )),
+            Commands::File { input, .. } => {
+                if postal_codes.len() > 1 {
+                    return Err(locale(options)
+                        .message(Message::OnePostalCodeWithFile)
+                        .into());
+                }
+                Self::Endpoint(Box::new(
+                    DeliveryDays::file(input).with_max_size(options.max_response_size),
+                ))
+            }
+            Commands::Convert { input } => Self::Calendar(input),
+            Commands::Config { .. }
+            | Commands::Doctor { .. }
+            | Commands::Generate { .. }
+            | Commands::Healthcheck { .. }
+            | Commands::Import { .. }
+            | Commands::Lookup { .. }
+            | Commands::Explain(_)
+            | Commands::Paths
+            | Commands::Setup => {
+                unreachable!("handled in try_main")
+            }
+            #[cfg(feature = "tui")]
+            Commands::Tui => unreachable!("handled in try_main"),
+            #[cfg(all(windows, feature = "windows-service"))]
+            Commands::Service { .. } => unreachable!("handled in try_main"),
+            Commands::History { from, to, .. } | Commands::Stats { from, to } => {
+                Self::History { from, to }
+            }
+            Commands::Next { .. } => Self::History {
+                from: Some(Local::now().date_naive()),
+                to: None,
+            },
+        })
+    }
+}
+
+fn extend_from_history(
+    history: &History,
+    responses: Vec<(NorwegianPostalCode, serde_json::Value)>,
+) -> Result<Vec<(NorwegianPostalCode, serde_json::Value)>, serde_json::Error> {
+    let today = Local::now().date_naive();
+    responses
+        .into_iter()
+        .map(|(postal_code, mut response)| {
+            let fresh: ApiResponse = serde_json::from_value(response.clone())?;
+            response["delivery_dates"] = serde_json::to_value(history.merge_future(
+                postal_code,
+                &fresh.delivery_dates,
+                today,
+            ))?;
+            Ok((postal_code, response))
+        })
+        .collect()
+}
+
+/// How collected responses are presented.
+enum View {
+    /// Render with `--format`.
+    Format,
+    Next {
+        days_until: bool,
+    },
+    Stats,
+}
+
+impl View {
+    const fn new(command: &Commands) -> Self {
+        match command {
+            Commands::Next { days_until } => Self::Next {
+                days_until: *days_until,
+            },
+            Commands::Stats { .. } => Self::Stats,
+            _ => Self::Format,
+        }
+    }
+}
+
+fn map_dates(
+    responses: Vec<(NorwegianPostalCode, serde_json::Value)>,
+    f: impl Fn(Vec<NaiveDate>) -> Vec<NaiveDate>,
+) -> Result<Vec<(NorwegianPostalCode, serde_json::Value)>, serde_json::Error> {
+    responses
+        .into_iter()
+        .map(|(postal_code, mut response)| {
+            let ApiResponse { delivery_dates } = serde_json::from_value(response.clone())?;
+            response["delivery_dates"] = serde_json::to_value(f(delivery_dates))?;
+            Ok((postal_code, response))
+        })
+        .collect()
+}
+
+/// Responses collected from a [`Source`], along with what changed.
+#[derive(Default)]
+struct Collected {
+    responses: Vec<(NorwegianPostalCode, serde_json::Value)>,
+    changes: Vec<Changes>,
+    cancelled: Vec<DeliveryDate>,
+    /// Postal codes the source returned no delivery dates for
+    empty: Vec<NorwegianPostalCode>,
+    /// Time of the API server, used for `DTSTAMP`
+    created: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+/// The source returned no delivery dates for some postal codes.
+struct EmptyResponse(Vec<NorwegianPostalCode>);
+
+impl core::fmt::Display for EmptyResponse {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let codes: Vec<_> = self.0.iter().map(ToString::to_string).collect();
+        write!(f, "No delivery dates for {}", codes.join(", "))
+    }
+}
+
+impl Error for EmptyResponse {}
+
+#[cfg_attr(feature = "otel", tracing::instrument(skip_all, err))]
+async fn collect_responses(
+    source: Source,
+    options: &Options,
+    history: Option<&mut History>,
+) -> Result<Collected, Box<dyn Error>> {
+    let mut collected = Collected::default();
+    collected.responses = match source {
+        Source::Endpoint(endpoint) => {
+            let mut progress = Progress::new(
+                options.code.len(),
+                !options.no_progress && options.code.len() > 1 && std::io::stderr().is_terminal(),
+            );
+            let responses =
+                fetch_all(&endpoint, &options.code, options.concurrency, &mut progress).await?;
+            log::debug!("Got: {responses:?}");
+            collected.created = endpoint.server_time();
+            warn_about_holidays(&responses)?;
+            let today = Local::now().date_naive();
+            for (postal_code, response) in parse_responses(&responses)? {
+                match stats::horizon(&response.delivery_dates, today) {
+                    None => {
+                        log::warn!("{postal_code}: No delivery dates returned");
+                        collected.empty.push(postal_code);
+                    }
+                    Some(days) if days < options.min_horizon => log::warn!(
+                        "{postal_code}: Delivery dates only {days} days ahead, expected at least {}",
+                        options.min_horizon
+                    ),
+                    Some(_) => {}
+                }
+            }
+            if let Some(history) = history {
+                let now = Utc::now();
+                for (postal_code, response) in parse_responses(&responses)? {
+                    history.set_refreshed(postal_code, now);
+                    if options.keep_on_empty && collected.empty.contains(&postal_code) {
+                        continue;
+                    }
+                    if options.cancel_removed {
+                        collected.cancelled.extend(
+                            history
+                                .cancelled(postal_code, &response.delivery_dates, today)
+                                .into_iter()
+                                .map(|date| DeliveryDate::new(postal_code, date)),
+                        );
+                    }
+                    collected.changes.extend(history.record(
+                        postal_code,
+                        &response.delivery_dates,
+                        now,
+                    ));
+                }
+                history.save()?;
+                if options.extend_from_history {
+                    collected.responses = extend_from_history(history, responses)?;
+                    return Ok(collected);
+                }
+            }
+            responses
+        }
+        Source::Calendar(input) => {
+            read_calendar(input.as_deref(), &options.code, options.max_response_size)?
+        }
+        Source::History { from, to } => {
+            let history = history.ok_or_else(|| locale(options).required("--history"))?;
+            options
+                .code
+                .iter()
+                .map(|&postal_code| {
+                    let delivery_dates = history
+                        .dates_between(postal_code, from, to)
+                        .map(|(date, _)| date)
+                        .collect();
+                    Ok((
+                        postal_code,
+                        serde_json::to_value(ApiResponse { delivery_dates })?,
+                    ))
+                })
+                .collect::<Result<_, serde_json::Error>>()?
+        }
     };
-    let output = match cli.format {
-        OutputFormat::Ical => {
-            let response: ApiResponse = endpoint.get(cli.code).await?;
-            log::debug!("Got: {:?}", response);
-            let delivery_dates: Vec<DeliveryDate> = ApiResponseWithPostalCode {
+    Ok(collected)
+}
+
+/// Read the delivery dates of the events in an iCalendar file.
+///
+/// Only `postal_codes` are kept when given, and a single one is used for
+/// events without a postal code.
+fn read_calendar(
+    input: Option<&Path>,
+    postal_codes: &[NorwegianPostalCode],
+    max_size: u64,
+) -> Result<Vec<(NorwegianPostalCode, serde_json::Value)>, Box<dyn Error>> {
+    let events = ics::parse(&mailbox_delivery_dates::read_text(input, max_size)?)?;
+    let default = match postal_codes {
+        [postal_code] => Some(*postal_code),
+        _ => None,
+    };
+    Ok(ics::delivery_dates(&events, default)?
+        .into_iter()
+        .filter(|(postal_code, _)| postal_codes.is_empty() || postal_codes.contains(postal_code))
+        .map(|(postal_code, delivery_dates)| {
+            Ok((
+                postal_code,
+                serde_json::to_value(ApiResponse { delivery_dates })?,
+            ))
+        })
+        .collect::<Result<_, serde_json::Error>>()?)
+}
+
+/// Record the events in an iCalendar file in `history`.
+fn import_calendar(
+    input: Option<&Path>,
+    options: &Options,
+    history: Option<&mut History>,
+) -> Result<(), Box<dyn Error>> {
+    let history = history.ok_or_else(|| locale(options).required("--history"))?;
+    let events = ics::parse(&mailbox_delivery_dates::read_text(
+        input,
+        options.max_response_size,
+    )?)?;
+    let default = match options.code.as_slice() {
+        [postal_code] => Some(*postal_code),
+        _ => None,
+    };
+    let now = Utc::now();
+    for (postal_code, dates) in ics::delivery_dates(&events, default)? {
+        log::info!("Importing {} delivery dates for {postal_code}", dates.len());
+        let _ = history.record(postal_code, &dates, now);
+    }
+    for event in &events {
+        if let (Some(uid), Some(sequence)) = (&event.uid, event.sequence) {
+            history.import_sequence(uid, sequence);
+        }
+    }
+    Ok(history.save()?)
+}
+
+/// Warn about delivery dates on public holidays, which are likely errors.
+fn warn_about_holidays(
+    responses: &[(NorwegianPostalCode, serde_json::Value)],
+) -> Result<(), serde_json::Error> {
+    for delivery_date in delivery_dates(responses)? {
+        if let Some(holiday) = holidays::holiday(delivery_date.date) {
+            log::warn!(
+                "{}: Delivery on {holiday} {}",
+                delivery_date.postal_code,
+                delivery_date.date
+            );
+        }
+    }
+    Ok(())
+}
+
+fn delivery_dates(
+    responses: &[(NorwegianPostalCode, serde_json::Value)],
+) -> Result<Vec<DeliveryDate>, serde_json::Error> {
+    Ok(parse_responses(responses)?
+        .into_iter()
+        .flat_map(|(postal_code, response)| {
+            Vec::from(ApiResponseWithPostalCode {
                 response,
-                postal_code: cli.code,
+                postal_code,
+            })
+        })
+        .collect())
+}
+
+fn render_next(
+    days_until: bool,
+    options: &Options,
+    collected: &Collected,
+) -> Result<String, serde_json::Error> {
+    let today = Local::now().date_naive();
+    let next = mailbox_delivery_dates::next_delivery(&delivery_dates(&collected.responses)?, today);
+    Ok(match (days_until, next) {
+        (true, Some(next)) => format!("{}\n", (next.date - today).num_days()),
+        (true, None) => "-1\n".to_string(),
+        (false, Some(next)) => {
+            let summary = if options.relative_days {
+                locale(options).relative_summary(next, today)
+            } else {
+                locale(options).summary(next)
+            };
+            match options.names.get(&next.postal_code) {
+                Some(name) => format!("{}\n", calendar::rename(summary, next, name)),
+                None => format!("{summary}\n"),
+            }
+        }
+        (false, None) => String::new(),
+    })
+}
+
+/// A reminder if the next delivery date is today or tomorrow.
+fn reminder_for(collected: &Collected) -> Result<Option<Reminder>, serde_json::Error> {
+    let today = Local::now().date_naive();
+    Ok(
+        mailbox_delivery_dates::next_delivery(&delivery_dates(&collected.responses)?, today)
+            .filter(|next| (next.date - today).num_days() <= 1)
+            .map(|delivery_date| Reminder {
+                delivery_date,
+                today,
+            }),
+    )
+}
+
+/// The next few delivery dates, shown by some notifiers.
+fn upcoming_dates(collected: &Collected) -> Result<Vec<DeliveryDate>, serde_json::Error> {
+    const UPCOMING: usize = 5;
+    let today = Local::now().date_naive();
+    let mut dates: Vec<_> = delivery_dates(&collected.responses)?
+        .into_iter()
+        .filter(|delivery_date| delivery_date.date >= today)
+        .collect();
+    dates.sort_unstable_by_key(|delivery_date| (delivery_date.date, delivery_date.postal_code));
+    dates.truncate(UPCOMING);
+    Ok(dates)
+}
+
+fn render_stats(format: &OutputFormat, collected: &Collected) -> Result<String, serde_json::Error> {
+    let stats: Vec<_> = parse_responses(&collected.responses)?
+        .into_iter()
+        .map(|(postal_code, response)| Stats::new(postal_code, &response.delivery_dates))
+        .collect();
+    Ok(match format {
+        OutputFormat::Json => serde_json::to_string(&stats)?,
+        _ => stats
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    })
+}
+
+#[cfg_attr(
+    feature = "otel",
+    tracing::instrument(skip_all, fields(format = ?options.format), err)
+)]
+/// The alarm of the events with `trigger`, or `--alarm`.
+fn alarm(options: &Options, trigger: Option<AlarmTrigger>) -> Option<Alarm> {
+    trigger.or(options.alarm).map(|trigger| Alarm {
+        trigger,
+        action: options
+            .alarm_email
+            .clone()
+            .map_or(AlarmAction::Display, AlarmAction::Email),
+    })
+}
+
+/// The settings of the configuration for the events of each postal code.
+fn event_settings(options: &Options) -> BTreeMap<NorwegianPostalCode, EventSettings> {
+    options
+        .calendars
+        .iter()
+        .map(|(postal_code, settings)| {
+            let settings = EventSettings {
+                locale: settings.locale,
+                alarm: settings
+                    .alarm
+                    .and_then(|trigger| alarm(options, Some(trigger))),
+                summary_template: settings.summary.clone(),
+            };
+            (*postal_code, settings)
+        })
+        .collect()
+}
+
+/// Build an iCalendar, with the `settings` of a postal code with its own
+/// calendar.
+fn build_calendar(
+    options: &Options,
+    delivery_dates: Vec<DeliveryDate>,
+    cancelled: Vec<DeliveryDate>,
+    created: Option<DateTime<Utc>>,
+    settings: Option<&CodeCalendar>,
+    history: Option<&mut History>,
+) -> Result<Calendar, Box<dyn Error>> {
+    let mut cal = Calendar::new(delivery_dates, created)
+        .with_cancelled(cancelled)
+        .with_week_numbers(options.week_number)
+        .with_window(options.delivery_window)
+        .with_outlook(options.outlook)
+        .with_color(options.calendar_color.clone())
+        .with_line_length(options.line_length)
+        .with_bom(options.bom)
+        .with_names(options.names.clone())
+        .with_line_ending(if options.lf {
+            LineEnding::Lf
+        } else {
+            LineEnding::Crlf
+        })
+        .with_alarm(alarm(options, None))
+        .with_event_settings(event_settings(options));
+    if let Some(settings) = settings {
+        cal = cal
+            .with_calendar_name(settings.name.clone())
+            .with_summary_template(settings.summary.clone());
+        if let Some(locale) = settings.locale {
+            cal = cal.with_locale(locale);
+        }
+    }
+    if options.no_url {
+        cal = cal.with_url(None);
+    } else if let Some(url) = &options.url {
+        cal = cal.with_url(Some(url.to_string()));
+    }
+    if let Some(history) = history {
+        let sequences = history.sequences(&cal.fingerprints());
+        cal = cal.with_sequences(sequences);
+        history.save()?;
+    }
+    Ok(cal)
+}
+
+/// The responses for `--format json` and `--format yaml`.
+///
+/// A single postal code keeps the shape of the API response, several are
+/// keyed by postal code.
+fn json_output(responses: Vec<(NorwegianPostalCode, serde_json::Value)>) -> serde_json::Value {
+    let mut responses: serde_json::Map<_, _> = responses
+        .into_iter()
+        .map(|(postal_code, response)| (postal_code.to_string(), response))
+        .collect();
+    if responses.len() == 1 {
+        responses
+            .values_mut()
+            .next()
+            .map(serde_json::Value::take)
+            .unwrap_or_default()
+    } else {
+        responses.into()
+    }
+}
+
+/// Write the calendars of the postal codes with a file of their own, and
+/// return the calendar of the rest.
+fn render_ical(
+    options: &Options,
+    responses: &[(NorwegianPostalCode, serde_json::Value)],
+    cancelled: Vec<DeliveryDate>,
+    empty: &[NorwegianPostalCode],
+    created: Option<DateTime<Utc>>,
+    mut history: Option<&mut History>,
+) -> Result<Output, Box<dyn Error>> {
+    let (own, mut shared): (Vec<_>, Vec<_>) = delivery_dates(responses)?
+        .into_iter()
+        .partition(|date| options.has_own_calendar(date.postal_code));
+    // The merged calendar has the postal codes of their own calendars too
+    if options.merged {
+        shared = delivery_dates(responses)?;
+    }
+    if let Some(dir) = &options.output_dir {
+        std::fs::create_dir_all(dir).map_err(|err| io_error_to_string(&err, dir))?;
+    }
+    for (postal_code, settings, output) in options.own_calendars() {
+        let of_code = |dates: &[DeliveryDate]| -> Vec<_> {
+            let dates = dates.iter().filter(|date| date.postal_code == postal_code);
+            dates.copied().collect()
+        };
+        if !options.code.contains(&postal_code) && of_code(&own).is_empty() {
+            continue;
+        }
+        if options.keep_on_empty && empty.contains(&postal_code) {
+            log::warn!("Keeping {}", output.display());
+            continue;
+        }
+        let calendar = build_calendar(
+            options,
+            of_code(&own),
+            of_code(&cancelled),
+            created,
+            Some(settings),
+            history.as_deref_mut(),
+        )?;
+        write_output(Some(output), options, |writer| calendar.write_to(writer))?;
+        if options.split_by_month {
+            let dates = (of_code(&own), of_code(&cancelled));
+            write_months(
+                output,
+                options,
+                dates,
+                created,
+                Some(settings),
+                history.as_deref_mut(),
+            )?;
+        }
+    }
+    if shared.is_empty() && !own.is_empty() {
+        return Ok(Output::Text(String::new()));
+    }
+    let cancelled = cancelled
+        .into_iter()
+        .filter(|date| options.merged || !options.has_own_calendar(date.postal_code))
+        .collect::<Vec<_>>();
+    if let (true, Some(output)) = (options.split_by_month, &options.output) {
+        let dates = (shared.clone(), cancelled.clone());
+        write_months(
+            output,
+            options,
+            dates,
+            created,
+            None,
+            history.as_deref_mut(),
+        )?;
+    }
+    let calendar = build_calendar(options, shared, cancelled, created, None, history)?;
+    Ok(Output::Calendar(Box::new(calendar)))
+}
+
+fn render(
+    options: &Options,
+    collected: Collected,
+    history: Option<&mut History>,
+) -> Result<Output, Box<dyn Error>> {
+    let Collected {
+        responses,
+        cancelled,
+        empty,
+        created,
+        ..
+    } = collected;
+    let delivery_dates = || delivery_dates(&responses);
+    Ok(Output::Text(match options.format {
+        OutputFormat::Ical => {
+            return render_ical(options, &responses, cancelled, &empty, created, history);
+        }
+        OutputFormat::Jscalendar => {
+            let calendar = build_calendar(
+                options,
+                delivery_dates()?,
+                cancelled,
+                created,
+                None,
+                history,
+            )?;
+            serde_json::to_string(&calendar.to_jscalendar())?
+        }
+        OutputFormat::Json => serde_json::to_string(&json_output(responses))?,
+        OutputFormat::Yaml => formats::yaml::to_string(&json_output(responses)),
+        OutputFormat::Html => formats::html::to_string(
+            &delivery_dates()?,
+            Local::now().date_naive(),
+            options.relative_days,
+            options.html_refresh,
+        ),
+        OutputFormat::Remind => formats::remind::to_string(&delivery_dates()?),
+        OutputFormat::Taskwarrior => {
+            serde_json::to_string(&formats::taskwarrior::tasks(&delivery_dates()?))?
+        }
+        OutputFormat::Table => formats::table::to_string(
+            &delivery_dates()?,
+            Local::now().date_naive(),
+            use_color(options),
+        ),
+        OutputFormat::Waybar => serde_json::to_string(&formats::waybar::module(
+            &delivery_dates()?,
+            Local::now().date_naive(),
+            options.relative_days,
+        ))?,
+    }))
+}
+
+/// Write the delivery and cancelled `dates` of each month to its own file
+/// next to `path`.
+fn write_months(
+    path: &Path,
+    options: &Options,
+    dates: (Vec<DeliveryDate>, Vec<DeliveryDate>),
+    created: Option<DateTime<Utc>>,
+    settings: Option<&CodeCalendar>,
+    mut history: Option<&mut History>,
+) -> Result<(), Box<dyn Error>> {
+    let (delivery_dates, cancelled) = dates;
+    let month = |date: &DeliveryDate| (date.date.year(), date.date.month());
+    let months: BTreeSet<_> = delivery_dates.iter().chain(&cancelled).map(month).collect();
+    for (year, number) in months {
+        let of_month = |dates: &[DeliveryDate]| -> Vec<_> {
+            let dates = dates.iter().filter(|date| month(date) == (year, number));
+            dates.copied().collect()
+        };
+        let calendar = build_calendar(
+            options,
+            of_month(&delivery_dates),
+            of_month(&cancelled),
+            created,
+            settings,
+            history.as_deref_mut(),
+        )?;
+        write_output(
+            Some(&paths::month_path(path, year, number)),
+            options,
+            |writer| calendar.write_to(writer),
+        )?;
+    }
+    Ok(())
+}
+
+fn use_color(options: &Options) -> bool {
+    match options.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            options.output.is_none()
+                && std::io::stdout().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        }
+    }
+}
+
+fn validate_file(input: Option<&Path>, max_size: u64) -> Result<(), Box<dyn Error>> {
+    let value = mailbox_delivery_dates::read_input(input, max_size)?;
+    let errors = mailbox_delivery_dates::validate(&value);
+    if errors.is_empty() {
+        return Ok(());
+    }
+    for error in &errors {
+        eprintln!("{error}");
+    }
+    Err(format!("Input is invalid, found {} error(s)", errors.len()).into())
+}
+
+#[cfg_attr(feature = "otel", tracing::instrument(skip(write), err))]
+/// Write the output with `write` to `path`, or to stdout when `None`.
+fn write_output(
+    path: Option<&Path>,
+    options: &Options,
+    write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = path {
+        let file = std::fs::File::create(path).map_err(|err| io_error_to_string(&err, path))?;
+        let mut writer = std::io::BufWriter::new(file);
+        write(&mut writer)
+            .and_then(|()| writer.flush())
+            .map_err(|err| io_error_to_string(&err, path))?;
+        let gid = options
+            .output_group
+            .as_deref()
+            .map(paths::group_id)
+            .transpose()?;
+        paths::set_access(path, options.output_mode, gid)?;
+    } else {
+        let mut stdout = std::io::stdout().lock();
+        write(&mut stdout).and_then(|()| stdout.flush())?;
+    }
+    Ok(())
+}
+
+/// Contents of the files written by `--output` and the calendars of the
+/// configuration, `None` for missing files.
+///
+/// `DTSTAMP` lines are left out, as they change on every run.
+fn read_outputs(options: &Options) -> BTreeMap<PathBuf, Option<Vec<u8>>> {
+    options
+        .output
+        .iter()
+        .map(PathBuf::as_path)
+        .chain(options.own_calendars().map(|(_, _, output)| output))
+        .map(|path| {
+            let content = std::fs::read(path).ok().map(|content| {
+                content
+                    .split_inclusive(|&c| c == b'\n')
+                    .filter(|line| !line.starts_with(b"DTSTAMP:"))
+                    .flatten()
+                    .copied()
+                    .collect()
+            });
+            (path.to_path_buf(), content)
+        })
+        .collect()
+}
+
+/// Print how each written file differs from `before`, with the delivery
+/// dates added and removed for its postal code.
+fn print_summary(
+    options: &Options,
+    before: &BTreeMap<PathBuf, Option<Vec<u8>>>,
+    changes: &[Changes],
+) {
+    let postal_codes: BTreeMap<_, _> = options
+        .own_calendars()
+        .map(|(postal_code, _, output)| (output, postal_code))
+        .collect();
+    for (path, content) in read_outputs(options) {
+        let status = match before.get(&path) {
+            _ if content.is_none() => "not written",
+            Some(previous) if previous == &content => "unchanged",
+            Some(Some(_)) => "updated",
+            Some(None) | None => "created",
+        };
+        let counts = postal_codes
+            .get(path.as_path())
+            .and_then(|postal_code| {
+                changes
+                    .iter()
+                    .find(|changes| changes.postal_code == *postal_code)
+            })
+            .filter(|changes| !changes.is_empty())
+            .map(|changes| {
+                format!(
+                    ", {} added, {} removed",
+                    changes.added.len(),
+                    changes.removed.len()
+                )
+            })
+            .unwrap_or_default();
+        let given = postal_codes
+            .get(path.as_path())
+            .and_then(|postal_code| options.duplicates.get(postal_code))
+            .map(|codes| format!(", given as {}", codes.join(", ")))
+            .unwrap_or_default();
+        eprintln!("{}: {status}{counts}{given}", path.display());
+    }
+}
+
+/// Print the summary of `--output-dir`, and run `--on-change` if any
+/// written file differs from `before`.
+fn run_on_change(
+    options: &Options,
+    before: &BTreeMap<PathBuf, Option<Vec<u8>>>,
+    changes: &[Changes],
+) -> Result<(), Box<dyn Error>> {
+    if options.output_dir.is_some() {
+        print_summary(options, before, changes);
+    }
+    let Some(command) = &options.on_change else {
+        return Ok(());
+    };
+    let files: Vec<_> = read_outputs(options)
+        .into_iter()
+        .filter(|(path, content)| before.get(path) != Some(content))
+        .map(|(path, _)| path)
+        .collect();
+    if files.is_empty() {
+        return Ok(());
+    }
+    notify::run_hook(command, changes, &files)
+}
+
+/// Apply `--exclude-weekday` and `--limit` to `responses`.
+fn filter_dates(
+    view: &View,
+    options: &Options,
+    mut responses: Vec<(NorwegianPostalCode, serde_json::Value)>,
+) -> Result<Vec<(NorwegianPostalCode, serde_json::Value)>, serde_json::Error> {
+    if !options.exclude_weekday.is_empty() {
+        responses = map_dates(responses, |dates| {
+            dates
+                .into_iter()
+                .filter(|date| !options.exclude_weekday.contains(&date.weekday()))
+                .collect()
+        })?;
+    }
+    if let (View::Format, Some(limit)) = (view, options.limit) {
+        let today = Local::now().date_naive();
+        responses = map_dates(responses, |dates| {
+            dates
+                .into_iter()
+                .filter(|date| *date >= today)
+                .take(limit)
+                .collect()
+        })?;
+    }
+    Ok(responses)
+}
+
+/// Read the given or discovered config file and fill in missing options from it.
+fn load_config(options: &mut Options) -> Result<(Option<PathBuf>, Config), String> {
+    let config_path = options.config.clone().or_else(config::discover);
+    let config = config_path
+        .as_deref()
+        .map(Config::load)
+        .transpose()?
+        .unwrap_or_default();
+    log::debug!("Using config {config_path:?}");
+    let mut given: BTreeMap<NorwegianPostalCode, Vec<String>> = BTreeMap::new();
+    for code in &options.codes {
+        let postal_code = config.postal_code(code)?;
+        if !given.contains_key(&postal_code) {
+            options.code.push(postal_code);
+        }
+        given.entry(postal_code).or_default().push(code.clone());
+    }
+    given.retain(|_, codes| codes.len() > 1);
+    for (postal_code, codes) in &given {
+        log::info!(
+            "{postal_code} is given as {}, fetching it once",
+            codes.join(", ")
+        );
+    }
+    options.duplicates = given;
+    options.names = config.names();
+    for (key, calendar) in &config.calendars {
+        options
+            .calendars
+            .insert(config.postal_code(key)?, CodeCalendar::new(key, calendar)?);
+    }
+    if options.code.is_empty() {
+        for &postal_code in &config.code {
+            if !options.code.contains(&postal_code) {
+                options.code.push(postal_code);
             }
-            .into();
-            let cal: Calendar = delivery_dates.into();
-            format!("{cal}")
         }
-        OutputFormat::Json => {
-            let response: serde_json::Value = endpoint.get(cli.code).await?;
-            log::debug!("Got: {:?}", response);
-            serde_json::to_string(&response)?
+    }
+    if let Some(dir) = &options.output_dir {
+        if !matches!(options.format, OutputFormat::Ical) {
+            return Err(locale(options).message(Message::OutputDirNeedsIcal));
+        }
+        for &postal_code in &options.code {
+            options
+                .calendars
+                .entry(postal_code)
+                .or_insert_with(|| CodeCalendar {
+                    output: None,
+                    name: None,
+                    locale: None,
+                    alarm: None,
+                    summary: None,
+                })
+                .output
+                .get_or_insert_with(|| dir.join(format!("{postal_code}.ics")));
+        }
+        if options.merged {
+            options.output = Some(dir.join("all.ics"));
         }
+    } else if options.output.is_none() {
+        options.output.clone_from(&config.output);
+    }
+    if options.split_by_month
+        && (!matches!(options.format, OutputFormat::Ical)
+            || options.output.is_none() && options.own_calendars().next().is_none())
+    {
+        return Err(locale(options).message(Message::SplitByMonthNeedsFile));
+    }
+    config_notifiers(options, &config)?;
+    Ok((config_path, config))
+}
+
+/// Add the notifiers and message templates of `config` to `options`.
+fn config_notifiers(options: &mut Options, config: &Config) -> Result<(), String> {
+    if let Some(messages) = &config.messages {
+        options.templates = notify::Templates {
+            changes: messages.changes.clone(),
+            reminder: messages.reminder.clone(),
+            names: options.names.clone(),
+            places: BTreeMap::new(),
+            locale: match &messages.locale {
+                Some(locale) => locale
+                    .parse()
+                    .map_err(|err| format!("messages.locale: {err}"))?,
+                None => locale(options),
+            },
+        };
+    }
+    if let Some(telegram) = &config.telegram {
+        options.notify.push(Notifier::Telegram {
+            token: telegram.token.clone(),
+            chat_id: telegram.chat_id.clone(),
+        });
+    }
+    if let Some(matrix) = &config.matrix {
+        options.notify.push(Notifier::Matrix {
+            homeserver: Url::parse(&matrix.homeserver)
+                .map_err(|err| format!("matrix.homeserver: {err}"))?,
+            token: matrix.token.clone(),
+            room_id: matrix.room_id.clone(),
+        });
+    }
+    #[cfg(feature = "smtp")]
+    if let Some(smtp) = &config.smtp {
+        let attachment = smtp
+            .attach_calendar
+            .then(|| {
+                options
+                    .output
+                    .clone()
+                    .ok_or("smtp.attach-calendar needs --output")
+            })
+            .transpose()?;
+        options
+            .notify
+            .push(Notifier::Smtp(Box::new(postgang::smtp::Mailer::new(
+                smtp, attachment,
+            )?)));
+    }
+    #[cfg(not(feature = "smtp"))]
+    if config.smtp.is_some() {
+        return Err("Email over SMTP needs the smtp feature".to_string());
+    }
+    Ok(())
+}
+
+/// Print the post place of each of `codes`, or of `--code` if empty.
+///
+/// Fails after printing them all if any of them is invalid.
+async fn lookup_postal_codes(
+    codes: &[String],
+    api_uid: Option<ApiUid>,
+    api_key: Option<ApiKey>,
+    options: &Options,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let (api_uid, api_key) = credentials(api_uid, api_key, locale(options), config)?;
+    let postal_codes: Vec<_> = if codes.is_empty() {
+        options.code.clone()
+    } else {
+        codes
+            .iter()
+            .map(|code| config.postal_code(code))
+            .collect::<Result<_, _>>()?
     };
-    match cli.output {
-        Some(path) => {
-            // Try to create file before we do any network requests
-            let mut file =
-                std::fs::File::create(&path).map_err(|err| io_error_to_string(&err, &path))?;
-            write!(file, "{output}").map_err(|err| io_error_to_string(&err, &path))?;
+    if postal_codes.is_empty() {
+        return Err(locale(options).required("--code").into());
+    }
+    let client = PostalCodes::new(http_client::Client::new(), api_uid, api_key);
+    let mut invalid = Vec::new();
+    for postal_code in postal_codes {
+        let place = client.lookup(postal_code).await?;
+        println!("{}", place.describe(postal_code, locale(options)));
+        if !place.valid {
+            invalid.push(postal_code.to_string());
         }
-        None => std::io::stdout().write_fmt(format_args!("{output}"))?,
     }
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        let invalid = invalid.join(", ");
+        Err(locale(options)
+            .message(Message::InvalidPostalCodes(&invalid))
+            .into())
+    }
+}
+
+/// Where the files of a run are kept, with `--history` and `--no-history`
+/// taking precedence.
+fn resolve_paths(options: &Options, config: &Config) -> Paths {
+    let mut paths = Paths::from_env(config);
+    if options.history.is_some() || options.no_history {
+        paths.history.clone_from(&options.history);
+    }
+    paths
+}
 
+/// Run `postgang config` on the file given with `--config`, or else the
+/// configuration file in use.
+///
+/// A new file is created in the user's configuration directory.
+fn edit_config(command: ConfigCommands, options: &Options) -> Result<(), Box<dyn Error>> {
+    let user_config = || config::user_config(|var| std::env::var_os(var));
+    let path = match command {
+        ConfigCommands::Init { .. } => options.config.clone().or_else(user_config),
+        _ => options
+            .config
+            .clone()
+            .or_else(config::discover)
+            .or_else(user_config),
+    }
+    .ok_or_else(|| locale(options).required("--config"))?;
+    let read = || std::fs::read_to_string(&path).map_err(|err| io_error_to_string(&err, &path));
+    let write = |content: &str| {
+        if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|err| io_error_to_string(&err, parent))?;
+        }
+        std::fs::write(&path, content).map_err(|err| io_error_to_string(&err, &path))
+    };
+    match command {
+        ConfigCommands::Init { force } => {
+            if path.exists() && !force {
+                return Err(
+                    format!("{} exists, use --force to overwrite it", path.display()).into(),
+                );
+            }
+            write(config::TEMPLATE)?;
+            println!("{}", path.display());
+        }
+        ConfigCommands::Get { key } => match config::get(&read()?, &key)? {
+            Some(value) => println!("{value}"),
+            None => return Err(format!("{key} is not set in {}", path.display()).into()),
+        },
+        ConfigCommands::Set { key, value } => {
+            let content = if path.exists() {
+                read()?
+            } else {
+                String::new()
+            };
+            let content = config::set(&content, &key, &value)?;
+            write(&content)?;
+            if let Err(err) = Config::parse(&content) {
+                log::warn!("{}: {}", path.display(), err.message().trim_end());
+            }
+        }
+        ConfigCommands::Show => print!("{}", config::mask_secrets(&read()?)?),
+    }
+    Ok(())
+}
+
+/// Print the files of `target`, running postgang with the options given
+/// before `generate`.
+fn generate(target: &GenerateCommands) -> Result<(), Box<dyn Error>> {
+    let (GenerateCommands::Systemd { command, .. }
+    | GenerateCommands::Cron { command, .. }
+    | GenerateCommands::Launchd { command, .. }) = target;
+    let exec = exec_args("generate", command.clone())?;
+    let working_directory = std::env::current_dir()?;
+    match target {
+        GenerateCommands::Systemd { interval, user, .. } => {
+            let units = systemd::units(&exec, &working_directory, *interval, *user);
+            let dir = if *user {
+                "~/.config/systemd/user"
+            } else {
+                "/etc/systemd/system"
+            };
+            let enable = if *user {
+                "systemctl --user enable --now postgang.timer"
+            } else {
+                "systemctl enable --now postgang.timer"
+            };
+            println!("# {dir}/postgang.service\n{}", units.service);
+            println!("# {dir}/postgang.timer\n{}", units.timer);
+            println!("# Then run: {enable}");
+        }
+        GenerateCommands::Cron { interval, .. } => {
+            // Spread the runs of different users over the hour, and run
+            // daily jobs early in the morning
+            let schedule = cron::schedule(*interval, fastrand::u32(0..60), fastrand::u32(4..7))?;
+            println!("# Add with crontab -e");
+            if let Some(hint) = credentials_hint() {
+                println!("# {hint}");
+            }
+            println!(
+                "{}",
+                cron::entry(&schedule, &working_directory, &exec_env(), &exec)
+            );
+        }
+        GenerateCommands::Launchd { interval, .. } => {
+            let log = std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join("Library/Logs/postgang.log"));
+            print!(
+                "{}",
+                launchd::plist(
+                    &exec,
+                    &working_directory,
+                    *interval,
+                    &exec_env(),
+                    log.as_deref()
+                )
+            );
+            // Keep stdout a valid plist when redirected to the file
+            if let Some(hint) = credentials_hint() {
+                eprintln!("{hint}");
+            }
+            eprintln!(
+                "Save as ~/Library/LaunchAgents/{}.plist and run: launchctl load -w ~/Library/LaunchAgents/{}.plist",
+                launchd::LABEL,
+                launchd::LABEL
+            );
+        }
+    }
     Ok(())
 }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> ExitCode {
-    env_logger::init();
+/// Variables holding credentials, which are left out of generated files.
+const CREDENTIAL_ENV: [&str; 2] = ["POSTGANG_API_UID", "POSTGANG_API_KEY"];
 
-    match try_main().await {
-        Ok(()) => ExitCode::SUCCESS,
+/// The variables of the environment a scheduled run needs to find the same
+/// files, except for [`CREDENTIAL_ENV`].
+fn exec_env() -> Vec<(String, String)> {
+    let mut env: Vec<_> = std::env::vars()
+        .filter(|(name, _)| {
+            (name.starts_with("POSTGANG_") && !CREDENTIAL_ENV.contains(&name.as_str()))
+                || matches!(
+                    name.as_str(),
+                    "XDG_CONFIG_HOME" | "XDG_CACHE_HOME" | "XDG_STATE_HOME"
+                )
+        })
+        .collect();
+    env.sort();
+    env
+}
+
+/// Where to keep the credentials instead, if any are set in the environment.
+fn credentials_hint() -> Option<String> {
+    let set: Vec<_> = CREDENTIAL_ENV
+        .into_iter()
+        .filter(|name| std::env::var_os(name).is_some())
+        .collect();
+    (!set.is_empty()).then(|| {
+        format!(
+            "{} left out, set api_uid and api_key in the config file or store the key with postgang setup",
+            set.join(" and ")
+        )
+    })
+}
+
+/// The options given before `subcommand`.
+fn options_before(subcommand: &str) -> Vec<String> {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    args.truncate(
+        args.iter()
+            .position(|arg| arg == subcommand)
+            .unwrap_or(args.len()),
+    );
+    args
+}
+
+/// This program with the options given before `subcommand`, followed by
+/// `command`, or `api` if it is empty.
+fn exec_args(subcommand: &str, mut command: Vec<String>) -> Result<Vec<String>, Box<dyn Error>> {
+    let exe = std::env::current_exe()?;
+    if command.is_empty() {
+        command.push("api".to_string());
+    }
+    Ok([exe.to_string_lossy().into_owned()]
+        .into_iter()
+        .chain(options_before(subcommand))
+        .chain(command)
+        .collect())
+}
+
+/// Manage the Windows service, which runs postgang with the options given
+/// before `service`.
+#[cfg(all(windows, feature = "windows-service"))]
+fn windows_service(
+    command: ServiceCommands,
+    options: &Options,
+    loaded: Result<(Option<PathBuf>, Config), String>,
+) -> Result<(), Box<dyn Error>> {
+    use postgang::service;
+
+    match command {
+        ServiceCommands::Install { schedule, command } => {
+            let schedule = schedule.to_string();
+            let arguments = options_before("service")
+                .into_iter()
+                .chain(["service", "run", "--schedule", &schedule, "--"].map(String::from))
+                .chain(command)
+                .collect();
+            service::install(arguments)?;
+            eprintln!(
+                "Installed the service {}, start it with: postgang service start",
+                service::NAME
+            );
+        }
+        ServiceCommands::Uninstall => service::uninstall()?,
+        ServiceCommands::Start => service::start()?,
+        ServiceCommands::Stop => service::stop()?,
+        ServiceCommands::Run { schedule, command } => {
+            // The job runs with the options before `service`, and so the same history
+            let (_, config) = loaded?;
+            let failures = resolve_paths(options, &config)
+                .history
+                .map(|history| failures::path(&history));
+            service::run(service::Job {
+                exec: exec_args("service", command)?,
+                schedule,
+                failures,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Show the month view of the history.
+///
+/// Only reads the history, so runs are not locked out while browsing.
+#[cfg(feature = "tui")]
+fn browse(history: Option<PathBuf>, options: Options) -> Result<(), Box<dyn Error>> {
+    if options.code.is_empty() {
+        return Err(locale(&options).required("--code").into());
+    }
+    let history = history.ok_or_else(|| locale(&options).required("--history"))?;
+    postgang::tui::run(history, options.code)
+}
+
+/// Check that the delivery dates of every postal code were fetched from the
+/// API within `max_age`, as recorded in the history, and that the last run
+/// of the service didn't fail.
+fn healthcheck(
+    history: Option<PathBuf>,
+    options: &Options,
+    max_age: Duration,
+) -> Result<(), Box<dyn Error>> {
+    if options.code.is_empty() {
+        return Err(locale(options).required("--code").into());
+    }
+    let locale = locale(options);
+    let history = history.ok_or_else(|| locale.required("--history"))?;
+    let failures = Failures::load(&failures::path(&history))?;
+    let history = History::open(history)?;
+    let now = Utc::now();
+    let age = |time: DateTime<Utc>| {
+        // A time in the future, from a skewed clock, is now
+        let age = (now - time).to_std().unwrap_or_default();
+        Duration::from_secs(age.as_secs())
+    };
+    let mut checks: Vec<_> = options
+        .code
+        .iter()
+        .map(|&postal_code| {
+            let result = match history.refreshed(postal_code) {
+                None => Err(locale.message(Message::NeverRefreshed)),
+                Some(refreshed) => {
+                    let age = age(refreshed);
+                    if age <= max_age {
+                        Ok(locale.message(Message::Refreshed { age, max_age: None }))
+                    } else {
+                        Err(locale.message(Message::Refreshed {
+                            age,
+                            max_age: Some(max_age),
+                        }))
+                    }
+                }
+            };
+            Check::new(postal_code.to_string(), result)
+        })
+        .collect();
+    let fresh = checks.iter().all(Check::passed);
+    if let Some(failures) = failures {
+        let message = Message::FailedRuns {
+            count: failures.count,
+            age: age(failures.last_failure),
+        };
+        checks.push(Check::new("service", Err(locale.message(message))));
+    }
+    for check in &checks {
+        println!("{check}");
+    }
+    if !fresh {
+        Err(locale.message(Message::Stale).into())
+    } else if failures.is_some() {
+        Err(locale.message(Message::Degraded).into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Ask for the credentials until they are accepted by the API along with a
+/// postal code.
+async fn ask_credentials(
+    input: &mut impl std::io::BufRead,
+    output: &mut impl Write,
+    locale: Locale,
+) -> Result<(String, String, NorwegianPostalCode), Box<dyn Error>> {
+    loop {
+        let api_uid = setup::ask(
+            input,
+            output,
+            &locale.setup(Setup::ApiUid),
+            None,
+            |answer| parse_api_uid(answer).map(|_| answer.to_string()),
+        )?;
+        let api_key = setup::ask(
+            input,
+            output,
+            &locale.setup(Setup::ApiKey),
+            None,
+            |answer| parse_api_key(answer).map(|_| answer.to_string()),
+        )?;
+        let client = PostalCodes::new(
+            http_client::Client::new(),
+            parse_api_uid(&api_uid)?,
+            parse_api_key(&api_key)?,
+        );
+        loop {
+            let postal_code = setup::ask(
+                input,
+                output,
+                &locale.setup(Setup::PostalCode),
+                None,
+                |answer| NorwegianPostalCode::try_from(answer).map_err(|err| err.to_string()),
+            )?;
+            let result = client.lookup(postal_code).await;
+            match result {
+                Ok(place) => {
+                    writeln!(output, "{}", place.describe(postal_code, locale))?;
+                    if place.valid {
+                        return Ok((api_uid, api_key, postal_code));
+                    }
+                }
+                Err(err) => {
+                    writeln!(
+                        output,
+                        "{}",
+                        locale.setup(Setup::CheckFailed(&err.to_string()))
+                    )?;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Write a configuration file from answers on the terminal, checking the
+/// credentials and the postal code against the API first.
+async fn setup_wizard(options: &Options) -> Result<(), Box<dyn Error>> {
+    let locale = locale(options);
+    let path = options
+        .config
+        .clone()
+        .or_else(|| config::user_config(|var| std::env::var_os(var)))
+        .ok_or_else(|| locale.required("--config"))?;
+    let mut input = std::io::stdin().lock();
+    let mut output = std::io::stdout();
+    let overwrite = locale.setup(Setup::Overwrite(&path));
+    if path.exists() && !setup::confirm(&mut input, &mut output, &overwrite, false, locale)? {
+        return Ok(());
+    }
+    writeln!(output, "{}", locale.setup(Setup::Intro))?;
+    let (api_uid, api_key, postal_code) = ask_credentials(&mut input, &mut output, locale).await?;
+    #[cfg(feature = "keyring")]
+    let api_key = if setup::confirm(
+        &mut input,
+        &mut output,
+        &locale.setup(Setup::Keyring),
+        true,
+        locale,
+    )? {
+        setup::store_api_key(&api_uid, &api_key)?;
+        None
+    } else {
+        Some(api_key)
+    };
+    #[cfg(not(feature = "keyring"))]
+    let api_key = Some(api_key);
+    let content = setup::config_content(&setup::Answers {
+        api_uid,
+        api_key,
+        postal_code,
+    })?;
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|err| io_error_to_string(&err, parent))?;
+    }
+    std::fs::write(&path, content).map_err(|err| io_error_to_string(&err, &path))?;
+    // The file may hold the API key
+    paths::set_access(&path, Some(0o600), None)?;
+    writeln!(output, "{}", locale.setup(Setup::Written(&path)))?;
+    Ok(())
+}
+
+/// Check the setup and print a report, failing if any check fails.
+async fn doctor(
+    loaded: Result<(Option<PathBuf>, Config), String>,
+    options: &Options,
+    api_uid: Option<ApiUid>,
+    api_key: Option<ApiKey>,
+) -> Result<(), Box<dyn Error>> {
+    let locale = locale(options);
+    let mut checks = Vec::new();
+    let config = match loaded {
+        Ok((config_path, config)) => {
+            let found = config_path.map_or_else(
+                || locale.message(Message::NoConfigFile),
+                |path| locale.message(Message::Valid(&path)),
+            );
+            checks.push(Check::new("config", Ok(found)));
+            config
+        }
+        Err(err) => {
+            checks.push(Check::new("config", Err(err)));
+            Config::default()
+        }
+    };
+    let credentials = credentials(api_uid, api_key, locale, &config)
+        .map(|_| locale.message(Message::CredentialsSet));
+    checks.push(Check::new("credentials", credentials));
+    checks.push(Check::new(
+        "network",
+        doctor::reachable("https://api.bring.com/", Duration::from_secs(10), locale).await,
+    ));
+    let paths = resolve_paths(options, &config);
+    if let Some(cache_dir) = &paths.cache_dir {
+        checks.push(Check::new("cache", doctor::writable_dir(cache_dir, locale)));
+    }
+    if let Some(history) = &paths.history {
+        checks.push(Check::new(
+            "history",
+            doctor::writable_file(history, locale),
+        ));
+    }
+    let outputs = options
+        .output
+        .iter()
+        .map(PathBuf::as_path)
+        .chain(options.own_calendars().map(|(_, _, output)| output));
+    for output in outputs {
+        checks.push(Check::new("output", doctor::writable_file(output, locale)));
+    }
+    for check in &checks {
+        println!("{check}");
+    }
+    let failed = checks.iter().filter(|check| !check.passed()).count();
+    if failed == 0 {
+        Ok(())
+    } else {
+        let total = checks.len();
+        Err(locale
+            .message(Message::ChecksFailed { failed, total })
+            .into())
+    }
+}
+
+/// Print the value of every option before the subcommand and where it came
+/// from, followed by the settings only found in the configuration, the `api`
+/// settings given to `explain` and the resolved paths.
+///
+/// Secrets are masked, and notifiers are shown by kind only as their URLs
+/// may hold tokens.
+fn explain(
+    options: &Options,
+    args: &ApiArgs,
+    config_path: Option<&Path>,
+    config: &Config,
+    paths: &Paths,
+) -> Result<(), Box<dyn Error>> {
+    let command = Cli::command();
+    let matches = command.clone().try_get_matches_from(std::env::args_os())?;
+    let is_set = |var: &str| std::env::var_os(var).is_some_and(|value| !value.is_empty());
+    let source = match matches.value_source("config") {
+        Some(ValueSource::CommandLine) => "--config",
+        Some(ValueSource::EnvVariable) => "POSTGANG_CONFIG",
+        _ => "found",
+    };
+    match config_path {
+        Some(path) => println!("config: {} ({source})", path.display()),
+        None => println!("config: - (no file found)"),
+    }
+    explain_args(&command, &matches, false);
+    explain_config(options, config, &matches);
+    if let (Some(command), Some(matches)) = (
+        command.find_subcommand("explain"),
+        matches.subcommand_matches("explain"),
+    ) {
+        explain_api(args, config, command, matches);
+    }
+    let cache_source = if args.no_cache {
+        Some("--no-cache")
+    } else if args.cache_dir.is_some() {
+        Some("--cache-dir")
+    } else {
+        None
+    };
+    let cache_dir = if args.no_cache {
+        None
+    } else {
+        args.cache_dir.clone().or_else(|| paths.cache_dir.clone())
+    };
+    for (name, path, var, configured) in [
+        (
+            "cache",
+            &cache_dir,
+            "POSTGANG_CACHE_DIR",
+            config.cache_dir.is_some(),
+        ),
+        (
+            "history",
+            &paths.history,
+            "POSTGANG_HISTORY",
+            config.history.is_some(),
+        ),
+        ("lock", &paths.lock, "POSTGANG_LOCK", false),
+    ] {
+        let source = if name == "history" && (options.history.is_some() || options.no_history) {
+            "--history"
+        } else if let Some(source) = cache_source.filter(|_| name == "cache") {
+            source
+        } else if is_set(var) {
+            var
+        } else if configured {
+            "config file"
+        } else {
+            "XDG base directory"
+        };
+        match path {
+            Some(path) => println!("{name}: {} ({source})", path.display()),
+            None => println!("{name}: - ({source})"),
+        }
+    }
+    Ok(())
+}
+
+/// Print the value of each argument of `command` given in `matches` and
+/// where it came from, or of `all` of them with a `-` for those not given.
+///
+/// The API credentials are left to [`explain_api`].
+fn explain_args(command: &clap::Command, matches: &clap::ArgMatches, all: bool) {
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        let source = match matches.value_source(id) {
+            _ if matches!(id, "help" | "version" | "config" | "api_uid" | "api_key") => continue,
+            Some(ValueSource::CommandLine) => format!("--{long}"),
+            Some(ValueSource::EnvVariable) => arg
+                .get_env()
+                .map_or_else(String::new, |var| var.to_string_lossy().into_owned()),
+            Some(ValueSource::DefaultValue) => "default".to_string(),
+            _ if all => {
+                println!("{long}: - (default)");
+                continue;
+            }
+            _ => continue,
+        };
+        let values: Vec<_> = matches
+            .get_raw(id)
+            .into_iter()
+            .flatten()
+            .map(|value| {
+                let value = value.to_string_lossy();
+                match (id, value.split_once(':')) {
+                    ("notify", Some((kind, _))) => kind.to_string(),
+                    _ => value.into_owned(),
+                }
+            })
+            .collect();
+        println!("{long}: {} ({source})", values.join(", "));
+    }
+}
+
+/// Print the credentials and every other setting of `args`, the options of
+/// `api` given to `explain`, and where they came from as a run with them
+/// would resolve them.
+fn explain_api(
+    args: &ApiArgs,
+    config: &Config,
+    command: &clap::Command,
+    matches: &clap::ArgMatches,
+) {
+    const MASK: &str = "********";
+    let source = |id: &str, long: &'static str, var: &'static str| {
+        if matches.value_source(id) == Some(ValueSource::EnvVariable) {
+            var
+        } else {
+            long
+        }
+    };
+    match (&args.api_uid, &config.api_uid) {
+        (Some(api_uid), _) => {
+            let source = source("api_uid", "--api-uid", "POSTGANG_API_UID");
+            println!(
+                "api-uid: {} ({source})",
+                api_uid.as_str().unwrap_or_default()
+            );
+        }
+        (None, Some(api_uid)) => println!("api-uid: {} (config file)", api_uid.0),
+        (None, None) => println!("api-uid: - (not set)"),
+    }
+    // Like credentials, which only looks in the keyring without another key
+    #[cfg(feature = "keyring")]
+    let stored = args
+        .api_uid
+        .as_ref()
+        .and_then(ApiUid::as_str)
+        .or_else(|| config.api_uid.as_ref().map(|api_uid| api_uid.0.as_str()))
+        .and_then(postgang::setup::load_api_key)
+        .is_some();
+    #[cfg(not(feature = "keyring"))]
+    let stored = false;
+    match (&args.api_key, &config.api_key) {
+        (Some(_), _) => {
+            let source = source("api_key", "--api-key", "POSTGANG_API_KEY");
+            println!("api-key: {MASK} ({source})");
+        }
+        (None, Some(_)) => println!("api-key: {MASK} (config file)"),
+        (None, None) if stored => println!("api-key: {MASK} (keyring)"),
+        (None, None) => println!("api-key: - (not set)"),
+    }
+    explain_args(command, matches, true);
+}
+
+/// Print the settings of `postgang explain` that can't be given before
+/// the subcommand, or that only the configuration set.
+fn explain_config(options: &Options, config: &Config, matches: &clap::ArgMatches) {
+    if matches.value_source("codes").is_none() && !options.code.is_empty() {
+        let codes: Vec<_> = options.code.iter().map(ToString::to_string).collect();
+        println!("code: {} (config file)", codes.join(", "));
+    }
+    // --output-dir replaces the output of the configuration
+    if let (None, Some(output)) = (matches.value_source("output"), &config.output) {
+        if options.output.as_ref() == Some(output) {
+            println!("output: {} (config file)", output.display());
+        }
+    }
+    for (postal_code, _, output) in options.own_calendars() {
+        let configured = config
+            .calendars
+            .values()
+            .any(|configured| configured.output.as_deref() == Some(output));
+        let source = if configured {
+            "config file"
+        } else {
+            "--output-dir"
+        };
+        println!("calendar {postal_code}: {} ({source})", output.display());
+    }
+    if config.telegram.is_some() {
+        println!("notify: telegram (config file)");
+    }
+    if config.matrix.is_some() {
+        println!("notify: matrix (config file)");
+    }
+    if config.smtp.is_some() {
+        println!("notify: smtp (config file)");
+    }
+    if config.messages.is_some() {
+        println!("messages: templates (config file)");
+    }
+}
+
+/// Print where the configuration and the files of a run are kept.
+fn print_paths(config_path: Option<&Path>, paths: &Paths) {
+    match config_path {
+        Some(path) => println!("config: {}", path.display()),
+        None => println!("config: -"),
+    }
+    print!("{paths}");
+}
+
+/// The records of `postal_codes` between `from` and `to` in `format`.
+fn export_history(
+    history: &History,
+    postal_codes: &[NorwegianPostalCode],
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    format: &ExportFormat,
+) -> Result<String, Box<dyn Error>> {
+    let records = history.records(postal_codes, from, to);
+    Ok(match format {
+        ExportFormat::Csv => history::to_csv(&records),
+        ExportFormat::Json => serde_json::to_string(&records)?,
+    })
+}
+
+async fn try_main(cli: Cli) -> Result<(), Box<dyn Error>> {
+    log::debug!("Got CLI args: {cli:?}");
+    let Cli {
+        command,
+        mut options,
+    } = cli;
+    let loaded = load_config(&mut options);
+    let command = match command {
+        Commands::Config { command } => return edit_config(command, &options),
+        Commands::Doctor { api_uid, api_key } => {
+            return doctor(loaded, &options, api_uid, api_key).await;
+        }
+        Commands::Generate { target } => return generate(&target),
+        Commands::Setup => return setup_wizard(&options).await,
+        #[cfg(all(windows, feature = "windows-service"))]
+        Commands::Service { command } => return windows_service(command, &options, loaded),
+        command => command,
+    };
+    let (config_path, config) = loaded?;
+    let paths = resolve_paths(&options, &config);
+    let command = match command {
+        Commands::Paths => {
+            print_paths(config_path.as_deref(), &paths);
+            return Ok(());
+        }
+        Commands::Explain(args) => {
+            return explain(&options, &args, config_path.as_deref(), &config, &paths);
+        }
+        Commands::File {
+            print_schema: true, ..
+        } => {
+            std::io::stdout().write_all(SCHEMA.as_bytes())?;
+            return Ok(());
+        }
+        Commands::File {
+            input,
+            validate_only: true,
+            ..
+        } => return validate_file(input.as_deref(), options.max_response_size),
+        Commands::Lookup {
+            codes,
+            api_uid,
+            api_key,
+        } => return lookup_postal_codes(&codes, api_uid, api_key, &options, &config).await,
+        Commands::Healthcheck { max_age } => return healthcheck(paths.history, &options, max_age),
+        #[cfg(feature = "tui")]
+        Commands::Tui => return browse(paths.history, options),
+        command => command,
+    };
+    if options.code.is_empty()
+        && !matches!(command, Commands::Convert { .. } | Commands::Import { .. })
+    {
+        return Err(locale(&options).required("--code").into());
+    }
+    // Keep concurrent runs from overwriting each other's history
+    let _lock = match (&paths.history, &paths.lock) {
+        (Some(_), Some(lock)) => Some(paths::lock(lock)?),
+        _ => None,
+    };
+    let mut history = paths.history.clone().map(History::open).transpose()?;
+    if let Commands::Import { input } = &command {
+        return import_calendar(input.as_deref(), &options, history.as_mut());
+    }
+    let before = (options.on_change.is_some() || options.output_dir.is_some())
+        .then(|| read_outputs(&options));
+    let api_credentials = given_credentials(&command);
+    let (output, outcome) = if let Commands::History {
+        from,
+        to,
+        command: Some(HistoryCommands::Export { format }),
+    } = command
+    {
+        let history = history
+            .as_ref()
+            .ok_or_else(|| locale(&options).required("--history"))?;
+        let output = export_history(history, &options.code, from, to, &format)?;
+        (Output::Text(output), Outcome::default())
+    } else {
+        let view = View::new(&command);
+        let source = Source::new(command, &options, &config, &paths)?;
+        render_view(view, source, &options, history.as_mut()).await?
+    };
+    publish(&options, &output, &outcome.empty)?;
+    if let Some(before) = before {
+        run_on_change(&options, &before, &outcome.changes)?;
+    }
+    let templates = message_templates(&options, &config, api_credentials).await;
+    notify_all(
+        &options.notify,
+        &outcome.changes,
+        outcome.reminder,
+        &templates,
+        &outcome.upcoming,
+    )
+    .await?;
+    if options.empty_exit_code.is_some() && !outcome.empty.is_empty() {
+        return Err(EmptyResponse(outcome.empty).into());
+    }
+    Ok(())
+}
+
+/// The credentials given to `api` on the command line or in the environment.
+fn given_credentials(command: &Commands) -> (Option<ApiUid>, Option<ApiKey>) {
+    match command {
+        Commands::Api(args) => (args.api_uid.clone(), args.api_key.clone()),
+        _ => (None, None),
+    }
+}
+
+/// The notification templates of `options`, with the post places looked up
+/// with `given` or the credentials of `config` when a template needs them.
+async fn message_templates(
+    options: &Options,
+    config: &Config,
+    given: (Option<ApiUid>, Option<ApiKey>),
+) -> notify::Templates {
+    let mut templates = options.templates.clone();
+    if templates.uses_place() && !options.notify.is_empty() {
+        let (api_uid, api_key) = given;
+        let credentials = credentials(api_uid, api_key, locale(options), config);
+        templates.places = post_places(&options.code, credentials).await;
+    }
+    templates
+}
+
+/// The post places of `postal_codes` for `{place}` in the notification
+/// messages, leaving out those that can't be looked up.
+async fn post_places(
+    postal_codes: &[NorwegianPostalCode],
+    credentials: Result<(ApiUid, ApiKey), String>,
+) -> BTreeMap<NorwegianPostalCode, String> {
+    let (api_uid, api_key) = match credentials {
+        Ok(credentials) => credentials,
+        Err(err) => {
+            log::warn!("Not looking up post places: {err}");
+            return BTreeMap::new();
+        }
+    };
+    let client = PostalCodes::new(http_client::Client::new(), api_uid, api_key);
+    let mut places = BTreeMap::new();
+    for &postal_code in postal_codes {
+        let result = client.lookup(postal_code).await;
+        match result {
+            Ok(place) if place.valid => {
+                places.insert(postal_code, place.result);
+            }
+            Ok(_) => {}
+            Err(err) => log::warn!("{postal_code}: Looking up the post place failed: {err}"),
+        }
+    }
+    places
+}
+
+/// What a run writes to `--output` or stdout.
+enum Output {
+    Text(String),
+    /// Written with [`Calendar::write_to`] instead of formatted first.
+    Calendar(Box<Calendar>),
+}
+
+impl Output {
+    fn write_to(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        match self {
+            Self::Text(text) => writer.write_all(text.as_bytes()),
+            Self::Calendar(calendar) => calendar.write_to(writer),
+        }
+    }
+}
+
+#[derive(Default)]
+/// What a run found besides its output.
+struct Outcome {
+    changes: Vec<Changes>,
+    reminder: Option<Reminder>,
+    upcoming: Vec<DeliveryDate>,
+    empty: Vec<NorwegianPostalCode>,
+}
+
+/// Collect delivery dates from `source` and present them as `view`.
+async fn render_view(
+    view: View,
+    source: Source,
+    options: &Options,
+    mut history: Option<&mut History>,
+) -> Result<(Output, Outcome), Box<dyn Error>> {
+    let mut collected = collect_responses(source, options, history.as_deref_mut()).await?;
+    let outcome = Outcome {
+        changes: core::mem::take(&mut collected.changes),
+        reminder: if options.remind {
+            reminder_for(&collected)?
+        } else {
+            None
+        },
+        upcoming: if options.notify.is_empty() {
+            Vec::new()
+        } else {
+            upcoming_dates(&collected)?
+        },
+        empty: collected.empty.clone(),
+    };
+    collected.responses = filter_dates(&view, options, collected.responses)?;
+    let output = match view {
+        View::Format => render(options, collected, history)?,
+        View::Next { days_until } => Output::Text(render_next(days_until, options, &collected)?),
+        View::Stats => Output::Text(render_stats(&options.format, &collected)?),
+    };
+    Ok((output, outcome))
+}
+
+/// Write `output`, unless `--keep-on-empty` keeps the previous output
+/// because the source returned no delivery dates.
+fn publish(
+    options: &Options,
+    output: &Output,
+    empty: &[NorwegianPostalCode],
+) -> Result<(), Box<dyn Error>> {
+    let keep = options.keep_on_empty
+        && !empty.is_empty()
+        && options
+            .code
+            .iter()
+            .filter(|code| !options.has_own_calendar(**code))
+            .all(|code| empty.contains(code));
+    match &options.output {
+        Some(path) if keep => log::warn!("Keeping {}", path.display()),
+        path => write_output(path.as_deref(), options, |writer| output.write_to(writer))?,
+    }
+    Ok(())
+}
+
+/// Log level for the system loggers, from `level` or `RUST_LOG` if it is a
+/// plain level.
+#[cfg(any(feature = "journald", feature = "syslog"))]
+fn system_log_level(level: Option<log::LevelFilter>) -> log::LevelFilter {
+    level.unwrap_or_else(|| {
+        std::env::var("RUST_LOG")
+            .ok()
+            .and_then(|level| level.parse().ok())
+            .unwrap_or(log::LevelFilter::Info)
+    })
+}
+
+/// Language chosen with `--locale`, or from the environment.
+fn locale(options: &Options) -> Locale {
+    options
+        .locale
+        .unwrap_or_else(|| Locale::from_env(|var| std::env::var_os(var)))
+}
+
+/// Log level chosen with `-q` or `-v`.
+const fn verbosity(options: &Options) -> Option<log::LevelFilter> {
+    match (options.quiet, options.verbose) {
+        (true, _) => Some(log::LevelFilter::Error),
+        (false, 0) => None,
+        (false, 1) => Some(log::LevelFilter::Info),
+        (false, 2) => Some(log::LevelFilter::Debug),
+        (false, _) => Some(log::LevelFilter::Trace),
+    }
+}
+
+#[cfg(feature = "journald")]
+fn init_journald(level: Option<log::LevelFilter>) -> Result<(), String> {
+    systemd_journal_logger::JournalLog::new()
+        .map_err(|err| format!("journald: {err}"))?
+        .with_syslog_identifier("postgang".to_string())
+        .add_extra_field("POSTGANG_VERSION", VERSION)
+        .install()
+        .map_err(|err| format!("journald: {err}"))?;
+    log::set_max_level(system_log_level(level));
+    Ok(())
+}
+
+#[cfg(not(feature = "journald"))]
+fn init_journald(_level: Option<log::LevelFilter>) -> Result<(), String> {
+    Err("postgang was built without the journald feature".to_string())
+}
+
+#[cfg(feature = "syslog")]
+fn init_syslog(level: Option<log::LevelFilter>) -> Result<(), String> {
+    let formatter = syslog::Formatter3164 {
+        facility: syslog::Facility::LOG_DAEMON,
+        hostname: None,
+        process: "postgang".to_string(),
+        pid: std::process::id(),
+    };
+    let logger = syslog::unix(formatter).map_err(|err| format!("syslog: {err}"))?;
+    log::set_boxed_logger(Box::new(syslog::BasicLogger::new(logger)))
+        .map_err(|err| format!("syslog: {err}"))?;
+    log::set_max_level(system_log_level(level));
+    Ok(())
+}
+
+#[cfg(not(feature = "syslog"))]
+fn init_syslog(_level: Option<log::LevelFilter>) -> Result<(), String> {
+    Err("postgang was built without the syslog feature".to_string())
+}
+
+fn init_logger(options: &Options) -> Result<(), String> {
+    let level = verbosity(options);
+    match options.log_target {
+        LogTarget::Stderr => {}
+        LogTarget::Journald => return init_journald(level),
+        LogTarget::Syslog => return init_syslog(level),
+    }
+    let builder = |default: log::LevelFilter| {
+        let mut builder = match level {
+            Some(level) => {
+                let mut builder = env_logger::Builder::new();
+                builder.filter_level(level);
+                builder
+            }
+            None => env_logger::Builder::from_env(
+                env_logger::Env::default().default_filter_or(default.as_str()),
+            ),
+        };
+        if options.trace_http {
+            builder.filter_module("postgang::http_client", log::LevelFilter::Trace);
+        }
+        builder
+    };
+    let Some(path) = &options.log_file else {
+        builder(log::LevelFilter::Warn).init();
+        return Ok(());
+    };
+    let rotation = Rotation {
+        max_size: Some(options.log_max_size),
+        max_age: options.log_max_age,
+        keep: options.log_keep,
+    };
+    let file =
+        RotatingFile::open(path.clone(), rotation).map_err(|err| io_error_to_string(&err, path))?;
+    builder(log::LevelFilter::Info)
+        .target(env_logger::Target::Pipe(Box::new(file)))
+        .write_style(env_logger::WriteStyle::Never)
+        .init();
+    Ok(())
+}
+
+/// Export tracing spans with OTLP when `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+#[cfg(feature = "otel")]
+fn init_tracing() -> Result<Option<opentelemetry_sdk::trace::SdkTracerProvider>, String> {
+    use opentelemetry::trace::TracerProvider as _;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() {
+        return Ok(None);
+    }
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+        .map_err(|err| format!("OTLP: {err}"))?;
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name("postgang")
+                .build(),
+        )
+        .build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("postgang"));
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))
+        .map_err(|err| format!("OTLP: {err}"))?;
+    Ok(Some(provider))
+}
+
+/// Report panics and errors to Sentry when `SENTRY_DSN` is set.
+#[cfg(feature = "sentry")]
+fn init_sentry(cli: &Cli) -> Option<sentry::ClientInitGuard> {
+    std::env::var_os("SENTRY_DSN")?;
+    let guard = sentry::init(sentry::ClientOptions {
+        release: Some(VERSION.into()),
+        ..Default::default()
+    });
+    let source = match &cli.command {
+        Commands::Api(_) => "api",
+        Commands::File { .. } => "file",
+        Commands::Convert { .. } => "convert",
+        Commands::Import { .. } => "import",
+        Commands::History { .. } => "history",
+        Commands::Next { .. } => "next",
+        Commands::Config { .. } => "config",
+        Commands::Doctor { .. } => "doctor",
+        Commands::Generate { .. } => "generate",
+        Commands::Healthcheck { .. } => "healthcheck",
+        Commands::Lookup { .. } => "lookup",
+        Commands::Explain(_) => "explain",
+        Commands::Paths => "paths",
+        Commands::Setup => "setup",
+        Commands::Stats { .. } => "stats",
+        #[cfg(feature = "tui")]
+        Commands::Tui => "tui",
+        #[cfg(all(windows, feature = "windows-service"))]
+        Commands::Service { .. } => "service",
+    };
+    let postal_codes = cli.options.codes.clone();
+    sentry::configure_scope(|scope| {
+        scope.set_tag("source", source);
+        if let Commands::Api(_) = cli.command {
+            scope.set_tag("endpoint", "api.bring.com");
+        }
+        scope.set_tag("postal_code", postal_codes.join(","));
+    });
+    Some(guard)
+}
+
+fn main() -> ExitCode {
+    http_client::block_on(async_main())
+}
+
+async fn async_main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Err(err) = init_logger(&cli.options) {
+        eprintln!("{err}");
+        return ExitCode::FAILURE;
+    }
+    #[cfg(feature = "sentry")]
+    let _sentry = init_sentry(&cli);
+    #[cfg(feature = "otel")]
+    let tracer_provider = match init_tracing() {
+        Ok(provider) => provider,
         Err(err) => {
             log::error!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let error_format = cli.options.error_format.clone();
+    let locale = locale(&cli.options);
+    let empty_exit_code = cli.options.empty_exit_code;
+    let result = try_main(cli).await;
+    #[cfg(feature = "otel")]
+    if let Some(provider) = tracer_provider {
+        if let Err(err) = provider.shutdown() {
+            log::warn!("OTLP: {err}");
+        }
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            match error_format {
+                ErrorFormat::Text => log::error!("{}", locale.error(err.as_ref())),
+                ErrorFormat::Json => match serde_json::to_string(&ErrorReport::new(err.as_ref())) {
+                    Ok(report) => eprintln!("{report}"),
+                    Err(_) => log::error!("{err}"),
+                },
+            }
+            if let (Some(code), true) = (empty_exit_code, err.is::<EmptyResponse>()) {
+                return ExitCode::from(code);
+            }
+            // A later run is likely to succeed, so only report the others
+            #[cfg(feature = "sentry")]
+            if !ErrorReport::new(err.as_ref()).retryable {
+                sentry::capture_error(err.as_ref());
+            }
             ExitCode::FAILURE
         }
     }
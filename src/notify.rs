@@ -0,0 +1,905 @@
+//! Notifications about changed delivery dates.
+use alloc::collections::BTreeMap;
+use core::{
+    error::Error,
+    fmt::{self, Debug, Display, Write as _},
+    str::FromStr,
+};
+use std::{
+    io::Write as _,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+use crate::{
+    bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode},
+    calendar::{fill, month, placeholders, weekday},
+    history::Changes,
+    http_client::{self, Client, Url},
+    locale::Locale,
+};
+
+const SUBJECT: &str = "Postgang: endrede leveringsdager";
+
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+/// A token that is left out of debug output.
+pub struct Secret(pub String);
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Mail arrives today or tomorrow.
+pub struct Reminder {
+    pub delivery_date: DeliveryDate,
+    pub today: NaiveDate,
+}
+
+#[derive(Debug, Clone)]
+/// Something to notify about.
+pub enum Notification {
+    /// Delivery dates changed since the last run.
+    Changes(Vec<Changes>),
+    Reminder(Reminder),
+}
+
+#[derive(Clone, PartialEq, Eq)]
+/// A destination for change notifications.
+///
+/// Debug output only shows the origin of URLs, as the rest of a webhook URL
+/// is what grants access to it.
+///
+/// Notifiers are parsed from `kind:target` strings.
+///
+/// ```
+/// use postgang::notify::Notifier;
+///
+/// assert_eq!(
+///     "exec:echo changed".parse::<Notifier>().unwrap(),
+///     Notifier::Exec("echo changed".to_string())
+/// );
+/// assert!("webhook:https://example.com/hook".parse::<Notifier>().is_ok());
+/// assert!("webhook:not a url".parse::<Notifier>().is_err());
+/// assert!("pigeon:home".parse::<Notifier>().is_err());
+/// ```
+pub enum Notifier {
+    /// POST the changes as JSON to a URL.
+    Webhook(Url),
+    /// POST a text message to a push service topic URL, e.g. ntfy.
+    Push(Url),
+    /// Send a text message to an address with the local `sendmail`.
+    Email(String),
+    /// Run a command with `sh -c`.
+    ///
+    /// The changes are passed as JSON in `POSTGANG_CHANGES` and as text in
+    /// `POSTGANG_MESSAGE`.
+    Exec(String),
+    /// POST a message with the upcoming dates to a Slack incoming webhook.
+    Slack(Url),
+    /// POST an embed with the upcoming dates to a Discord webhook.
+    Discord(Url),
+    /// Send a text message to a chat with a Telegram bot.
+    Telegram { token: Secret, chat_id: String },
+    /// Show a desktop notification, given as just `desktop`.
+    #[cfg(feature = "desktop")]
+    Desktop,
+    /// Send a text message to a Matrix room.
+    Matrix {
+        homeserver: Url,
+        token: Secret,
+        room_id: String,
+    },
+    /// Send an email over SMTP, set up in the `[smtp]` section.
+    #[cfg(feature = "smtp")]
+    Smtp(Box<crate::smtp::Mailer>),
+}
+
+/// The origin of `url`, leaving out the path and query that may hold a
+/// secret.
+fn origin(url: &Url) -> String {
+    format!("{}/…", url.origin().ascii_serialization())
+}
+
+impl Debug for Notifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Webhook(url) => f.debug_tuple("Webhook").field(&origin(url)).finish(),
+            Self::Push(url) => f.debug_tuple("Push").field(&origin(url)).finish(),
+            Self::Email(address) => f.debug_tuple("Email").field(address).finish(),
+            Self::Exec(command) => f.debug_tuple("Exec").field(command).finish(),
+            Self::Slack(url) => f.debug_tuple("Slack").field(&origin(url)).finish(),
+            Self::Discord(url) => f.debug_tuple("Discord").field(&origin(url)).finish(),
+            Self::Telegram { token, chat_id } => f
+                .debug_struct("Telegram")
+                .field("token", token)
+                .field("chat_id", chat_id)
+                .finish(),
+            #[cfg(feature = "desktop")]
+            Self::Desktop => f.write_str("Desktop"),
+            Self::Matrix {
+                homeserver,
+                token,
+                room_id,
+            } => f
+                .debug_struct("Matrix")
+                .field("homeserver", &origin(homeserver))
+                .field("token", token)
+                .field("room_id", room_id)
+                .finish(),
+            #[cfg(feature = "smtp")]
+            Self::Smtp(mailer) => f.debug_tuple("Smtp").field(mailer).finish(),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// A possible error when parsing a [`Notifier`] from a string.
+pub struct InvalidNotifier(String);
+
+impl Display for InvalidNotifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for InvalidNotifier {}
+
+impl FromStr for Notifier {
+    type Err = InvalidNotifier;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "desktop" {
+            #[cfg(feature = "desktop")]
+            return Ok(Self::Desktop);
+            #[cfg(not(feature = "desktop"))]
+            return Err(InvalidNotifier(
+                "Desktop notifications need the desktop feature".to_string(),
+            ));
+        }
+        let (kind, target) = s
+            .split_once(':')
+            .ok_or_else(|| InvalidNotifier(format!("Expected kind:target, got {s:?}")))?;
+        let url = |target: &str| {
+            Url::parse(target).map_err(|err| InvalidNotifier(format!("{err}: {target:?}")))
+        };
+        match kind {
+            "webhook" => Ok(Self::Webhook(url(target)?)),
+            "push" => Ok(Self::Push(url(target)?)),
+            "email" => Ok(Self::Email(target.to_string())),
+            "exec" => Ok(Self::Exec(target.to_string())),
+            "slack" => Ok(Self::Slack(url(target)?)),
+            "discord" => Ok(Self::Discord(url(target)?)),
+            "telegram" | "matrix" | "smtp" => Err(InvalidNotifier(format!(
+                "{kind} is set up in the [{kind}] section of the configuration file"
+            ))),
+            _ => Err(InvalidNotifier(format!(
+                "Unknown notifier {kind:?}, expected one of webhook, push, email, exec, slack, discord"
+            ))),
+        }
+    }
+}
+
+#[must_use]
+/// Describe `changes` as a short text message.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::history::Changes;
+/// use postgang::notify::message;
+///
+/// let changes = Changes {
+///     postal_code: NorwegianPostalCode::try_from("7800").unwrap(),
+///     added: vec![NaiveDate::from_ymd_opt(2024, 9, 2).unwrap()],
+///     removed: vec![NaiveDate::from_ymd_opt(2024, 9, 4).unwrap()],
+/// };
+/// assert_eq!(
+///     message(&[changes]),
+///     "7800: nye leveringsdager 2024-09-02\n7800: fjernede leveringsdager 2024-09-04\n"
+/// );
+/// ```
+pub fn message(changes: &[Changes]) -> String {
+    let mut message = String::new();
+    for change in changes {
+        for (label, dates) in [("nye", &change.added), ("fjernede", &change.removed)] {
+            if dates.is_empty() {
+                continue;
+            }
+            let dates: Vec<_> = dates.iter().map(ToString::to_string).collect();
+            // Writing to a String can't fail
+            let _ = writeln!(
+                message,
+                "{}: {label} leveringsdager {}",
+                change.postal_code,
+                dates.join(", ")
+            );
+        }
+    }
+    message
+}
+
+#[derive(Debug, Clone, Default)]
+/// Templates of the notification messages, replacing the built-in text.
+pub struct Templates {
+    /// A line per postal code with changes, with the placeholders of
+    /// [`format_changes`].
+    pub changes: Option<String>,
+    /// A reminder, with the placeholders of
+    /// [`format_summary`](crate::calendar::format_summary) and `{place}`.
+    pub reminder: Option<String>,
+    /// Aliases by postal code, for `{name}`.
+    pub names: BTreeMap<NorwegianPostalCode, String>,
+    /// Post places by postal code, for `{place}`.
+    pub places: BTreeMap<NorwegianPostalCode, String>,
+    /// Language of weekdays and months.
+    pub locale: Locale,
+}
+
+impl Templates {
+    fn name(&self, postal_code: NorwegianPostalCode) -> String {
+        self.names
+            .get(&postal_code)
+            .cloned()
+            .unwrap_or_else(|| postal_code.to_string())
+    }
+
+    fn place(&self, postal_code: NorwegianPostalCode) -> String {
+        self.places.get(&postal_code).cloned().unwrap_or_default()
+    }
+
+    #[must_use]
+    /// Whether a template has `{place}`, so the post places are needed.
+    pub fn uses_place(&self) -> bool {
+        [&self.changes, &self.reminder]
+            .into_iter()
+            .flatten()
+            .any(|template| template.contains("{place}"))
+    }
+}
+
+#[must_use]
+/// Fill in the placeholders of `template` for `changes`, with the names,
+/// post places and locale of `templates`.
+///
+/// The placeholders are `{code}`, `{name}`, the post `{place}`, empty if not
+/// known, the `{added}` and `{removed}` dates, separated by commas, and the
+/// `{next}` delivery date, empty if not known. The placeholders of
+/// [`format_summary`](crate::calendar::format_summary) are filled in for the
+/// next delivery date.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::history::Changes;
+/// use postgang::locale::Locale;
+/// use postgang::notify::{format_changes, Templates};
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let changes = Changes {
+///     postal_code,
+///     added: vec![NaiveDate::from_ymd_opt(2024, 9, 2).unwrap()],
+///     removed: vec![],
+/// };
+/// let templates = Templates {
+///     names: [(postal_code, "home".to_string())].into(),
+///     places: [(postal_code, "NAMSOS".to_string())].into(),
+///     locale: Locale::English,
+///     ..Templates::default()
+/// };
+/// assert_eq!(
+///     format_changes("{name} in {place}: new {added}, next {next}", &changes, &templates, changes.added.first().copied()),
+///     "home in NAMSOS: new Monday 2 September, next Monday 2 September"
+/// );
+/// ```
+pub fn format_changes(
+    template: &str,
+    changes: &Changes,
+    templates: &Templates,
+    next: Option<NaiveDate>,
+) -> String {
+    let locale = templates.locale;
+    let dates = |dates: &[NaiveDate]| {
+        dates
+            .iter()
+            .map(|date| locale.date(*date))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let postal_code = changes.postal_code;
+    let name = templates.name(postal_code);
+    let mut values = match next {
+        Some(next) => placeholders(DeliveryDate::new(postal_code, next), &name, locale),
+        None => vec![("code", postal_code.to_string()), ("name", name)],
+    };
+    values.extend([
+        ("place", templates.place(postal_code)),
+        ("added", dates(&changes.added)),
+        ("removed", dates(&changes.removed)),
+        (
+            "next",
+            next.map(|date| locale.date(date)).unwrap_or_default(),
+        ),
+    ]);
+    fill(template, &values)
+}
+
+#[must_use]
+/// List `upcoming` delivery dates, one per line.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode};
+/// use postgang::notify::upcoming_lines;
+///
+/// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+/// let date = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+/// assert_eq!(
+///     upcoming_lines(&[DeliveryDate::new(postal_code, date)]),
+///     "7800: mandag 2. september\n"
+/// );
+/// ```
+pub fn upcoming_lines(upcoming: &[DeliveryDate]) -> String {
+    let mut lines = String::new();
+    for delivery_date in upcoming {
+        let date = delivery_date.date;
+        // Writing to a String can't fail
+        let _ = writeln!(
+            lines,
+            "{}: {} {}. {}",
+            delivery_date.postal_code,
+            weekday(date),
+            date.day(),
+            month(date)
+        );
+    }
+    lines
+}
+
+/// JSON posted to a webhook: the changes, or the date of a reminder.
+fn webhook_payload(notification: &Notification) -> Result<serde_json::Value, serde_json::Error> {
+    Ok(match notification {
+        Notification::Changes(changes) => serde_json::to_value(changes)?,
+        Notification::Reminder(reminder) => serde_json::json!({
+            "reminder": {
+                "postal_code": reminder.delivery_date.postal_code,
+                "date": reminder.delivery_date.date,
+            }
+        }),
+    })
+}
+
+async fn post_json(
+    client: &Client,
+    url: &Url,
+    body: &serde_json::Value,
+) -> Result<(), http_client::Error> {
+    client
+        .post(url.clone())
+        .json(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Slack message with blocks for the notification and the upcoming dates.
+fn slack_payload(
+    notification: &Notification,
+    message: &str,
+    upcoming: &[DeliveryDate],
+) -> serde_json::Value {
+    let mut blocks = vec![
+        serde_json::json!({
+            "type": "header",
+            "text": {"type": "plain_text", "text": notification.subject()},
+        }),
+        serde_json::json!({
+            "type": "section",
+            "text": {"type": "mrkdwn", "text": message},
+        }),
+    ];
+    if !upcoming.is_empty() {
+        blocks.push(serde_json::json!({
+            "type": "section",
+            "text": {
+                "type": "mrkdwn",
+                "text": format!("*Neste leveringsdager*\n{}", upcoming_lines(upcoming)),
+            },
+        }));
+    }
+    serde_json::json!({"text": message, "blocks": blocks})
+}
+
+/// Discord message with an embed for the notification and the upcoming dates.
+fn discord_payload(
+    notification: &Notification,
+    message: &str,
+    upcoming: &[DeliveryDate],
+) -> serde_json::Value {
+    let mut embed = serde_json::json!({
+        "title": notification.subject(),
+        "description": message,
+    });
+    if !upcoming.is_empty() {
+        embed["fields"] = serde_json::json!([{
+            "name": "Neste leveringsdager",
+            "value": upcoming_lines(upcoming),
+        }]);
+    }
+    serde_json::json!({"embeds": [embed]})
+}
+
+/// Send `message` to `address` with the local `sendmail`.
+fn sendmail(address: &str, subject: &str, message: &str) -> Result<(), Box<dyn Error>> {
+    let mut child = Command::new("sendmail")
+        .arg("-t")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("sendmail: {err}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        write!(
+            stdin,
+            "To: {address}\r\nSubject: {subject}\r\nMIME-Version: 1.0\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\r\n{message}"
+        )?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(format!("sendmail: {status}").into());
+    }
+    Ok(())
+}
+
+/// URL for sending a message event to `room_id`, with the transaction id
+/// `transaction`.
+fn matrix_send_url(homeserver: &Url, room_id: &str, transaction: u64) -> Result<Url, String> {
+    let mut url = homeserver.clone();
+    url.path_segments_mut()
+        .map_err(|()| format!("Invalid Matrix homeserver: {homeserver}"))?
+        .pop_if_empty()
+        .extend([
+            "_matrix",
+            "client",
+            "v3",
+            "rooms",
+            room_id,
+            "send",
+            "m.room.message",
+        ])
+        .push(&format!("postgang-{transaction}"));
+    Ok(url)
+}
+
+impl Notification {
+    #[must_use]
+    /// Subject line for notifiers that have one.
+    pub fn subject(&self) -> &'static str {
+        match self {
+            Self::Changes(_) => SUBJECT,
+            Self::Reminder(reminder) if reminder.delivery_date.date == reminder.today => {
+                "Postgang: posten kommer i dag"
+            }
+            Self::Reminder(_) => "Postgang: posten kommer i morgen",
+        }
+    }
+
+    #[must_use]
+    /// The notification as a short text message.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode};
+    /// use postgang::notify::{Notification, Reminder};
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+    /// let reminder = Reminder {
+    ///     delivery_date: DeliveryDate::new(postal_code, date),
+    ///     today: date.pred_opt().unwrap(),
+    /// };
+    /// assert_eq!(
+    ///     Notification::Reminder(reminder).message(),
+    ///     "7800: Posten kommer i morgen.\n"
+    /// );
+    /// ```
+    pub fn message(&self) -> String {
+        self.format(&Templates::default(), &[])
+    }
+
+    #[must_use]
+    /// The notification as a short text message from `templates`, with the
+    /// next of the `upcoming` delivery dates for `{next}`.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode};
+    /// use postgang::history::Changes;
+    /// use postgang::locale::Locale;
+    /// use postgang::notify::{Notification, Templates};
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let date = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+    /// let templates = Templates {
+    ///     changes: Some("Mail to {name} on {added}".to_string()),
+    ///     reminder: Some("Mail to {name} on {weekday}".to_string()),
+    ///     names: [(postal_code, "the cabin".to_string())].into(),
+    ///     places: Default::default(),
+    ///     locale: Locale::English,
+    /// };
+    /// let changes = Changes { postal_code, added: vec![date], removed: vec![] };
+    /// assert_eq!(
+    ///     Notification::Changes(vec![changes]).format(&templates, &[]),
+    ///     "Mail to the cabin on Monday 2 September\n"
+    /// );
+    /// ```
+    pub fn format(&self, templates: &Templates, upcoming: &[DeliveryDate]) -> String {
+        match (self, templates) {
+            (
+                Self::Changes(changes),
+                Templates {
+                    changes: Some(template),
+                    ..
+                },
+            ) => changes
+                .iter()
+                .filter(|changes| !changes.is_empty())
+                .map(|changes| {
+                    let next = upcoming
+                        .iter()
+                        .find(|upcoming| upcoming.postal_code == changes.postal_code)
+                        .map(|upcoming| upcoming.date);
+                    format_changes(template, changes, templates, next) + "\n"
+                })
+                .collect(),
+            (Self::Changes(changes), _) => message(changes),
+            (
+                Self::Reminder(reminder),
+                Templates {
+                    reminder: Some(template),
+                    ..
+                },
+            ) => {
+                let postal_code = reminder.delivery_date.postal_code;
+                let name = templates.name(postal_code);
+                let mut values = placeholders(reminder.delivery_date, &name, templates.locale);
+                values.push(("place", templates.place(postal_code)));
+                fill(template, &values) + "\n"
+            }
+            (Self::Reminder(reminder), _) => {
+                let summary = templates
+                    .locale
+                    .relative_summary(reminder.delivery_date, reminder.today);
+                format!("{summary}\n")
+            }
+        }
+    }
+
+    /// The changes as JSON, an empty list for a reminder.
+    fn changes_json(&self) -> Result<String, serde_json::Error> {
+        match self {
+            Self::Changes(changes) => serde_json::to_string(changes),
+            Self::Reminder(_) => Ok("[]".to_string()),
+        }
+    }
+}
+
+impl Notifier {
+    /// Send `notification` as text from `templates`, with the `upcoming`
+    /// delivery dates for notifiers that show them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the notification could not be delivered.
+    pub async fn notify(
+        &self,
+        client: &Client,
+        notification: &Notification,
+        templates: &Templates,
+        upcoming: &[DeliveryDate],
+    ) -> Result<(), Box<dyn Error>> {
+        let message = notification.format(templates, upcoming);
+        match self {
+            Self::Webhook(url) => post_json(client, url, &webhook_payload(notification)?).await?,
+            Self::Push(url) => {
+                client
+                    .post(url.clone())
+                    .header("Title", notification.subject())
+                    .body(message)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            Self::Email(address) => sendmail(address, notification.subject(), &message)?,
+            #[cfg(feature = "smtp")]
+            Self::Smtp(mailer) => mailer.send(notification.subject(), &message)?,
+            Self::Exec(command) => {
+                let status = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .env("POSTGANG_CHANGES", notification.changes_json()?)
+                    .env("POSTGANG_MESSAGE", message)
+                    .status()
+                    .map_err(|err| format!("{command}: {err}"))?;
+                if !status.success() {
+                    return Err(format!("{command}: {status}").into());
+                }
+            }
+            Self::Slack(url) => {
+                post_json(
+                    client,
+                    url,
+                    &slack_payload(notification, &message, upcoming),
+                )
+                .await?;
+            }
+            Self::Discord(url) => {
+                post_json(
+                    client,
+                    url,
+                    &discord_payload(notification, &message, upcoming),
+                )
+                .await?;
+            }
+            Self::Telegram { token, chat_id } => {
+                client
+                    .post(format!(
+                        "https://api.telegram.org/bot{}/sendMessage",
+                        token.0
+                    ))
+                    .json(&serde_json::json!({
+                        "chat_id": chat_id,
+                        "text": message,
+                    }))
+                    .send()
+                    .await
+                    // The URL contains the token
+                    .map_err(http_client::Error::without_url)?
+                    .error_for_status()
+                    .map_err(http_client::Error::without_url)?;
+            }
+            #[cfg(feature = "desktop")]
+            Self::Desktop => {
+                notify_rust::Notification::new()
+                    .appname("postgang")
+                    .summary(notification.subject())
+                    .body(message.trim_end())
+                    .show()?;
+            }
+            Self::Matrix {
+                homeserver,
+                token,
+                room_id,
+            } => {
+                client
+                    .put(matrix_send_url(homeserver, room_id, fastrand::u64(..))?)
+                    .bearer_auth(&token.0)
+                    .json(&serde_json::json!({
+                        "msgtype": "m.text",
+                        "body": message,
+                    }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run `command` with `sh -c` after the `files` were changed.
+///
+/// The changed files are passed one per line in `POSTGANG_FILES`, and the
+/// changed dates as JSON in `POSTGANG_CHANGES` and as text in
+/// `POSTGANG_MESSAGE`.
+///
+/// # Errors
+///
+/// Returns an error if the command could not be run or failed.
+pub fn run_hook(
+    command: &str,
+    changes: &[Changes],
+    files: &[PathBuf],
+) -> Result<(), Box<dyn Error>> {
+    let files: Vec<_> = files
+        .iter()
+        .map(|file| file.display().to_string())
+        .collect();
+    let changes: Vec<_> = changes.iter().filter(|c| !c.is_empty()).cloned().collect();
+    log::debug!("Running {command:?} for {files:?}");
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("POSTGANG_FILES", files.join("\n"))
+        .env("POSTGANG_CHANGES", serde_json::to_string(&changes)?)
+        .env("POSTGANG_MESSAGE", message(&changes))
+        .status()
+        .map_err(|err| format!("{command}: {err}"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{command}: {status}").into())
+    }
+}
+
+/// Send `changes` and a reminder, if any, to all `notifiers` as text from
+/// `templates`, skipping empty changes. Some notifiers also show the
+/// `upcoming` delivery dates.
+///
+/// Every notifier is tried even if an earlier one fails.
+///
+/// # Errors
+///
+/// Returns the first error if any notifier failed.
+pub async fn notify_all(
+    notifiers: &[Notifier],
+    changes: &[Changes],
+    reminder: Option<Reminder>,
+    templates: &Templates,
+    upcoming: &[DeliveryDate],
+) -> Result<(), Box<dyn Error>> {
+    let changes: Vec<_> = changes.iter().filter(|c| !c.is_empty()).cloned().collect();
+    let notifications: Vec<_> = (!changes.is_empty())
+        .then_some(Notification::Changes(changes))
+        .into_iter()
+        .chain(reminder.map(Notification::Reminder))
+        .collect();
+    if notifications.is_empty() || notifiers.is_empty() {
+        return Ok(());
+    }
+    let client = Client::new();
+    let mut first_error = None;
+    for notification in &notifications {
+        for notifier in notifiers {
+            log::debug!("Notifying {notifier:?}");
+            let result = notifier
+                .notify(&client, notification, templates, upcoming)
+                .await;
+            if let Err(err) = result {
+                log::error!("Notification failed: {err}");
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::{
+        discord_payload, matrix_send_url, slack_payload, Notification, Notifier, Reminder,
+        Templates,
+    };
+    use crate::{
+        bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode},
+        history::Changes,
+        http_client::Url,
+        locale::Locale,
+    };
+
+    #[test]
+    fn test_debug_redacted() {
+        let notifiers: Vec<Notifier> = [
+            "slack:https://hooks.slack.com/services/T000/B000/SECRET",
+            "discord:https://discord.com/api/webhooks/1/SECRET",
+            "webhook:https://example.com/hook?token=SECRET",
+            "push:https://ntfy.sh/SECRET",
+        ]
+        .iter()
+        .map(|notifier| notifier.parse().unwrap())
+        .collect();
+        let debug = format!("{notifiers:?}");
+        assert!(!debug.contains("SECRET"), "{debug}");
+        assert!(debug.contains("https://hooks.slack.com/…"), "{debug}");
+        let matrix = Notifier::Matrix {
+            homeserver: Url::parse("https://matrix.example.com").unwrap(),
+            token: super::Secret("SECRET".to_string()),
+            room_id: "!abc:example.com".to_string(),
+        };
+        assert!(!format!("{matrix:?}").contains("SECRET"));
+    }
+
+    #[test]
+    fn test_format() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(2024, 9, 3).unwrap());
+        let changes = Notification::Changes(vec![
+            Changes {
+                postal_code,
+                added: vec![],
+                removed: vec![NaiveDate::from_ymd_opt(2024, 9, 2).unwrap()],
+            },
+            Changes {
+                postal_code: NorwegianPostalCode::try_from("2450").unwrap(),
+                added: vec![],
+                removed: vec![],
+            },
+        ]);
+        let reminder = Notification::Reminder(Reminder {
+            delivery_date: date,
+            today: date.date,
+        });
+        let templates = Templates {
+            changes: Some("{code}: -{removed} +{added}, next {next}".to_string()),
+            reminder: Some("{name} i {place}: {weekday} {day}. {month}".to_string()),
+            names: [(postal_code, "hjemme".to_string())].into(),
+            places: [(postal_code, "NAMSOS".to_string())].into(),
+            locale: Locale::Norwegian,
+        };
+        assert_eq!(
+            changes.format(&templates, &[date]),
+            "7800: -mandag 2. september +, next tirsdag 3. september\n"
+        );
+        assert_eq!(
+            reminder.format(&templates, &[]),
+            "hjemme i NAMSOS: tirsdag 3. september\n"
+        );
+        assert_eq!(
+            reminder.format(&Templates::default(), &[]),
+            reminder.message()
+        );
+        let english = Templates {
+            locale: Locale::English,
+            ..Templates::default()
+        };
+        assert_eq!(
+            reminder.format(&english, &[]),
+            "7800: Mail arrives today.\n"
+        );
+    }
+
+    #[test]
+    fn test_slack_payload() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(2024, 9, 3).unwrap());
+        let reminder = Notification::Reminder(Reminder {
+            delivery_date: date,
+            today: date.date.pred_opt().unwrap(),
+        });
+        let payload = slack_payload(&reminder, &reminder.message(), &[date]);
+        assert_eq!(payload["text"], "7800: Posten kommer i morgen.\n");
+        assert_eq!(payload["blocks"][0]["type"], "header");
+        assert_eq!(
+            payload["blocks"][2]["text"]["text"],
+            "*Neste leveringsdager*\n7800: tirsdag 3. september\n"
+        );
+        let payload = slack_payload(&reminder, "", &[]);
+        assert_eq!(payload["blocks"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_discord_payload() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let date = DeliveryDate::new(postal_code, NaiveDate::from_ymd_opt(2024, 9, 3).unwrap());
+        let reminder = Notification::Reminder(Reminder {
+            delivery_date: date,
+            today: date.date,
+        });
+        let payload = discord_payload(&reminder, &reminder.message(), &[date]);
+        let embed = &payload["embeds"][0];
+        assert_eq!(embed["title"], "Postgang: posten kommer i dag");
+        assert_eq!(embed["description"], "7800: Posten kommer i dag.\n");
+        assert_eq!(embed["fields"][0]["value"], "7800: tirsdag 3. september\n");
+        let payload = discord_payload(&reminder, "", &[]);
+        assert!(payload["embeds"][0].get("fields").is_none());
+    }
+
+    #[test]
+    fn test_matrix_send_url() {
+        let homeserver = Url::parse("https://matrix.example.com/").unwrap();
+        assert_eq!(
+            matrix_send_url(&homeserver, "!abc:example.com", 42)
+                .unwrap()
+                .as_str(),
+            "https://matrix.example.com/_matrix/client/v3/rooms/!abc:example.com/send/m.room.message/postgang-42"
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! Default file locations following the
+//! [XDG base directory specification](https://specifications.freedesktop.org/basedir-spec/latest/).
+use core::fmt;
+use std::{
+    ffi::OsString,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use crate::{config::Config, io_error_to_string};
+
+const APP: &str = "postgang";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Where postgang keeps its files.
+///
+/// Each location can be overridden with a `POSTGANG_*` variable or in the
+/// [`Config`], and is `None` when none of them, the XDG variable nor `HOME`
+/// is set.
+pub struct Paths {
+    /// Cached API responses, `$POSTGANG_CACHE_DIR` or `$XDG_CACHE_HOME/postgang`.
+    pub cache_dir: Option<PathBuf>,
+    /// History of delivery dates, `$POSTGANG_HISTORY` or
+    /// `$XDG_STATE_HOME/postgang/history.json`.
+    pub history: Option<PathBuf>,
+    /// Lock held while the history is updated, `$POSTGANG_LOCK` or
+    /// `$XDG_STATE_HOME/postgang/postgang.lock`.
+    pub lock: Option<PathBuf>,
+}
+
+/// Resolve an XDG base directory, falling back to `fallback` below `HOME`.
+///
+/// Relative paths in the variable are invalid and ignored, as the
+/// specification requires.
+pub(crate) fn base_dir(
+    env: &impl Fn(&str) -> Option<OsString>,
+    var: &str,
+    fallback: &str,
+) -> Option<PathBuf> {
+    env(var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            env("HOME")
+                .filter(|home| !home.is_empty())
+                .map(|home| Path::new(&home).join(fallback))
+        })
+        .map(|dir| dir.join(APP))
+}
+
+impl Paths {
+    #[must_use]
+    /// Resolve the locations from the environment of this process and `config`.
+    pub fn from_env(config: &Config) -> Self {
+        Self::with_config(|var| std::env::var_os(var), config)
+    }
+
+    #[must_use]
+    /// Resolve the locations using `env` to look up variables.
+    ///
+    /// ```
+    /// use std::path::PathBuf;
+    ///
+    /// use postgang::paths::Paths;
+    ///
+    /// let paths = Paths::new(|var| match var {
+    ///     "HOME" => Some("/home/ola".into()),
+    ///     "XDG_CACHE_HOME" => Some("/var/cache".into()),
+    ///     _ => None,
+    /// });
+    /// assert_eq!(paths.cache_dir, Some(PathBuf::from("/var/cache/postgang")));
+    /// assert_eq!(
+    ///     paths.history,
+    ///     Some(PathBuf::from("/home/ola/.local/state/postgang/history.json"))
+    /// );
+    /// ```
+    pub fn new(env: impl Fn(&str) -> Option<OsString>) -> Self {
+        Self::with_config(env, &Config::default())
+    }
+
+    #[must_use]
+    /// Resolve the locations using `env` to look up variables, preferring
+    /// locations set in `config` over the XDG directories.
+    pub fn with_config(env: impl Fn(&str) -> Option<OsString>, config: &Config) -> Self {
+        let state_dir = base_dir(&env, "XDG_STATE_HOME", ".local/state");
+        let var = |var| {
+            env(var)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+        Self {
+            cache_dir: var("POSTGANG_CACHE_DIR")
+                .or_else(|| config.cache_dir.clone())
+                .or_else(|| base_dir(&env, "XDG_CACHE_HOME", ".cache")),
+            history: var("POSTGANG_HISTORY")
+                .or_else(|| config.history.clone())
+                .or_else(|| state_dir.as_ref().map(|dir| dir.join("history.json"))),
+            lock: var("POSTGANG_LOCK").or_else(|| state_dir.map(|dir| dir.join("postgang.lock"))),
+        }
+    }
+}
+
+impl fmt::Display for Paths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, path) in [
+            ("cache", &self.cache_dir),
+            ("history", &self.history),
+            ("lock", &self.lock),
+        ] {
+            match path {
+                Some(path) => writeln!(f, "{name}: {}", path.display())?,
+                None => writeln!(f, "{name}: -")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Take an exclusive lock on `path`, waiting for other processes to release it.
+///
+/// The lock is held until the returned file is dropped.
+///
+/// # Errors
+///
+/// Returns an error if the file could not be created or locked.
+pub fn lock(path: &Path) -> Result<File, String> {
+    if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|err| io_error_to_string(&err, parent))?;
+    }
+    let file = File::create(path).map_err(|err| io_error_to_string(&err, path))?;
+    file.lock().map_err(|err| io_error_to_string(&err, path))?;
+    Ok(file)
+}
+
+#[must_use]
+/// The file next to `path` holding the month `year`-`month`, with the month
+/// added to the file name before the extension.
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+///
+/// use postgang::paths::month_path;
+///
+/// assert_eq!(
+///     month_path(Path::new("/var/www/post.ics"), 2024, 9),
+///     PathBuf::from("/var/www/post-2024-09.ics")
+/// );
+/// assert_eq!(month_path(Path::new("post"), 2024, 12), PathBuf::from("post-2024-12"));
+/// ```
+pub fn month_path(path: &Path, year: i32, month: u32) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{year}-{month:02}"));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+#[must_use]
+/// Find the id of `group` in the contents of `/etc/group`.
+///
+/// ```
+/// use postgang::paths::find_group;
+///
+/// let groups = "root:x:0:\nwww-data:x:33:\n";
+/// assert_eq!(find_group(groups, "www-data"), Some(33));
+/// assert_eq!(find_group(groups, "nginx"), None);
+/// ```
+pub fn find_group(groups: &str, group: &str) -> Option<u32> {
+    groups.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != group {
+            return None;
+        }
+        fields.nth(1)?.parse().ok()
+    })
+}
+
+/// Look up the id of `group`, given by name or number.
+///
+/// # Errors
+///
+/// Returns an error if there is no such group in `/etc/group`.
+pub fn group_id(group: &str) -> Result<u32, String> {
+    const GROUP_FILE: &str = "/etc/group";
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+    let groups = std::fs::read_to_string(GROUP_FILE)
+        .map_err(|err| io_error_to_string(&err, Path::new(GROUP_FILE)))?;
+    find_group(&groups, group).ok_or_else(|| format!("Unknown group {group}"))
+}
+
+/// Give the file at `path` the permissions `mode` and the group `gid`.
+///
+/// # Errors
+///
+/// Returns an error if the permissions or ownership could not be changed.
+#[cfg(unix)]
+pub fn set_access(path: &Path, mode: Option<u32>, gid: Option<u32>) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    if let Some(gid) = gid {
+        std::os::unix::fs::chown(path, None, Some(gid))
+            .map_err(|err| io_error_to_string(&err, path))?;
+    }
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .map_err(|err| io_error_to_string(&err, path))?;
+    }
+    Ok(())
+}
+
+/// Permissions and groups are Unix only, so elsewhere the file keeps the
+/// access it was created with.
+///
+/// # Errors
+///
+/// Never, the signature matches the Unix version.
+#[cfg(not(unix))]
+pub fn set_access(path: &Path, mode: Option<u32>, gid: Option<u32>) -> Result<(), String> {
+    if mode.is_some() || gid.is_some() {
+        log::warn!(
+            "Permissions and groups are not supported here: {}",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{ffi::OsString, path::PathBuf};
+
+    use super::Paths;
+    use crate::config::Config;
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<OsString> + 'a {
+        |var| {
+            vars.iter()
+                .find(|(name, _)| *name == var)
+                .map(|(_, value)| OsString::from(value))
+        }
+    }
+
+    #[test]
+    fn test_xdg() {
+        let paths = Paths::new(env(&[
+            ("HOME", "/home/ola"),
+            ("XDG_CACHE_HOME", "/tmp/cache"),
+            ("XDG_STATE_HOME", "/tmp/state"),
+        ]));
+        assert_eq!(paths.cache_dir, Some(PathBuf::from("/tmp/cache/postgang")));
+        assert_eq!(
+            paths.history,
+            Some(PathBuf::from("/tmp/state/postgang/history.json"))
+        );
+        assert_eq!(
+            paths.lock,
+            Some(PathBuf::from("/tmp/state/postgang/postgang.lock"))
+        );
+    }
+
+    #[test]
+    fn test_fallback() {
+        let paths = Paths::new(env(&[("HOME", "/home/ola"), ("XDG_CACHE_HOME", "cache")]));
+        assert_eq!(
+            paths.cache_dir,
+            Some(PathBuf::from("/home/ola/.cache/postgang"))
+        );
+        let paths = Paths::new(env(&[("POSTGANG_HISTORY", "history.json")]));
+        assert_eq!(paths.history, Some(PathBuf::from("history.json")));
+        assert_eq!(paths.lock, None);
+        let config = Config {
+            history: Some(PathBuf::from("/var/lib/postgang/history.json")),
+            ..Config::default()
+        };
+        let paths = Paths::with_config(env(&[("HOME", "/home/ola")]), &config);
+        assert_eq!(paths.history, config.history);
+        let paths = Paths::with_config(env(&[("POSTGANG_HISTORY", "history.json")]), &config);
+        assert_eq!(paths.history, Some(PathBuf::from("history.json")));
+        assert_eq!(
+            Paths::new(env(&[])),
+            Paths {
+                cache_dir: None,
+                history: None,
+                lock: None
+            }
+        );
+    }
+}
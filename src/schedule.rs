@@ -0,0 +1,287 @@
+//! When the Windows service runs postgang.
+use core::{fmt, str::FromStr, time::Duration};
+
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone};
+
+/// Days searched for the next time of a cron expression, enough to reach
+/// the next 29 February.
+const SEARCH_DAYS: u32 = 8 * 366;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// How often postgang runs.
+///
+/// Parsed from a duration between runs, e.g. `6h`, from local times of day,
+/// e.g. `05:30,17:00`, or from a cron expression, e.g. `0 5 * * *`.
+///
+/// ```
+/// use core::time::Duration;
+///
+/// use postgang::schedule::Schedule;
+///
+/// assert_eq!("6h".parse::<Schedule>().unwrap(), Schedule::Every(Duration::from_hours(6)));
+/// assert_eq!("05:30,17:00".parse::<Schedule>().unwrap().to_string(), "05:30,17:00");
+/// assert_eq!("0 5 * * 1-5".parse::<Schedule>().unwrap().to_string(), "0 5 * * 1-5");
+/// assert!("0 25 * * *".parse::<Schedule>().is_err());
+/// assert!("25:00".parse::<Schedule>().is_err());
+/// ```
+pub enum Schedule {
+    /// This long after the previous run.
+    Every(Duration),
+    /// At these times every day.
+    At(Vec<NaiveTime>),
+    /// When a cron expression matches.
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// The first time to run after `after`, `None` if there is none.
+    ///
+    /// Times skipped by a daylight saving time change are skipped, and times
+    /// repeated by one run the first time.
+    pub fn next<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        match self {
+            Self::Every(interval) => {
+                Some(after.clone() + chrono::Duration::from_std(*interval).ok()?)
+            }
+            Self::At(times) => (0..=2).find_map(|days| {
+                let date = after.date_naive() + chrono::Days::new(days);
+                times
+                    .iter()
+                    .filter_map(|time| local(after, date.and_time(*time)))
+                    .filter(|time| time > after)
+                    .min()
+            }),
+            Self::Cron(cron) => cron.next(after),
+        }
+    }
+}
+
+/// `datetime` in the time zone of `reference`.
+fn local<Tz: TimeZone>(
+    reference: &DateTime<Tz>,
+    datetime: chrono::NaiveDateTime,
+) -> Option<DateTime<Tz>> {
+    reference
+        .timezone()
+        .from_local_datetime(&datetime)
+        .earliest()
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.split_whitespace().count() > 1 {
+            return s.parse().map(Self::Cron);
+        }
+        if s.contains(':') {
+            let mut times = s
+                .split(',')
+                .map(|time| {
+                    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                        .map_err(|err| format!("{err}: {time:?}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            times.sort_unstable();
+            times.dedup();
+            return Ok(Self::At(times));
+        }
+        match humantime::parse_duration(s) {
+            Ok(interval) if interval.is_zero() => Err("The interval can't be 0".to_string()),
+            Ok(interval) => Ok(Self::Every(interval)),
+            Err(err) => Err(format!(
+                "{err}: {s:?}, expected a duration, times like 05:30 or a cron expression"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Every(interval) => write!(f, "{}", humantime::format_duration(*interval)),
+            Self::At(times) => {
+                let times: Vec<_> = times
+                    .iter()
+                    .map(|time| time.format("%H:%M").to_string())
+                    .collect();
+                f.write_str(&times.join(","))
+            }
+            Self::Cron(cron) => f.write_str(&cron.expression),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A cron expression with the fields minute, hour, day of month, month and
+/// day of week.
+///
+/// Fields are `*`, numbers, ranges like `1-5` and steps like `*/15`,
+/// separated by commas. Sunday is both 0 and 7. As in cron, a day matches
+/// either field when both the day of month and the day of week are given.
+pub struct Cron {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Parse a cron `field` of values from `min` to `max` into a bit set.
+fn field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut values = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("Invalid step in {field:?}"))?,
+            ),
+            None => (part, 1),
+        };
+        let number = |value: &str| {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|value| (min..=max).contains(value))
+                .ok_or_else(|| format!("Expected {min}-{max} in {field:?}, got {value:?}"))
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (number(first)?, number(last)?),
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if first > last {
+            return Err(format!("Invalid range in {field:?}"));
+        }
+        for value in (first..=last).step_by(step as usize) {
+            values |= 1 << value;
+        }
+    }
+    Ok(values)
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<_> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("Expected 5 fields in the cron expression {s:?}"));
+        };
+        let mut weekday_set = field(weekdays, 0, 7)?;
+        // Sunday is 7 too
+        if weekday_set & 1 << 7 != 0 {
+            weekday_set |= 1;
+        }
+        Ok(Self {
+            expression: fields.join(" "),
+            minutes: field(minutes, 0, 59)?,
+            hours: field(hours, 0, 23)?,
+            days: field(days, 1, 31)?,
+            months: field(months, 1, 12)?,
+            weekdays: weekday_set,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+}
+
+impl Cron {
+    const fn day_matches(&self, day: u32, weekday: u32) -> bool {
+        let day = self.days & 1 << day != 0;
+        let weekday = self.weekdays & 1 << weekday != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first time after `after` the expression matches.
+    fn next<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let start = after.naive_local();
+        (0..SEARCH_DAYS).find_map(|days| {
+            let date = start.date() + chrono::Days::new(days.into());
+            if self.months & 1 << date.month() == 0
+                || !self.day_matches(date.day(), date.weekday().num_days_from_sunday())
+            {
+                return None;
+            }
+            (0..24)
+                .filter(|hour| self.hours & 1 << hour != 0)
+                .flat_map(|hour| {
+                    (0..60)
+                        .filter(|minute| self.minutes & 1 << minute != 0)
+                        .map(move |minute| NaiveTime::from_hms_opt(hour, minute, 0))
+                })
+                .flatten()
+                .find_map(|time| local(after, date.and_time(time)).filter(|time| time > after))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, Utc};
+
+    use super::Schedule;
+
+    fn timestamp(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().into()
+    }
+
+    #[test]
+    fn test_next() {
+        let after = timestamp("2024-09-06T05:00:00Z"); // A Friday
+        let next = |schedule: &str| {
+            schedule
+                .parse::<Schedule>()
+                .unwrap()
+                .next(&after)
+                .map(|next| next.to_rfc3339())
+        };
+        assert_eq!(next("90m").as_deref(), Some("2024-09-06T06:30:00+00:00"));
+        assert_eq!(
+            next("17:00,05:00").as_deref(),
+            Some("2024-09-06T17:00:00+00:00")
+        );
+        assert_eq!(next("04:30").as_deref(), Some("2024-09-07T04:30:00+00:00"));
+        assert_eq!(
+            next("0 5 * * *").as_deref(),
+            Some("2024-09-07T05:00:00+00:00")
+        );
+        assert_eq!(
+            next("*/15 * * * *").as_deref(),
+            Some("2024-09-06T05:15:00+00:00")
+        );
+        assert_eq!(
+            next("30 6 * * 1-5").as_deref(),
+            Some("2024-09-06T06:30:00+00:00")
+        );
+        assert_eq!(
+            next("0 5 * * 0").as_deref(),
+            Some("2024-09-08T05:00:00+00:00")
+        );
+        assert_eq!(
+            next("0 5 * * 7").as_deref(),
+            Some("2024-09-08T05:00:00+00:00")
+        );
+        // Either the 1st or a Monday
+        assert_eq!(
+            next("0 5 1 * 1").as_deref(),
+            Some("2024-09-09T05:00:00+00:00")
+        );
+        assert_eq!(
+            next("0 0 29 2 *").as_deref(),
+            Some("2028-02-29T00:00:00+00:00")
+        );
+        assert_eq!(next("0 0 31 2 *"), None);
+    }
+}
@@ -0,0 +1,268 @@
+//! Windows service running postgang regularly, for `postgang service`.
+//!
+//! The service runs a job, postgang with the options it was installed with,
+//! as a child process when it starts and then on its [`Schedule`] until it
+//! is stopped. Runs that fail in a row are spread out further and further,
+//! up to [`BACKOFF`]'s maximum, and logged and kept as [`Failures`] for
+//! `postgang healthcheck` until a run succeeds again. Logs go to
+//! `--log-file` if the job is given one, as a service has no console.
+use core::time::Duration;
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        OnceLock,
+    },
+};
+
+use chrono::{Local, Utc};
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+
+use crate::{bring_client::retry::RetryPolicy, failures::Failures, schedule::Schedule};
+
+/// Name of the service.
+pub const NAME: &str = "postgang";
+const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+
+/// Least time between runs after failed ones, by the number of failures.
+pub const BACKOFF: RetryPolicy = RetryPolicy {
+    retries: u32::MAX,
+    base_delay: Duration::from_mins(30),
+    max_delay: Duration::from_hours(12),
+    jitter: false,
+};
+
+#[derive(Debug, Clone)]
+/// What the service runs.
+pub struct Job {
+    /// Program and arguments.
+    pub exec: Vec<String>,
+    pub schedule: Schedule,
+    /// Where the failed runs in a row are kept, see [`crate::failures::path`].
+    pub failures: Option<PathBuf>,
+}
+
+/// The job of this process, set before handing it over to the service
+/// control manager.
+static JOB: OnceLock<Job> = OnceLock::new();
+
+fn to_string(err: impl core::fmt::Display) -> String {
+    format!("Windows service: {err}")
+}
+
+fn manager(access: ServiceManagerAccess) -> Result<ServiceManager, String> {
+    ServiceManager::local_computer(None::<&str>, access).map_err(to_string)
+}
+
+/// Register the service, started at boot with `arguments`.
+///
+/// # Errors
+///
+/// Returns an error if the service exists or can't be created, which needs
+/// an administrator.
+pub fn install(arguments: Vec<String>) -> Result<(), String> {
+    let manager = manager(ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)?;
+    let info = ServiceInfo {
+        name: OsString::from(NAME),
+        display_name: OsString::from("Postgang"),
+        service_type: SERVICE_TYPE,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe().map_err(|err| err.to_string())?,
+        launch_arguments: arguments.into_iter().map(OsString::from).collect(),
+        dependencies: Vec::new(),
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager
+        .create_service(&info, ServiceAccess::CHANGE_CONFIG)
+        .map_err(to_string)?;
+    service
+        .set_description("Fetches mailbox delivery dates from Bring")
+        .map_err(to_string)
+}
+
+/// Remove the service, which is stopped first when running.
+///
+/// # Errors
+///
+/// Returns an error if the service isn't installed or can't be removed.
+pub fn uninstall() -> Result<(), String> {
+    let service = manager(ServiceManagerAccess::CONNECT)?
+        .open_service(
+            NAME,
+            ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+        )
+        .map_err(to_string)?;
+    if service.query_status().map_err(to_string)?.current_state != ServiceState::Stopped {
+        service.stop().map_err(to_string)?;
+    }
+    service.delete().map_err(to_string)
+}
+
+/// Start the installed service.
+///
+/// # Errors
+///
+/// Returns an error if the service isn't installed or fails to start.
+pub fn start() -> Result<(), String> {
+    manager(ServiceManagerAccess::CONNECT)?
+        .open_service(NAME, ServiceAccess::START)
+        .and_then(|service| service.start::<&str>(&[]))
+        .map_err(to_string)
+}
+
+/// Stop the running service.
+///
+/// # Errors
+///
+/// Returns an error if the service isn't installed or running.
+pub fn stop() -> Result<(), String> {
+    manager(ServiceManagerAccess::CONNECT)?
+        .open_service(NAME, ServiceAccess::STOP)
+        .and_then(|service| service.stop())
+        .map(|_| ())
+        .map_err(to_string)
+}
+
+/// Run `job` as the service, called by the service control manager.
+///
+/// # Errors
+///
+/// Returns an error if the process was not started as a service.
+pub fn run(job: Job) -> Result<(), String> {
+    JOB.set(job)
+        .map_err(|_| "The service is already running".to_string())?;
+    service_dispatcher::start(NAME, ffi_service_main).map_err(to_string)
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(err) = run_job() {
+        log::error!("{}", to_string(err));
+    }
+}
+
+fn set_state(
+    status: ServiceStatusHandle,
+    state: ServiceState,
+    controls_accepted: ServiceControlAccept,
+) -> windows_service::Result<()> {
+    status.set_service_status(ServiceStatus {
+        service_type: SERVICE_TYPE,
+        current_state: state,
+        controls_accepted,
+        exit_code: ServiceExitCode::Win32(0),
+        checkpoint: 0,
+        wait_hint: Duration::ZERO,
+        process_id: None,
+    })
+}
+
+/// Run `exec` and wait for it, returning whether it succeeded.
+fn run_once(exec: &[String]) -> bool {
+    let Some((program, args)) = exec.split_first() else {
+        return false;
+    };
+    match Command::new(program).args(args).status() {
+        Ok(exit) if exit.success() => true,
+        Ok(exit) => {
+            log::warn!("{program} exited with {exit}");
+            false
+        }
+        Err(err) => {
+            log::error!("{program}: {err}");
+            false
+        }
+    }
+}
+
+/// Keep `count` failed runs in a row at `path`, or forget them when the run
+/// succeeded.
+fn record_failures(path: &Path, count: u32) {
+    let result = if count == 0 {
+        Failures::clear(path)
+    } else {
+        Failures {
+            count,
+            last_failure: Utc::now(),
+        }
+        .save(path)
+    };
+    if let Err(err) = result {
+        log::error!("{err}");
+    }
+}
+
+/// Run the job on its schedule until the service is stopped.
+fn run_job() -> windows_service::Result<()> {
+    let Some(job) = JOB.get() else {
+        return Ok(());
+    };
+    let (stop, stopped) = mpsc::channel();
+    let status = service_control_handler::register(NAME, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            let _ = stop.send(());
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+    set_state(
+        status,
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+    )?;
+    // A restarted service keeps backing off
+    let mut failures = match job.failures.as_deref().map(Failures::load) {
+        Some(Ok(Some(failures))) => failures.count,
+        Some(Err(err)) => {
+            log::error!("{err}");
+            0
+        }
+        Some(Ok(None)) | None => 0,
+    };
+    loop {
+        if run_once(&job.exec) {
+            if failures > 0 {
+                log::info!("Recovered after {failures} failed runs");
+            }
+            failures = 0;
+        } else {
+            failures += 1;
+        }
+        if let Some(path) = &job.failures {
+            record_failures(path, failures);
+        }
+        let now = Local::now();
+        let Some(next) = job.schedule.next(&now) else {
+            log::error!("{} never runs again", job.schedule);
+            break;
+        };
+        let mut wait = (next - now).to_std().unwrap_or_default();
+        if failures > 0 {
+            wait = wait.max(BACKOFF.delay(failures - 1, 1.0));
+            log::warn!(
+                "{failures} runs in a row failed, running again in {}",
+                humantime::format_duration(Duration::from_secs(wait.as_secs()))
+            );
+        }
+        match stopped.recv_timeout(wait) {
+            Err(RecvTimeoutError::Timeout) => {}
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    set_state(status, ServiceState::Stopped, ServiceControlAccept::empty())
+}
@@ -0,0 +1,175 @@
+//! Questions asked by `postgang setup`, and the configuration written from
+//! the answers.
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    bring_client::NorwegianPostalCode,
+    config,
+    locale::{Locale, Setup},
+};
+
+/// Keyring service the API key is stored under, with the API user id as the
+/// user name.
+#[cfg(feature = "keyring")]
+const KEYRING_SERVICE: &str = "postgang";
+
+/// Ask `question` until the answer is accepted by `parse`.
+///
+/// An empty answer is `default` when there is one. Errors from `parse` are
+/// shown before asking again.
+///
+/// # Errors
+///
+/// Returns an error if reading or writing fails, or if `input` ends.
+pub fn ask<T>(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: Option<&str>,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> io::Result<T> {
+    loop {
+        match default {
+            Some(default) => write!(output, "{question} [{default}]: ")?,
+            None => write!(output, "{question}: ")?,
+        }
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let answer = match (line.trim(), default) {
+            ("", Some(default)) => default,
+            (answer, _) => answer,
+        };
+        match parse(answer) {
+            Ok(value) => return Ok(value),
+            Err(err) => writeln!(output, "{err}")?,
+        }
+    }
+}
+
+/// Ask a yes or no `question` in `locale`, `default` on an empty answer.
+///
+/// # Errors
+///
+/// Returns an error if reading or writing fails, or if `input` ends.
+pub fn confirm(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+    default: bool,
+    locale: Locale,
+) -> io::Result<bool> {
+    let hint = locale.setup(Setup::YesNo(default));
+    ask(
+        input,
+        output,
+        &format!("{question} [{hint}]"),
+        None,
+        |answer| match answer.to_lowercase().as_str() {
+            "" => Ok(default),
+            "j" | "ja" | "y" | "yes" => Ok(true),
+            "n" | "nei" | "no" => Ok(false),
+            _ => Err(locale.setup(Setup::NotYesNo)),
+        },
+    )
+}
+
+/// Answers to the questions of `postgang setup`.
+pub struct Answers {
+    pub api_uid: String,
+    /// `None` when the key is stored in the keyring.
+    pub api_key: Option<String>,
+    pub postal_code: NorwegianPostalCode,
+}
+
+/// The commented configuration template with the answers filled in.
+///
+/// ```
+/// use postgang::bring_client::NorwegianPostalCode;
+/// use postgang::config::Config;
+/// use postgang::setup::{config_content, Answers};
+///
+/// let content = config_content(&Answers {
+///     api_uid: "ola@example.com".to_string(),
+///     api_key: None,
+///     postal_code: NorwegianPostalCode::try_from("7800").unwrap(),
+/// })
+/// .unwrap();
+/// let config = Config::parse(&content).unwrap();
+/// assert_eq!(config.code[0].to_string(), "7800");
+/// assert_eq!(config.api_uid.unwrap().0, "ola@example.com");
+/// assert_eq!(config.api_key, None);
+/// assert!(content.starts_with("# Configuration of postgang"));
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the template can't be changed, which is a bug.
+pub fn config_content(answers: &Answers) -> Result<String, String> {
+    let content = config::set(
+        config::TEMPLATE,
+        "code",
+        &format!("[\"{}\"]", answers.postal_code),
+    )?;
+    let content = config::set(&content, "api-uid", &answers.api_uid)?;
+    match &answers.api_key {
+        Some(api_key) => config::set(&content, "api-key", api_key),
+        None => Ok(content),
+    }
+}
+
+/// Store `api_key` in the keyring of the user.
+///
+/// # Errors
+///
+/// Returns an error if there is no keyring or it refuses the key.
+#[cfg(feature = "keyring")]
+pub fn store_api_key(api_uid: &str, api_key: &str) -> Result<(), String> {
+    keyring::Entry::new(KEYRING_SERVICE, api_uid)
+        .and_then(|entry| entry.set_password(api_key))
+        .map_err(|err| format!("Keyring: {err}"))
+}
+
+#[must_use]
+/// The API key of `api_uid` stored in the keyring by `postgang setup`.
+#[cfg(feature = "keyring")]
+pub fn load_api_key(api_uid: &str) -> Option<String> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, api_uid).ok()?;
+    let result = entry.get_password();
+    match result {
+        Ok(api_key) => Some(api_key),
+        Err(keyring::Error::NoEntry) => None,
+        Err(err) => {
+            log::warn!("Keyring: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::{ask, confirm};
+    use crate::{bring_client::NorwegianPostalCode, locale::Locale};
+
+    #[test]
+    fn test_ask() {
+        let mut input = Cursor::new("78\n7800\n\nkanskje\nnei\n");
+        let mut output = Vec::new();
+        let postal_code = ask(&mut input, &mut output, "Postnummer", None, |answer| {
+            NorwegianPostalCode::try_from(answer).map_err(|err| err.to_string())
+        })
+        .unwrap();
+        assert_eq!(postal_code.to_string(), "7800");
+        let mut confirm = || confirm(&mut input, &mut output, "Lagre", true, Locale::Norwegian);
+        assert!(confirm().unwrap());
+        assert!(!confirm().unwrap());
+        assert!(confirm().is_err());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Postnummer: Invalid postal code format"));
+        assert!(output.contains("Lagre [J/n]: Svar j eller n.\nLagre [J/n]: "));
+    }
+}
@@ -0,0 +1,230 @@
+//! Email about changed delivery dates over SMTP, set up in the `[smtp]`
+//! section of the configuration.
+use core::{error::Error, str::FromStr};
+use std::path::PathBuf;
+
+use lettre::{
+    message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    Message, SmtpTransport, Transport as _,
+};
+
+use crate::{config, io_error_to_string, notify::Secret};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How the connection to the SMTP server is encrypted.
+pub enum Tls {
+    /// Upgrade the connection with `STARTTLS`, which is required.
+    #[default]
+    StartTls,
+    /// Connect with TLS.
+    Implicit,
+    /// Send in plain text, only for a server on the same host.
+    None,
+}
+
+impl Tls {
+    #[must_use]
+    /// Port used when none is configured.
+    pub const fn default_port(self) -> u16 {
+        match self {
+            Self::StartTls => 587,
+            Self::Implicit => 465,
+            Self::None => 25,
+        }
+    }
+}
+
+impl FromStr for Tls {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "starttls" => Ok(Self::StartTls),
+            "tls" => Ok(Self::Implicit),
+            "none" => Ok(Self::None),
+            _ => Err(format!(
+                "Unknown TLS mode {s:?}, expected starttls, tls or none"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An SMTP server and who to send email to.
+pub struct Mailer {
+    host: String,
+    port: u16,
+    tls: Tls,
+    credentials: Option<(String, Secret)>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    /// Calendar attached to every email.
+    attachment: Option<PathBuf>,
+}
+
+impl Mailer {
+    /// Set up sending email as configured by `smtp`, attaching the calendar
+    /// at `attachment`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an address or the TLS mode is invalid, there are
+    /// no recipients, or only one of the username and the password is given.
+    pub fn new(smtp: &config::Smtp, attachment: Option<PathBuf>) -> Result<Self, String> {
+        let mailbox = |field, address: &str| {
+            address
+                .parse::<Mailbox>()
+                .map_err(|err| format!("smtp.{field}: {err}: {address:?}"))
+        };
+        let tls: Tls = smtp
+            .tls
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|err| format!("smtp.tls: {err}"))?
+            .unwrap_or_default();
+        let credentials = match (&smtp.username, &smtp.password) {
+            (Some(username), Some(password)) => Some((username.clone(), password.clone())),
+            (None, None) => None,
+            _ => return Err("smtp: Give both username and password, or neither".to_string()),
+        };
+        if smtp.to.is_empty() {
+            return Err("smtp.to: No recipients".to_string());
+        }
+        Ok(Self {
+            host: smtp.host.clone(),
+            port: smtp.port.unwrap_or_else(|| tls.default_port()),
+            tls,
+            credentials,
+            from: mailbox("from", &smtp.from)?,
+            to: smtp
+                .to
+                .iter()
+                .map(|address| mailbox("to", address))
+                .collect::<Result<_, _>>()?,
+            attachment,
+        })
+    }
+
+    /// The email with `subject` and the text `message`, and the calendar
+    /// attached if there is one.
+    fn email(&self, subject: &str, message: &str) -> Result<Message, Box<dyn Error>> {
+        let mut builder = Message::builder().from(self.from.clone()).subject(subject);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let text = SinglePart::plain(message.to_string());
+        let Some(path) = &self.attachment else {
+            return Ok(builder.singlepart(text)?);
+        };
+        let calendar = std::fs::read(path).map_err(|err| io_error_to_string(&err, path))?;
+        let name = path
+            .file_name()
+            .map_or_else(|| "postgang.ics".into(), |name| name.to_string_lossy());
+        let attachment = Attachment::new(name.into_owned()).body(
+            calendar,
+            ContentType::parse("text/calendar; charset=utf-8")?,
+        );
+        Ok(builder.multipart(MultiPart::mixed().singlepart(text).singlepart(attachment))?)
+    }
+
+    /// Send an email with `subject` and the text `message`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the calendar could not be read or the server
+    /// could not be reached or refused the email.
+    pub fn send(&self, subject: &str, message: &str) -> Result<(), Box<dyn Error>> {
+        let email = self.email(subject, message)?;
+        let mut transport = match self.tls {
+            Tls::StartTls => SmtpTransport::starttls_relay(&self.host)?,
+            Tls::Implicit => SmtpTransport::relay(&self.host)?,
+            Tls::None => SmtpTransport::builder_dangerous(&self.host),
+        }
+        .port(self.port);
+        if let Some((username, password)) = &self.credentials {
+            transport =
+                transport.credentials(Credentials::new(username.clone(), password.0.clone()));
+        }
+        transport.build().send(&email)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{Mailer, Tls};
+    use crate::{config, notify::Secret};
+
+    fn smtp() -> config::Smtp {
+        config::Smtp {
+            host: "smtp.example.com".to_string(),
+            port: None,
+            tls: Some("tls".to_string()),
+            username: Some("ola".to_string()),
+            password: Some(Secret("hemmelig".to_string())),
+            from: "Postgang <ola@example.com>".to_string(),
+            to: vec![
+                "ola@example.com".to_string(),
+                "kari@example.com".to_string(),
+            ],
+            attach_calendar: true,
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let mailer = Mailer::new(&smtp(), None).unwrap();
+        assert_eq!(mailer.tls, Tls::Implicit);
+        assert_eq!(mailer.port, 465);
+        assert_eq!(mailer.to.len(), 2);
+        let invalid = [
+            config::Smtp {
+                tls: Some("ssl".to_string()),
+                ..smtp()
+            },
+            config::Smtp {
+                password: None,
+                ..smtp()
+            },
+            config::Smtp {
+                to: vec![],
+                ..smtp()
+            },
+            config::Smtp {
+                from: "not an address".to_string(),
+                ..smtp()
+            },
+        ];
+        for smtp in invalid {
+            assert!(Mailer::new(&smtp, None).is_err(), "{smtp:?}");
+        }
+    }
+
+    #[test]
+    fn test_email() {
+        let name = format!("postgang-smtp-{}.ics", std::process::id());
+        let path = std::env::temp_dir().join(&name);
+        std::fs::write(&path, "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n").unwrap();
+        let mailer = Mailer::new(&smtp(), Some(path.clone())).unwrap();
+        let email = mailer
+            .email("Postgang", "7800: nye leveringsdager\n")
+            .unwrap();
+        let email = String::from_utf8(email.formatted()).unwrap();
+        assert!(
+            email.contains("To: ola@example.com, kari@example.com"),
+            "{email}"
+        );
+        assert!(
+            email.contains("Content-Type: text/calendar; charset=utf-8"),
+            "{email}"
+        );
+        assert!(email.contains(&format!("filename=\"{name}\"")), "{email}");
+        let mailer = Mailer::new(&smtp(), Some(PathBuf::from("/nonexistent.ics"))).unwrap();
+        assert!(mailer.email("Postgang", "").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
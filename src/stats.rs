@@ -0,0 +1,160 @@
+//! Delivery cadence statistics.
+use core::fmt;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::Serialize;
+
+use crate::{bring_client::NorwegianPostalCode, calendar::weekday};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// The days between two consecutive deliveries.
+pub struct Gap {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub days: i64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+/// Statistics for the delivery dates of a postal code.
+pub struct Stats {
+    pub postal_code: NorwegianPostalCode,
+    pub deliveries: usize,
+    pub first: Option<NaiveDate>,
+    pub last: Option<NaiveDate>,
+    /// Average number of days between deliveries.
+    pub average_gap: Option<f64>,
+    pub longest_gap: Option<Gap>,
+    /// Number of deliveries per weekday, starting on Monday.
+    pub weekdays: [usize; 7],
+}
+
+impl Stats {
+    #[must_use]
+    /// Compute statistics for `dates`, which must be sorted and unique.
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use postgang::bring_client::NorwegianPostalCode;
+    /// use postgang::stats::Stats;
+    ///
+    /// let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+    /// let day = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+    /// let stats = Stats::new(postal_code, &[day(2), day(4), day(9)]);
+    /// assert_eq!(stats.deliveries, 3);
+    /// assert_eq!(stats.average_gap, Some(3.5));
+    /// assert_eq!(stats.longest_gap.map(|gap| gap.days), Some(5));
+    /// assert_eq!(stats.weekdays, [2, 0, 1, 0, 0, 0, 0]);
+    /// ```
+    pub fn new(postal_code: NorwegianPostalCode, dates: &[NaiveDate]) -> Self {
+        let mut weekdays = [0; 7];
+        for date in dates {
+            weekdays[date.weekday().num_days_from_monday() as usize] += 1;
+        }
+        let longest_gap = dates
+            .windows(2)
+            .map(|pair| Gap {
+                from: pair[0],
+                to: pair[1],
+                days: (pair[1] - pair[0]).num_days(),
+            })
+            .max_by_key(|gap| (gap.days, core::cmp::Reverse(gap.from)));
+        let first = dates.first().copied();
+        let last = dates.last().copied();
+        #[allow(clippy::cast_precision_loss)]
+        let average_gap = match (first, last) {
+            (Some(first), Some(last)) if dates.len() > 1 => {
+                Some((last - first).num_days() as f64 / (dates.len() - 1) as f64)
+            }
+            _ => None,
+        };
+        Self {
+            postal_code,
+            deliveries: dates.len(),
+            first,
+            last,
+            average_gap,
+            longest_gap,
+            weekdays,
+        }
+    }
+}
+
+#[must_use]
+/// Number of days from `today` until the last of `dates`, `None` without
+/// dates.
+///
+/// A short horizon usually means something is wrong with the API.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use postgang::stats::horizon;
+///
+/// let day = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+/// assert_eq!(horizon(&[day(4), day(12), day(6)], day(2)), Some(10));
+/// assert_eq!(horizon(&[], day(2)), None);
+/// ```
+pub fn horizon(dates: &[NaiveDate], today: NaiveDate) -> Option<i64> {
+    dates.iter().max().map(|last| (*last - today).num_days())
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.postal_code)?;
+        write!(f, "  Leveringer: {}", self.deliveries)?;
+        if let (Some(first), Some(last)) = (self.first, self.last) {
+            write!(f, " ({first} – {last})")?;
+        }
+        writeln!(f)?;
+        if let Some(average_gap) = self.average_gap {
+            writeln!(f, "  Gjennomsnittlig opphold: {average_gap:.1} dager")?;
+        }
+        if let Some(Gap { from, to, days }) = self.longest_gap {
+            writeln!(f, "  Lengste opphold: {days} dager ({from} – {to})")?;
+        }
+        let mut day = Weekday::Mon;
+        for count in self.weekdays {
+            // Any date with the right weekday will do for the name
+            let date = NaiveDate::from_isoywd_opt(2024, 1, day).unwrap_or_default();
+            writeln!(f, "  {}: {count}", weekday(date))?;
+            day = day.succ();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use super::Stats;
+    use crate::bring_client::NorwegianPostalCode;
+
+    #[test]
+    fn test_empty() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let stats = Stats::new(postal_code, &[]);
+        assert_eq!(stats.deliveries, 0);
+        assert_eq!(stats.average_gap, None);
+        assert_eq!(stats.longest_gap, None);
+        assert_eq!(
+            stats.to_string(),
+            "7800\n  Leveringer: 0\n  mandag: 0\n  tirsdag: 0\n  onsdag: 0\n  torsdag: 0\n  \
+             fredag: 0\n  lørdag: 0\n  søndag: 0\n"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let day = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+        let stats = Stats::new(postal_code, &[day(2), day(4), day(6), day(11)]);
+        assert_eq!(
+            stats.to_string(),
+            "7800\n  Leveringer: 4 (2024-09-02 – 2024-09-11)\n  \
+             Gjennomsnittlig opphold: 3.0 dager\n  \
+             Lengste opphold: 5 dager (2024-09-06 – 2024-09-11)\n  \
+             mandag: 1\n  tirsdag: 0\n  onsdag: 2\n  torsdag: 0\n  \
+             fredag: 1\n  lørdag: 0\n  søndag: 0\n"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! systemd service and timer running postgang, for `postgang generate
+//! systemd`.
+use core::{fmt::Write as _, time::Duration};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Contents of `postgang.service` and `postgang.timer`.
+pub struct Units {
+    pub service: String,
+    pub timer: String,
+}
+
+/// Quote `arg` for `ExecStart`, escaping specifiers and variables.
+///
+/// ```
+/// use postgang::systemd::quote;
+///
+/// assert_eq!(quote("--code=7800"), "--code=7800");
+/// assert_eq!(quote("Posten på hytta"), "\"Posten på hytta\"");
+/// assert_eq!(quote("100%"), "100%%");
+/// assert_eq!(quote("$HOME"), "$$HOME");
+/// assert_eq!(quote(""), "\"\"");
+/// ```
+#[must_use]
+pub fn quote(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    if arg.is_empty()
+        || arg
+            .chars()
+            .any(|c| c.is_whitespace() || "'\";\\".contains(c))
+    {
+        format!("\"{escaped}\"")
+    } else {
+        escaped
+    }
+}
+
+#[must_use]
+/// Units running `exec` from `working_directory` every `interval`.
+///
+/// System units keep the history and cache in `/var/lib/postgang` and
+/// `/var/cache/postgang`, user units in the XDG directories of the user.
+///
+/// ```
+/// use core::time::Duration;
+/// use std::path::Path;
+///
+/// use postgang::systemd::units;
+///
+/// let exec = ["/usr/bin/postgang", "--code", "7800", "api"].map(String::from);
+/// let units = units(&exec, Path::new("/srv"), Duration::from_hours(6), true);
+/// assert!(units
+///     .service
+///     .contains("\nExecStart=/usr/bin/postgang --code 7800 api\n"));
+/// assert!(units.timer.contains("\nOnUnitActiveSec=6h\n"));
+/// assert!(units.timer.contains("\nWantedBy=timers.target\n"));
+/// ```
+pub fn units(exec: &[String], working_directory: &Path, interval: Duration, user: bool) -> Units {
+    let mut service = String::from("[Unit]\nDescription=Fetch mailbox delivery dates\n");
+    // The user manager has no network-online.target
+    if !user {
+        service.push_str("Wants=network-online.target\nAfter=network-online.target\n");
+    }
+    service.push_str("\n[Service]\nType=oneshot\n");
+    // Writing to a String can't fail
+    let _ = writeln!(
+        service,
+        "WorkingDirectory={}",
+        quote(&working_directory.to_string_lossy())
+    );
+    let exec = exec.iter().map(|arg| quote(arg)).collect::<Vec<_>>();
+    let _ = writeln!(service, "ExecStart={}", exec.join(" "));
+    if !user {
+        service.push_str(
+            "Environment=XDG_STATE_HOME=/var/lib XDG_CACHE_HOME=/var/cache\n\
+             StateDirectory=postgang\n\
+             CacheDirectory=postgang\n",
+        );
+    }
+    let mut timer = String::from(
+        "[Unit]\n\
+         Description=Fetch mailbox delivery dates regularly\n\
+         \n\
+         [Timer]\n\
+         OnBootSec=2min\n",
+    );
+    let _ = writeln!(
+        timer,
+        "OnUnitActiveSec={}",
+        humantime::format_duration(interval)
+    );
+    timer.push_str(
+        "RandomizedDelaySec=5min\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+    );
+    Units { service, timer }
+}
+
+#[cfg(test)]
+mod test {
+    use core::time::Duration;
+    use std::path::Path;
+
+    use super::units;
+
+    #[test]
+    fn test_system_units() {
+        let exec = [
+            "/usr/bin/postgang",
+            "--output",
+            "/var/www/post gang.ics",
+            "api",
+        ]
+        .map(String::from);
+        let units = units(&exec, Path::new("/"), Duration::from_mins(90), false);
+        assert_eq!(
+            units.service,
+            "[Unit]\n\
+             Description=Fetch mailbox delivery dates\n\
+             Wants=network-online.target\n\
+             After=network-online.target\n\
+             \n\
+             [Service]\n\
+             Type=oneshot\n\
+             WorkingDirectory=/\n\
+             ExecStart=/usr/bin/postgang --output \"/var/www/post gang.ics\" api\n\
+             Environment=XDG_STATE_HOME=/var/lib XDG_CACHE_HOME=/var/cache\n\
+             StateDirectory=postgang\n\
+             CacheDirectory=postgang\n"
+        );
+        assert!(units.timer.contains("\nOnUnitActiveSec=1h 30m\n"));
+    }
+}
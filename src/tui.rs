@@ -0,0 +1,248 @@
+//! Month view of the delivery dates in the history, for `postgang tui`.
+//!
+//! The history is read again on `r`, so a timer fetching new dates in the
+//! background shows up without restarting the viewer.
+use alloc::collections::BTreeSet;
+use core::error::Error;
+use std::path::PathBuf;
+
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    Frame,
+};
+
+use crate::{
+    bring_client::{mailbox_delivery_dates::DeliveryDate, NorwegianPostalCode},
+    calendar::{month, relative_summary},
+    history::History,
+    holidays,
+};
+
+const HELP: &str = "←/→ måned  tab postnummer  t i dag  r last på nytt  q avslutt";
+
+/// State of the viewer.
+pub struct App {
+    history: PathBuf,
+    postal_codes: Vec<NorwegianPostalCode>,
+    selected: usize,
+    /// First day of the shown month.
+    month: NaiveDate,
+    today: NaiveDate,
+    dates: BTreeSet<NaiveDate>,
+    error: Option<String>,
+}
+
+impl App {
+    #[must_use]
+    /// Show the dates recorded in `history` for `postal_codes`, starting
+    /// with the month of `today`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `postal_codes` is empty.
+    pub fn new(history: PathBuf, postal_codes: Vec<NorwegianPostalCode>, today: NaiveDate) -> Self {
+        assert!(!postal_codes.is_empty(), "No postal codes to show");
+        let mut app = Self {
+            history,
+            postal_codes,
+            selected: 0,
+            month: today.with_day(1).unwrap_or(today),
+            today,
+            dates: BTreeSet::new(),
+            error: None,
+        };
+        app.reload();
+        app
+    }
+
+    fn postal_code(&self) -> NorwegianPostalCode {
+        self.postal_codes[self.selected]
+    }
+
+    /// Read the dates of the selected postal code from the history.
+    pub fn reload(&mut self) {
+        match History::open(self.history.clone()) {
+            Ok(history) => {
+                self.dates = history
+                    .dates(self.postal_code())
+                    .map(|(date, _)| date)
+                    .collect();
+                self.error = None;
+            }
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    /// Handle `key`, returning whether to quit.
+    pub fn key(&mut self, key: KeyCode) -> bool {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Left | KeyCode::Char('h') => {
+                self.month = self.month - Months::new(1);
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                self.month = self.month + Months::new(1);
+            }
+            KeyCode::Char('t') => self.month = self.today.with_day(1).unwrap_or(self.today),
+            KeyCode::Tab | KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1) % self.postal_codes.len();
+                self.reload();
+            }
+            KeyCode::BackTab | KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self
+                    .selected
+                    .checked_sub(1)
+                    .unwrap_or(self.postal_codes.len() - 1);
+                self.reload();
+            }
+            KeyCode::Char('r') => self.reload(),
+            _ => {}
+        }
+        false
+    }
+
+    fn day(&self, date: NaiveDate) -> Span<'static> {
+        let mut style = Style::default();
+        if holidays::holiday(date).is_some() || date.weekday() == chrono::Weekday::Sun {
+            style = style.fg(Color::Red);
+        }
+        if self.dates.contains(&date) {
+            style = style
+                .fg(Color::Black)
+                .bg(Color::Green)
+                .add_modifier(Modifier::BOLD);
+        }
+        if date == self.today {
+            style = style.add_modifier(Modifier::UNDERLINED);
+        }
+        Span::styled(format!("{:>2}", date.day()), style)
+    }
+
+    /// Lines of the month view, one per week starting on Monday.
+    fn month_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = vec![Line::from("uke  ma ti on to fr lø sø").bold()];
+        let first = self.month;
+        let mut monday = first - Days::new(first.weekday().num_days_from_monday().into());
+        while monday.month() == first.month() || monday < first {
+            let mut spans = vec![Span::raw(format!("{:>3}  ", monday.iso_week().week()))];
+            for offset in 0..7 {
+                let date = monday + Days::new(offset);
+                if offset > 0 {
+                    spans.push(Span::raw(" "));
+                }
+                if date.month() == first.month() {
+                    spans.push(self.day(date));
+                } else {
+                    spans.push(Span::raw("  "));
+                }
+            }
+            lines.push(Line::from(spans));
+            monday = monday + Days::new(7);
+        }
+        lines
+    }
+
+    fn status(&self) -> String {
+        if let Some(error) = &self.error {
+            return error.clone();
+        }
+        self.dates.range(self.today..).next().map_or_else(
+            || "Ingen kommende leveringsdager i historikken.".to_string(),
+            |&date| relative_summary(DeliveryDate::new(self.postal_code(), date), self.today),
+        )
+    }
+
+    /// Draw the viewer on `frame`.
+    pub fn draw(&self, frame: &mut Frame<'_>) {
+        let [calendar, status, help] = Layout::vertical([
+            Constraint::Length(10),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let title = format!(
+            " {} · {} {} ",
+            self.postal_code(),
+            month(self.month),
+            self.month.year()
+        );
+        frame.render_widget(
+            Paragraph::new(self.month_lines()).block(Block::bordered().title(title)),
+            calendar,
+        );
+        frame.render_widget(Paragraph::new(self.status()), status);
+        frame.render_widget(Paragraph::new(HELP).dim(), help);
+    }
+}
+
+/// Show the viewer until the user quits.
+///
+/// # Errors
+///
+/// Returns an error if the terminal can't be drawn on or read from.
+pub fn run(history: PathBuf, postal_codes: Vec<NorwegianPostalCode>) -> Result<(), Box<dyn Error>> {
+    let mut app = App::new(history, postal_codes, Local::now().date_naive());
+    let mut terminal = ratatui::init();
+    let result = loop {
+        if let Err(err) = terminal.draw(|frame| app.draw(frame)) {
+            break Err(err);
+        }
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                if app.key(key.code) {
+                    break Ok(());
+                }
+            }
+            Ok(_) => {}
+            Err(err) => break Err(err),
+        }
+    };
+    ratatui::restore();
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use ratatui::{backend::TestBackend, crossterm::event::KeyCode, style::Color, Terminal};
+
+    use super::App;
+    use crate::{bring_client::NorwegianPostalCode, history::History};
+
+    #[test]
+    fn test_draw() {
+        let path = std::env::temp_dir().join(format!("postgang-tui-{}.json", std::process::id()));
+        let postal_code = NorwegianPostalCode::try_from("7800").unwrap();
+        let day = |day| NaiveDate::from_ymd_opt(2024, 9, day).unwrap();
+        let mut history = History::open(path.clone()).unwrap();
+        history.record(
+            postal_code,
+            &[day(4)],
+            "2024-09-01T12:00:00Z".parse().unwrap(),
+        );
+        history.save().unwrap();
+
+        let mut app = App::new(path.clone(), vec![postal_code], day(3));
+        std::fs::remove_file(&path).unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let line = |y| (0..40).map(|x| buffer[(x, y)].symbol()).collect::<String>();
+        assert!(line(0).contains("7800 · september 2024"));
+        assert!(line(2).contains(" 35                     1"));
+        assert!(line(3).contains(" 36   2  3  4  5  6  7  8"));
+        // Wednesday 4 September is the delivery day.
+        assert_eq!(buffer[(13, 3)].symbol(), "4");
+        assert_eq!(buffer[(13, 3)].bg, Color::Green);
+        assert!(line(10).contains("7800: Posten kommer i morgen."));
+
+        assert!(!app.key(KeyCode::Right));
+        assert_eq!(app.month, NaiveDate::from_ymd_opt(2024, 10, 1).unwrap());
+        assert!(app.key(KeyCode::Char('q')));
+    }
+}